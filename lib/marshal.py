@@ -1,23 +1,581 @@
+import base64
+import copy
 import dataclasses
+import datetime
+import math
+import re
+import warnings
 
 # this is an internal implementation detail of dataclasses
 from dataclasses import _FIELDS as DATACLASS_FIELDS, _FIELD as DATACLASS_FIELD
-from dataclasses import Field
+from dataclasses import Field, field
 from inspect import get_annotations
 from types import UnionType
-from typing import Any, Self, get_origin, get_args
+from typing import (
+    Annotated,
+    Any,
+    Callable,
+    Self,
+    Union,
+    get_origin,
+    get_args,
+    get_type_hints,
+)
+
+# checked by generated code when it's imported
+# see `runtime::VERSION`
+MARSHAL_RUNTIME_VERSION = 9
 
 ENUM_VARIANT_UNIT = 0
 ENUM_VARIANT_TUPLE = 1
 ENUM_VARIANT_STRUCT = 2
 
 
+class Absent:
+    """
+    The outer `None` of a `double_option` field, which was missing from the data
+
+    An inner `None` is `null` in the data
+    """
+
+    def __repr__(self):
+        return "ABSENT"
+
+    def __bool__(self):
+        return False
+
+    # there's only ever the one instance
+    def __copy__(self):
+        return self
+
+    def __deepcopy__(self, memo):
+        return self
+
+
+ABSENT = Absent()
+
+
+class Check:
+    """
+    `Annotated` metadata for values Python's types are too loose for, like a `char`
+
+    They're checked when they're serialized and deserialized
+    """
+
+    def __init__(self, rust: str, test: Callable[[Any], bool]):
+        self.rust = rust
+        self.test = test
+
+    def __repr__(self):
+        return f"Check({self.rust!r})"
+
+    def check(self, value: Any) -> Any:
+        if not self.test(value):
+            raise ValueError(f"{value!r} isn't a valid `{self.rust}`")
+
+        return value
+
+
+CHAR = Check("char", lambda value: isinstance(value, str) and len(value) == 1)
+
+
+def integer(rust: str, bits: int, signed: bool) -> Check:
+    low, high = (-(2 ** (bits - 1)), 2 ** (bits - 1) - 1) if signed else (0, 2**bits - 1)
+
+    # `bool` is an `int` to Python, but not to serde
+    return Check(
+        rust,
+        lambda value: isinstance(value, int)
+        and not isinstance(value, bool)
+        and low <= value <= high,
+    )
+
+
+I8 = integer("i8", 8, signed=True)
+I16 = integer("i16", 16, signed=True)
+I32 = integer("i32", 32, signed=True)
+I64 = integer("i64", 64, signed=True)
+I128 = integer("i128", 128, signed=True)
+U8 = integer("u8", 8, signed=False)
+U16 = integer("u16", 16, signed=False)
+U32 = integer("u32", 32, signed=False)
+U64 = integer("u64", 64, signed=False)
+U128 = integer("u128", 128, signed=False)
+
+# assuming a 64-bit target
+ISIZE = integer("isize", 64, signed=True)
+USIZE = integer("usize", 64, signed=False)
+
+
+def is_number(value: Any) -> bool:
+    return isinstance(value, int | float) and not isinstance(value, bool)
+
+
+# JSON has no infinities or NaN, and larger numbers would be infinite as an `f32`
+F32_MAX = 3.4028234663852886e38
+
+F32 = Check("f32", lambda value: is_number(value) and abs(value) <= F32_MAX)
+F64 = Check("f64", lambda value: is_number(value) and math.isfinite(value))
+
+
+class Encoding:
+    """
+    `Annotated` metadata for values with a format of their own, like bytes as base64
+    """
+
+    def __init__(self, name: str, encode: Callable[[Any], Any], decode: Callable[[Any], Any]):
+        self.name = name
+        self.encode = encode
+        self.decode = decode
+
+    def __repr__(self):
+        return f"Encoding({self.name!r})"
+
+
+def decode_bytes(data: Any) -> bytes:
+    # `bytes` checks the numbers are bytes
+    if isinstance(data, str):
+        raise ValueError(f"cannot deserialize {data!r} as bytes")
+
+    return bytes(data)
+
+
+def base64_encoding(name: str, alphabet: bytes | None, padded: bool) -> Encoding:
+    def encode(value: bytes) -> str:
+        text = base64.b64encode(value, altchars=alphabet).decode("ascii")
+
+        return text if padded else text.rstrip("=")
+
+    def decode(data: str) -> bytes:
+        # `b64decode` takes the standard alphabet's characters as well as `altchars`
+        if alphabet and re.search("[+/]", data):
+            raise ValueError(f"{data!r} isn't valid {name}")
+
+        # padding is optional when decoding
+        data = data.rstrip("=") + "=" * (-len(data.rstrip("=")) % 4)
+
+        return base64.b64decode(data, altchars=alphabet, validate=True)
+
+    return Encoding(name, encode, decode)
+
+
+# bytes serialized with `serialize_bytes`, as JSON does, or as binary formats do
+BYTE_ARRAY = Encoding("byte array", encode=list, decode=decode_bytes)
+RAW_BYTES = Encoding("raw bytes", encode=bytes, decode=decode_bytes)
+
+# `serde_with`'s `Base64<Alphabet, Padding>`
+BASE64 = base64_encoding("base64", None, padded=True)
+BASE64_UNPADDED = base64_encoding("unpadded base64", None, padded=False)
+BASE64_URL_SAFE = base64_encoding("URL safe base64", b"-_", padded=True)
+BASE64_URL_SAFE_UNPADDED = base64_encoding("unpadded URL safe base64", b"-_", padded=False)
+
+
+class Json:
+    """
+    `Annotated` metadata for `serde_json::Value`, which is used as it is
+    """
+
+    def __repr__(self):
+        return "JSON"
+
+
+JSON = Json()
+
+# `serde_json::Value`
+JsonValue = Annotated[
+    dict[str, "JsonValue"] | list["JsonValue"] | str | int | float | bool | None, JSON
+]
+
+
+# hooks for types mapped from Rust, keyed by class
+CODECS: dict[type, tuple[Callable | None, Callable | None]] = {}
+
+
+def register_codec(klass: type, encode: Callable | None = None, decode: Callable | None = None):
+    """
+    Serialize values of `klass` with `encode`, and deserialize them with `decode`
+
+    Without a hook the value is used as it is
+    """
+
+    CODECS[klass] = (encode, decode)
+
+
+def encode_plain(value: Any) -> Any:
+    """
+    Turns what `dataclasses.asdict` leaves alone into plain data, mapped types and sets
+    """
+
+    # subclasses too, `pathlib.Path()` is a `PosixPath`
+    for klass in type(value).__mro__:
+        if codec := CODECS.get(klass):
+            if codec[0]:
+                return codec[0](value)
+
+            break
+
+    if isinstance(value, list | set | frozenset):
+        return [encode_plain(item) for item in value]
+
+    if isinstance(value, tuple):
+        return tuple(encode_plain(item) for item in value)
+
+    if isinstance(value, dict):
+        return {key: encode_plain(item) for key, item in value.items()}
+
+    return value
+
+
+def int_key(key: Any) -> Any:
+    """
+    An integer map key, which JSON writes as its digits
+    """
+    if isinstance(key, str) and re.fullmatch("-?[0-9]+", key):
+        return int(key)
+
+    return key
+
+
+def is_int(ty: Any) -> bool:
+    """
+    `ty` is an integer type, `Annotated[int, U8]`
+    """
+    return get_origin(ty) is Annotated and get_args(ty)[0] is int
+
+
+def to_set(klass: type, items: list) -> set | frozenset:
+    """
+    The items of a Rust set, which keeps the first of any duplicates
+    """
+
+    value = klass(items)
+
+    if len(value) < len(items):
+        warnings.warn(f"{len(items) - len(value)} duplicate items in a set were dropped")
+
+    return value
+
+
+### Hooks for the built-in mappings
+
+
+# the fraction of a second, with as many digits as it takes
+def fraction(nanos: int, groups: bool) -> str:
+    if groups:
+        # chrono, in milli, micro or nanoseconds
+        for digits in (3, 6, 9):
+            if nanos % 10 ** (9 - digits) == 0:
+                return f".{nanos // 10 ** (9 - digits):0{digits}}" if nanos else ""
+
+    # time, at least one digit
+    return "." + f"{nanos:09}".rstrip("0") if nanos else ".0"
+
+
+def utc_offset(value: datetime.datetime, seconds: bool) -> str:
+    offset = int(value.utcoffset().total_seconds())
+    sign = "-" if offset < 0 else "+"
+    hours, rest = divmod(abs(offset), 3600)
+    minutes, secs = divmod(rest, 60)
+
+    if seconds:
+        return f"{sign}{hours:02}:{minutes:02}:{secs:02}"
+
+    return f"{sign}{hours:02}:{minutes:02}" + (f":{secs:02}" if secs else "")
+
+
+def encode_datetime(value: datetime.datetime) -> str:
+    """
+    chrono's format, RFC 3339 like `2024-05-01T12:30:00.250Z`, naive without an offset
+    """
+
+    text = value.replace(microsecond=0, tzinfo=None).isoformat()
+    text += fraction(value.microsecond * 1000, groups=True)
+
+    if value.tzinfo is None:
+        return text
+
+    if value.tzinfo is datetime.timezone.utc:
+        return text + "Z"
+
+    return text + utc_offset(value, seconds=False)
+
+
+def decode_datetime(data: str) -> datetime.datetime:
+    # Python keeps microseconds, and older versions don't read `Z`
+    data = re.sub(r"(\.\d{6})\d+", r"\1", data)
+    data = re.sub(r"[zZ]$", "+00:00", data)
+
+    return datetime.datetime.fromisoformat(data)
+
+
+def encode_date(value: datetime.date) -> str:
+    return value.isoformat()
+
+
+def decode_date(data: str) -> datetime.date:
+    return datetime.date.fromisoformat(data)
+
+
+OFFSET_DATETIME = re.compile(
+    r"([+-]?\d+)-(\d+)-(\d+) (\d+):(\d+):(\d+)(?:\.(\d+))? ([+-])(\d+):(\d+):(\d+)"
+)
+
+
+def encode_offset_datetime(value: datetime.datetime) -> str:
+    """
+    `time`'s format, like `2024-05-01 12:30:00.25 +00:00:00`
+    """
+
+    if value.tzinfo is None:
+        raise ValueError(f"{value!r} has no UTC offset")
+
+    date = f"{value.year:04}-{value.month:02}-{value.day:02}"
+    time = f"{value.hour:02}:{value.minute:02}:{value.second:02}"
+    time += fraction(value.microsecond * 1000, groups=False)
+
+    return f"{date} {time} {utc_offset(value, seconds=True)}"
+
+
+def decode_offset_datetime(data: str | list) -> datetime.datetime:
+    # formats other than JSON, or without `time`'s `serde-human-readable` feature
+    # `(year, ordinal, hour, minute, second, nanosecond, offset hours, minutes, seconds)`
+    if isinstance(data, list):
+        year, ordinal, hour, minute, second, nanos, *offset = data
+        date = datetime.date(year, 1, 1) + datetime.timedelta(days=ordinal - 1)
+        hours, minutes, seconds = offset
+    else:
+        if not (match := OFFSET_DATETIME.fullmatch(data)):
+            raise ValueError(f"cannot deserialize {data} as an `OffsetDateTime`")
+
+        year, month, day, hour, minute, second, subsecond, sign, *offset = match.groups()
+        date = datetime.date(int(year), int(month), int(day))
+        nanos = int((subsecond or "0").ljust(9, "0")[:9])
+        hours, minutes, seconds = (int(f"{sign}{part}") for part in offset)
+
+    offset = datetime.timedelta(hours=hours, minutes=minutes, seconds=seconds)
+    time = datetime.time(
+        int(hour), int(minute), int(second), nanos // 1000, datetime.timezone(offset)
+    )
+
+    return datetime.datetime.combine(date, time)
+
+
+def encode_duration(value: datetime.timedelta) -> dict:
+    """
+    `std::time::Duration`, `{"secs": 1, "nanos": 500000000}`
+    """
+
+    if value < datetime.timedelta(0):
+        raise ValueError(f"{value!r} is negative, `Duration`s can't be")
+
+    return {
+        "secs": value.days * 86400 + value.seconds,
+        "nanos": value.microseconds * 1000,
+    }
+
+
+def decode_duration(data: dict | list) -> datetime.timedelta:
+    secs, nanos = (data["secs"], data["nanos"]) if isinstance(data, dict) else data
+
+    return datetime.timedelta(seconds=secs, microseconds=nanos // 1000)
+
+
+EPOCH = datetime.datetime(1970, 1, 1, tzinfo=datetime.timezone.utc)
+
+
+def encode_system_time(value: datetime.datetime) -> dict:
+    """
+    `std::time::SystemTime`, `{"secs_since_epoch": 1714566600, "nanos_since_epoch": 0}`
+
+    A naive `datetime` is taken to be in UTC
+    """
+
+    if value.tzinfo is None:
+        value = value.replace(tzinfo=datetime.timezone.utc)
+
+    since = encode_duration(value - EPOCH)
+
+    return {"secs_since_epoch": since["secs"], "nanos_since_epoch": since["nanos"]}
+
+
+def decode_system_time(data: dict | list) -> datetime.datetime:
+    if isinstance(data, dict):
+        data = [data["secs_since_epoch"], data["nanos_since_epoch"]]
+
+    return EPOCH + decode_duration(data)
+
+
 def fields(dataklass: type) -> dict[str, Field]:
 
     # this is an internal implementation detail of dataclasses
     return getattr(dataklass, DATACLASS_FIELDS)
 
 
+def type_hints(klass: type) -> dict[str, Any]:
+
+    # generated modules use `from __future__ import annotations`
+    # so field types are strings until we resolve them
+    # this can only happen once the whole module has been loaded
+    # which is what lets recursive types refer to each other
+    if (hints := klass.__dict__.get("__marshal_hints__")) is None:
+        hints = get_type_hints(klass, include_extras=True)
+        klass.__marshal_hints__ = hints
+
+    return hints
+
+
+def is_annotated(ty: Any) -> bool:
+    if get_origin(ty) is Annotated:
+        return any(isinstance(meta, Check | Encoding) for meta in ty.__metadata__)
+
+    return any(is_annotated(arg) for arg in get_args(ty))
+
+
+def annotated_fields(klass: type) -> dict[str, Any]:
+
+    # only the fields with checks or encodings, most classes don't have any
+    if (annotated := klass.__dict__.get("__marshal_annotated__")) is None:
+        annotated = {k: ty for k, ty in type_hints(klass).items() if is_annotated(ty)}
+        klass.__marshal_annotated__ = annotated
+
+    return annotated
+
+
+def encode_annotated(ty: Any, v: Any) -> Any:
+    base = get_origin(ty)
+    args = get_args(ty)
+
+    if base is Annotated:
+        for meta in ty.__metadata__:
+            if isinstance(meta, Check):
+                meta.check(v)
+            elif isinstance(meta, Encoding):
+                return meta.encode(v)
+
+        return encode_annotated(args[0], v)
+    elif base in (UnionType, Union):
+        # an option, enum variants encode their own fields
+        if v is not None and len(options := [t for t in args if t is not type(None)]) == 1:
+            return encode_annotated(options[0], v)
+    elif base == tuple and isinstance(v, tuple | list):
+        return type(v)(encode_annotated(t, item) for t, item in zip(args, v))
+    elif base in (list, set, frozenset) and isinstance(v, list):
+        return [encode_annotated(args[0], item) for item in v]
+    elif base == dict and isinstance(v, dict):
+        return {
+            encode_annotated(args[0], key): encode_annotated(args[1], item)
+            for key, item in v.items()
+        }
+
+    return v
+
+
+def deserialize_value(ty: Any, v: Any) -> Any:
+
+    # list[int] -> list
+    base = get_origin(ty)
+
+    # Annotated[str, CHAR] -> str, once it's been checked
+    if base is Annotated:
+        for meta in ty.__metadata__:
+            if meta is JSON:
+                return v
+
+            if isinstance(meta, Check):
+                meta.check(v)
+            elif isinstance(meta, Encoding):
+                return meta.decode(v)
+
+        return deserialize_value(get_args(ty)[0], v)
+
+    # a mapped type, generic ones are registered without their arguments
+    if (codec := CODECS.get(ty) or CODECS.get(base)) and codec[1]:
+        return codec[1](v)
+
+    # tuple[int, str] -> [int, str]
+    args = get_args(ty)
+
+    if hasattr(ty, "ENUM_DATA"):
+        # an enum with a single variant
+        # isn't a union, but is handled like one
+        base, args = UnionType, (ty,)
+    elif dataclasses.is_dataclass(ty):
+        # if the field is a dataclass
+        # recurse right away
+        return deserialize(ty, v)
+
+    # if the type isn't subscripted
+    # these will be None or ()
+    # but we're going to assume marshall
+    # generated the types, and so they _are_ subscripted
+    # otherwise it's a primitive type and we
+    # don't need to do anything special
+
+    if base in (UnionType, Union):
+        if v is None and type(None) in args:
+            # this is an option
+            return None
+
+        for cls in args:
+            if enum_data := getattr(cls, "ENUM_DATA", None):
+                # this is an enum
+                variant, tag = enum_data
+
+                if isinstance(v, str):
+                    if variant == ENUM_VARIANT_UNIT and tag == v:
+                        return cls()
+                elif variant == ENUM_VARIANT_TUPLE:
+                    if tag in v:
+                        hints = type_hints(cls)
+                        items = v[tag]
+
+                        # a newtype variant is what it holds
+                        if len(hints) == 1:
+                            items = [items]
+
+                        return cls(
+                            *(
+                                deserialize_value(hints[f"_{i}"], item)
+                                for i, item in enumerate(items)
+                            )
+                        )
+                elif variant == ENUM_VARIANT_STRUCT:
+                    if tag in v:
+                        return deserialize(cls, v[tag])
+            elif cls is not type(None):
+                # this is an option of some non-enum type
+                return deserialize_value(cls, v)
+
+        raise ValueError(f"cannot deserialize {v} as {ty}")
+    elif base == tuple:
+        if not isinstance(v, tuple | list):
+            raise ValueError(f"cannot deserialize {v} as {ty}")
+
+        return tuple(deserialize_value(t, v) for t, v in zip(args, v))
+    elif base == list:
+        if not isinstance(v, list):
+            raise ValueError(f"cannot deserialize {v} as {ty}")
+
+        return [deserialize_value(args[0], i) for i in v]
+    elif base in (set, frozenset):
+        if not isinstance(v, list):
+            raise ValueError(f"cannot deserialize {v} as {ty}")
+
+        return to_set(base, [deserialize_value(args[0], i) for i in v])
+    elif base == dict:
+        if not isinstance(v, dict):
+            raise ValueError(f"cannot deserialize {v} as {ty}")
+
+        key = int_key if is_int(args[0]) else lambda k: k
+
+        return {
+            deserialize_value(args[0], key(k)): deserialize_value(args[1], v)
+            for k, v in v.items()
+        }
+
+    return v
+
+
 def deserialize(klass: type, data: dict) -> Self:
 
     # handle special recursive case
@@ -26,96 +584,46 @@ def deserialize(klass: type, data: dict) -> Self:
 
     if skip := getattr(klass, "SKIP_DESERIALIZING", None):
         for key in skip:
-            del data[key]
+            data.pop(key, None)
 
     if skip_if := getattr(klass, "SKIP_DESERIALIZING_IF", None):
         for key, condition in skip_if.items():
-            if condition(data[key]):
+            if key in data and condition(data[key]):
                 del data[key]
 
     if rename := getattr(klass, "RENAME", None):
-        for old_key, new_key in rename.items():
-            data[new_key] = data.pop(old_key)
+        for key, renamed in rename.items():
+            if renamed in data:
+                data[key] = data.pop(renamed)
 
     # be careful with this dict
     # if we modify it, we modify the class!
     fields_ = fields(klass)
+    hints = type_hints(klass)
+    codecs = getattr(klass, "FIELD_CODECS", {})
 
     d = {}
 
     for k, v in data.items():
-        if not (field := fields_.get(k)):
+        if k not in fields_ or k == "__class__":
             continue
 
-        ty = field.type
-
-        # if the field is a dataclass
-        # recurse right away
-        if dataclasses.is_dataclass(ty):
-            d[k] = deserialize(ty, v)
-
-            continue
-
-        # list[int] -> list
-        base = get_origin(ty)
-
-        # tuple[int, str] -> [int, str]
-        args = get_args(ty)
-
-        # if the type isn't subscripted
-        # these will be None or ()
-        # but we're going to assume marshall
-        # generated the types, and so they _are_ subscripted
-        # otherwise it's a primitive type and we
-        # don't need to do anything special
-
-        if base == UnionType:
-            # this is an enum
-
-            for cls in args:
-                if enum_data := getattr(cls, "ENUM_DATA", None):
-                    variant, tag = enum_data
-
-                    if isinstance(v, str):
-                        if variant == ENUM_VARIANT_UNIT:
-                            if tag == v:
-                                d[k] = cls()
-                                break
-                    elif variant == ENUM_VARIANT_TUPLE:
-                        if tag in v:
-                            d[k] = cls(*v[tag])
-                            break
-                    elif variant == ENUM_VARIANT_STRUCT:
-                        if tag in v:
-                            d[k] = cls(**v[tag])
-                            break
-            else:
-                raise ValueError(f"cannot deserialize {v} as {ty}")
-        elif base == tuple:
-            if not isinstance(v, tuple | list):
-                raise ValueError(f"cannot deserialize {v} as {ty}")
-
-            d[k] = tuple(deserialize(t, v) for t, v in zip(args, v))
-        elif base == list:
-            if not isinstance(v, list):
-                raise ValueError(f"cannot deserialize {v} as {ty}")
-
-            d[k] = [deserialize(args[0], i) for i in v]
-        elif base == dict:
-            if not isinstance(v, dict):
-                raise ValueError(f"cannot deserialize {v} as {ty}")
-
-            d[k] = {
-                deserialize(args[0], k): deserialize(args[1], v) for k, v in v.items()
-            }
+        # a `deserialize_with` function
+        if (decode := codecs.get(k, (None, None))[1]) is not None:
+            d[k] = decode(v)
         else:
-            d[k] = v
+            d[k] = deserialize_value(hints[k], v)
 
     return klass(**d)
 
 
-def dataclass(cls):
-    klass = dataclasses.dataclass(cls)
+def dataclass(cls=None, /, **kwargs):
+
+    # used as `@dataclass(kw_only=True)`
+    if cls is None:
+        return lambda cls: dataclass(cls, **kwargs)
+
+    klass = dataclasses.dataclass(cls, **kwargs)
 
     fields_: dict[str, Field] = fields(klass)
 
@@ -134,7 +642,24 @@ def dataclass(cls):
     return klass
 
 
+def field_encoders(klass: type) -> dict[str, Callable]:
+
+    # the fields with a `serialize_with` function, which get their values as they are
+    return {
+        key: encode
+        for key, (encode, _) in getattr(klass, "FIELD_CODECS", {}).items()
+        if encode is not None
+    }
+
+
 def my_dict_factory(items: list[tuple[str, any]], dict_factory=dict):
+    klass: type | None = dict(items).get("__class__")
+    encoders = field_encoders(klass) if klass else {}
+
+    items = [
+        (key, value if key in encoders else encode_plain(value)) for key, value in items
+    ]
+
     d = dict_factory(items)
 
     if klass := d.pop("__class__", None):
@@ -146,6 +671,10 @@ def my_dict_factory(items: list[tuple[str, any]], dict_factory=dict):
 
     klass: type = klass
 
+    for key, ty in annotated_fields(klass).items():
+        if key in d and key not in encoders:
+            d[key] = encode_annotated(ty, d[key])
+
     if skip := getattr(klass, "SKIP_SERIALIZING", None):
         for key in skip:
             del d[key]
@@ -155,9 +684,17 @@ def my_dict_factory(items: list[tuple[str, any]], dict_factory=dict):
             if condition(d[key]):
                 del d[key]
 
+    # after the skips, which see the field's own value
+    for key, encode in encoders.items():
+        if key in d:
+            d[key] = encode(d[key])
+
+    # serde serializes both of a double option's `None`s as `null`
+    d = {key: None if value is ABSENT else value for key, value in d.items()}
+
     if rename := getattr(klass, "RENAME", None):
-        for old_key, new_key in rename.items():
-            d[new_key] = d.pop(old_key)
+        # rebuild the dict so renamed keys keep their position
+        d = {rename.get(key, key): value for key, value in d.items()}
 
     if data := getattr(klass, "ENUM_DATA", None):
         variant, tag = data
@@ -165,17 +702,50 @@ def my_dict_factory(items: list[tuple[str, any]], dict_factory=dict):
         if variant == ENUM_VARIANT_UNIT:
             d = tag
         elif variant == ENUM_VARIANT_TUPLE:
-            d = {tag: tuple(d.values())}
+            items = tuple(d.values())
+
+            # a newtype variant is what it holds
+            d = {tag: items[0] if len(items) == 1 else items}
         elif variant == ENUM_VARIANT_STRUCT:
             d = {tag: d}
 
     return d
 
 
+def asdict_inner(obj, dict_factory):
+
+    # like `dataclasses.asdict`, but fields with a `serialize_with` function are left alone
+    if dataclasses.is_dataclass(obj) and not isinstance(obj, type):
+        encoders = field_encoders(type(obj))
+
+        items = []
+
+        for f in dataclasses.fields(obj):
+            value = getattr(obj, f.name)
+
+            if f.name not in encoders:
+                value = asdict_inner(value, dict_factory)
+
+            items.append((f.name, value))
+
+        return dict_factory(items)
+
+    if isinstance(obj, list | tuple):
+        return type(obj)(asdict_inner(item, dict_factory) for item in obj)
+
+    if isinstance(obj, dict):
+        return type(obj)(
+            (asdict_inner(key, dict_factory), asdict_inner(value, dict_factory))
+            for key, value in obj.items()
+        )
+
+    return copy.deepcopy(obj)
+
+
 def asdict(obj, dict_factory=dict):
     factory = lambda items: my_dict_factory(items, dict_factory=dict_factory)
 
-    return dataclasses.asdict(obj, dict_factory=factory)
+    return asdict_inner(obj, factory)
 
 
 ### Utility
@@ -200,3 +770,19 @@ class TupleVariant:
 
 def is_empty(value: Any) -> bool:
     return len(value) == 0
+
+
+def is_none(value: Any) -> bool:
+    return value is None
+
+
+def is_some(value: Any) -> bool:
+    return value is not None
+
+
+def is_absent(value: Any) -> bool:
+    return value is ABSENT
+
+
+def is_present(value: Any) -> bool:
+    return value is not ABSENT
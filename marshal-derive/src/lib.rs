@@ -0,0 +1,515 @@
+// `#[derive(Marshal)]`, which writes the Python for a type while its crate compiles
+//
+// ```rust
+// #[derive(Serialize, Deserialize, Default, Marshal)]
+// #[marshal(module = "models", default)]
+// struct User { ... }
+// ```
+//
+// each invocation only sees its own item, so items are kept as fragments in
+// `$MARSHAL_OUT_DIR/.marshal/<crate>-<kind>/<module>/`, and the module is regenerated from all of them
+// in name order, so the result doesn't depend on the order the derives ran in
+//
+// the lib, its tests and a `cargo check` are compiled by separate rustc processes, maybe at the same time,
+// so each keeps its own fragments, and every derive in one of them runs in the same process,
+// so the first one to see a new process id clears the fragments of the last build
+// the tests and binaries write their modules into directories of their own too, as they have other types,
+// a check writes the same ones as its build
+//
+// `[types]` and `[python]` come from the crate's `marshal.toml`, or the file `$MARSHAL_CONFIG` names,
+// which the derives include so that editing it rebuilds the crate
+//
+// a type may use one whose derive hasn't run yet, so while that's the case the module isn't written,
+// and every derive checks the types it uses derive `Marshal` into the same module, so the last one writes it,
+// or fails the build with the module's errors
+//
+// rustc takes the `#[derive(...)]`s off the items derives see, so `#[marshal(default)]` says it derives `Default`
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use marshal::codegen::{Options, Runtime};
+use marshal::config::{self, Config};
+use marshal::mapping::{self, TypeMapping};
+use marshal::parser::{
+    is_bare_bytes, DeclaredType, EnumVariantInner, InnerType, SerdeAttribute, StructField, Type,
+};
+use marshal::{Builder, Destination, Source};
+use proc_macro::{Delimiter, TokenStream, TokenTree};
+
+/// Where modules are written, relative to the crate's `Cargo.toml`
+const OUT_DIR: &str = "MARSHAL_OUT_DIR";
+
+/// The settings to read instead of `marshal.toml`, relative to the crate's `Cargo.toml`
+const CONFIG: &str = "MARSHAL_CONFIG";
+
+/// Writes the Python for a struct or enum to `$MARSHAL_OUT_DIR/<module>.py`
+///
+/// The module is the crate's name, or `#[marshal(module = "...")]`, and `#[marshal(default)]` says it derives `Default`
+#[proc_macro_derive(Marshal, attributes(marshal))]
+pub fn derive_marshal(input: TokenStream) -> TokenStream {
+    let mut output = String::new();
+
+    if let Err(message) = derive(input, &mut output) {
+        output.push_str(&format!("compile_error!({message:?});"));
+    }
+
+    output.parse().expect("the output is valid tokens")
+}
+
+#[derive(Default)]
+struct Attributes {
+    module: Option<String>,
+    default: bool,
+}
+
+fn derive(input: TokenStream, output: &mut String) -> Result<(), String> {
+    let name = type_name(&input).ok_or("`#[derive(Marshal)]` only works on structs and enums")?;
+    let attrs = attributes(&input)?;
+
+    let module = match attrs.module {
+        Some(module) => module,
+        None => std::env::var("CARGO_CRATE_NAME").map_err(|_| "`CARGO_CRATE_NAME` isn't set")?,
+    };
+
+    // says which module it's in, so the types using it can check it's in theirs
+    let marker = format!("__marshal_module_{module}");
+
+    output.push_str(&format!(
+        "impl {name} {{ #[doc(hidden)] #[allow(non_upper_case_globals)] pub const {marker}: () = (); }}\n"
+    ));
+
+    let mut text = input.to_string();
+
+    if attrs.default {
+        text = format!("#[derive(Default)] {text}");
+    }
+
+    let (options, config) = options()?;
+
+    if let Some(config) = config {
+        output.push_str(&format!(
+            "const _: &[u8] = include_bytes!({:?});\n",
+            config.display().to_string()
+        ));
+    }
+
+    // its own mistakes are errors right away
+    let source = Source::new(format!("{name}.rs"), text.clone());
+    let builder = Builder::new().source(source);
+    let lexed = builder.lex();
+
+    let types = builder
+        .parse(&lexed)
+        .map_err(|errors| builder.render(&errors))?;
+
+    // the types it uses derive `Marshal` into the same module,
+    // so none are missing once every derive has run
+    for path in types
+        .iter()
+        .flatten()
+        .flat_map(|decl| used_types(decl, &options.type_mappings))
+    {
+        output.push_str(&format!("const _: () = {path}::{marker};\n"));
+    }
+
+    // rust-analyzer expands derives one at a time, in its own process,
+    // which would clear the fragments of the real build
+    if is_proc_macro_server() {
+        return Ok(());
+    }
+
+    let dir = out_dir()?;
+    let unit = unit();
+    let fragments = dir.join(".marshal").join(&unit).join(&module);
+
+    start_session(&fragments)?;
+
+    let fragment = fragments.join(format!("{name}.rs"));
+
+    match std::fs::read_to_string(&fragment) {
+        Ok(existing) if existing != text => {
+            return Err(format!(
+                "there's another type named `{name}` in the Python module `{module}`, \
+                 put one of them in another with `#[marshal(module = \"...\")]`"
+            ));
+        }
+        _ => write(&fragment, &text)?,
+    }
+
+    let path = module_dir(&dir, &unit).join(&module).with_extension("py");
+
+    generate(&path, &fragments, options)
+}
+
+fn is_proc_macro_server() -> bool {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| {
+            Some(
+                exe.file_name()?
+                    .to_string_lossy()
+                    .contains("proc-macro-srv"),
+            )
+        })
+        .unwrap_or(false)
+}
+
+fn out_dir() -> Result<PathBuf, String> {
+    let dir = std::env::var(OUT_DIR).map_err(|_| {
+        format!(
+            "`#[derive(Marshal)]` needs a directory to write to, set `{OUT_DIR}`, \
+             for example in `[env]` in `.cargo/config.toml`"
+        )
+    })?;
+
+    let manifest = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+
+    Ok(Path::new(&manifest).join(dir))
+}
+
+// the config's options, and its path, with the runtime written next to the module unless it says otherwise
+fn options() -> Result<(Options, Option<PathBuf>), String> {
+    let manifest = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default());
+
+    let path = match std::env::var(CONFIG) {
+        Ok(path) => manifest.join(path),
+        Err(_) if manifest.join(config::FILE).is_file() => manifest.join(config::FILE),
+        Err(_) => {
+            let options = Options {
+                runtime: Runtime::Write,
+                ..Options::default()
+            };
+
+            return Ok((options, None));
+        }
+    };
+
+    let text = std::fs::read_to_string(&path)
+        .map_err(|err| format!("cannot read `{}`: {err}", path.display()))?;
+
+    let config = Config::parse(&text, &path)
+        .map_err(|err| err.with_file(path.display().to_string()).render(&text))?;
+
+    let mut options = config.options();
+
+    if config.python.runtime.is_none() {
+        options.runtime = Runtime::Write;
+    }
+
+    Ok((options, Some(path)))
+}
+
+// what's being compiled, like `app-lib`, `app-test` or `app-lib-check`,
+// from the arguments of the rustc process derives run in
+fn unit() -> String {
+    let args: Vec<String> = std::env::args().collect();
+
+    let value = |flag: &str| {
+        args.iter()
+            .enumerate()
+            .find_map(|(i, arg)| match arg.strip_prefix(flag) {
+                Some("") => args.get(i + 1).cloned(),
+                Some(value) => value.strip_prefix('=').map(str::to_string),
+                None => None,
+            })
+    };
+
+    let krate = std::env::var("CARGO_CRATE_NAME")
+        .ok()
+        .or_else(|| value("--crate-name"))
+        .unwrap_or_default();
+
+    let kind = if args.iter().any(|arg| arg == "--test") {
+        "test".to_string()
+    } else {
+        value("--crate-type").unwrap_or_else(|| "lib".to_string())
+    };
+
+    // `cargo check` only emits metadata
+    match value("--emit") {
+        Some(emit) if !emit.split(',').any(|kind| kind == "link") => {
+            format!("{krate}-{kind}-check")
+        }
+        _ => format!("{krate}-{kind}"),
+    }
+}
+
+// where `unit`'s modules are written, a library's and its check's in `dir`,
+// and the tests' and binaries' in directories named after them, like `app-test`
+fn module_dir(dir: &Path, unit: &str) -> PathBuf {
+    let unit = unit.strip_suffix("-check").unwrap_or(unit);
+
+    if unit.ends_with("-test") || unit.ends_with("-bin") {
+        dir.join(unit)
+    } else {
+        dir.to_path_buf()
+    }
+}
+
+// clears the fragments of an earlier compilation
+fn start_session(fragments: &Path) -> Result<(), String> {
+    let session = fragments.join("session");
+    let id = std::process::id().to_string();
+
+    if std::fs::read_to_string(&session).is_ok_and(|existing| existing == id) {
+        return Ok(());
+    }
+
+    if let Ok(entries) = std::fs::read_dir(fragments) {
+        for entry in entries.flatten() {
+            if entry.path().extension().is_some_and(|ext| ext == "rs") {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
+
+    write(&session, &id)
+}
+
+// regenerates the module at `path` from every fragment so far,
+// unless some of the types it uses haven't been seen yet
+fn generate(path: &Path, fragments: &Path, options: Options) -> Result<(), String> {
+    let mut paths: Vec<_> = std::fs::read_dir(fragments)
+        .map_err(|err| format!("cannot read `{}`: {err}", fragments.display()))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "rs"))
+        .collect();
+
+    paths.sort();
+
+    let sources = paths
+        .iter()
+        .map(|path| {
+            Source::from_path(path)
+                .map_err(|err| format!("cannot read `{}`: {err}", path.display()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mappings = options.type_mappings.clone();
+
+    let builder = Builder::new()
+        .sources(sources)
+        .options(options)
+        .destination(Destination::File(Some(path.to_path_buf())));
+
+    let lexed = builder.lex();
+    let types = builder
+        .parse(&lexed)
+        .map_err(|errors| builder.render(&errors))?;
+
+    let declared: Vec<_> = types.iter().flatten().map(|decl| decl.name).collect();
+
+    let pending = types
+        .iter()
+        .flatten()
+        .flat_map(|decl| used_types(decl, &mappings))
+        .any(|path| !declared.contains(&path.rsplit("::").next().unwrap_or_default()));
+
+    if pending {
+        return Ok(());
+    }
+
+    let mut generated = builder.generate();
+
+    if !generated.diagnostics.is_empty() {
+        for diagnostic in &mut generated.diagnostics {
+            if diagnostic.message.ends_with("does not derive `Default`") {
+                diagnostic.notes.push(
+                    "say it does with `#[marshal(default)]`, rustc hides its `#[derive(...)]`"
+                        .to_string(),
+                );
+            }
+        }
+
+        return Err(generated.render_diagnostics());
+    }
+
+    for file in generated.files {
+        if let Some(path) = file.path {
+            write(&path, &file.contents)?;
+        }
+    }
+
+    Ok(())
+}
+
+// the paths of the declared types `decl` uses, which have to derive `Marshal` too
+//
+// paths `mappings` or the built-in ones know aren't, and neither are ones with generic arguments,
+// which can't be declared, so code generation reports them
+fn used_types(decl: &DeclaredType, mappings: &HashMap<String, TypeMapping>) -> Vec<String> {
+    fn visit(ty: &Type, mappings: &[&HashMap<String, TypeMapping>], paths: &mut Vec<String>) {
+        match ty {
+            Type::Option(ty) | Type::Array(ty) | Type::Vec(ty) | Type::Set(ty) => {
+                visit(ty, mappings, paths)
+            }
+            Type::Map(key, value) => {
+                visit(key, mappings, paths);
+                visit(value, mappings, paths);
+            }
+            Type::Tuple(types) => {
+                for ty in types {
+                    visit(ty, mappings, paths);
+                }
+            }
+            Type::User(path) => {
+                for ty in &path.args {
+                    visit(ty, mappings, paths);
+                }
+
+                let path_str = path.segments.join("::");
+
+                // a bare `Bytes` is `bytes::Bytes` unless it's declared, which codegen checks
+                if path.args.is_empty()
+                    && path.name() != "Self"
+                    && !is_bare_bytes(path)
+                    && mappings
+                        .iter()
+                        .all(|mappings| mapping::find(mappings, &path.segments, 0).is_none())
+                    && !paths.contains(&path_str)
+                {
+                    paths.push(path_str);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let builtins = mapping::builtins();
+    let mappings = [mappings, &builtins];
+    let mut paths = Vec::new();
+
+    // skipped fields and variants aren't generated
+    fn fields<'t, 'a>(fields: &'t [StructField<'a>]) -> impl Iterator<Item = &'t Type<'a>> {
+        fields
+            .iter()
+            .filter(|field| {
+                !field
+                    .attrs
+                    .iter()
+                    .any(|attr| matches!(attr, SerdeAttribute::Skip))
+            })
+            .map(|field| &field.ty)
+    }
+
+    let types: Vec<&Type> = match &decl.inner {
+        InnerType::Struct(struc) => fields(&struc.fields).collect(),
+        InnerType::Enum(enu) => enu
+            .variants
+            .iter()
+            .filter(|variant| {
+                !variant
+                    .attrs
+                    .iter()
+                    .any(|attr| matches!(attr, SerdeAttribute::Skip))
+            })
+            .flat_map(|variant| match &variant.inner {
+                EnumVariantInner::Unit => Vec::new(),
+                EnumVariantInner::Tuple(types) => types.iter().collect(),
+                EnumVariantInner::Struct(struc) => fields(&struc.fields).collect(),
+            })
+            .collect(),
+    };
+
+    for ty in types {
+        visit(ty, &mappings, &mut paths);
+    }
+
+    paths
+}
+
+// replaces the file in one go, so nothing reads half of it, and leaves it alone if it's the same
+fn write(path: &Path, contents: &str) -> Result<(), String> {
+    if std::fs::read_to_string(path).is_ok_and(|existing| existing == contents) {
+        return Ok(());
+    }
+
+    let fail = |err: std::io::Error| format!("cannot write `{}`: {err}", path.display());
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(fail)?;
+    }
+
+    let temp = path.with_extension(format!("tmp{}", std::process::id()));
+
+    std::fs::write(&temp, contents).map_err(fail)?;
+    std::fs::rename(&temp, path).map_err(fail)
+}
+
+// the name after `struct` or `enum`
+fn type_name(input: &TokenStream) -> Option<String> {
+    let mut tokens = input.clone().into_iter();
+
+    while let Some(token) = tokens.next() {
+        if let TokenTree::Ident(ident) = token {
+            if matches!(ident.to_string().as_str(), "struct" | "enum") {
+                return match tokens.next()? {
+                    TokenTree::Ident(name) => Some(name.to_string()),
+                    _ => None,
+                };
+            }
+        }
+    }
+
+    None
+}
+
+// `#[marshal(module = "models", default)]`
+fn attributes(input: &TokenStream) -> Result<Attributes, String> {
+    let mut attrs = Attributes::default();
+
+    for token in input.clone() {
+        let TokenTree::Group(attr) = token else {
+            continue;
+        };
+
+        if attr.delimiter() != Delimiter::Bracket {
+            continue;
+        }
+
+        let mut attr = attr.stream().into_iter();
+
+        if !matches!(attr.next(), Some(TokenTree::Ident(ident)) if ident.to_string() == "marshal") {
+            continue;
+        }
+
+        let args = match attr.next() {
+            Some(TokenTree::Group(args)) if args.delimiter() == Delimiter::Parenthesis => args
+                .stream()
+                .into_iter()
+                .map(|token| token.to_string())
+                .collect::<Vec<_>>(),
+            _ => Vec::new(),
+        };
+
+        let expected =
+            || "expected `#[marshal(module = \"...\")]` or `#[marshal(default)]`".to_string();
+
+        if args.is_empty() {
+            return Err(expected());
+        }
+
+        for arg in args.split(|token| token == ",") {
+            match arg {
+                [key] if key == "default" => attrs.default = true,
+                [key, eq, value] if key == "module" && eq == "=" => {
+                    let module = value.trim_matches('"');
+
+                    if module.is_empty() || !module.chars().all(|c| c.is_alphanumeric() || c == '_')
+                    {
+                        return Err(format!("`{module}` isn't a Python module name"));
+                    }
+
+                    attrs.module = Some(module.to_string());
+                }
+                // a trailing comma
+                [] => {}
+                _ => return Err(expected()),
+            }
+        }
+    }
+
+    Ok(attrs)
+}
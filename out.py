@@ -1,20 +1,25 @@
-# Generated code
+# Generated code, marshal runtime protocol version 9
+
+from __future__ import annotations
 
 from lib.marshal import *
 
+if globals().get("MARSHAL_RUNTIME_VERSION") != 9:
+    raise ImportError(
+        "this module needs marshal runtime protocol version 9, "
+        "regenerate it or update the runtime"
+    )
 
 @dataclass
 class None_:
     ENUM_DATA = (ENUM_VARIANT_UNIT, "None")
 
-
 @dataclass
 class First(TupleVariant):
     ENUM_DATA = (ENUM_VARIANT_TUPLE, "First")
 
     _0: str
 
-
 @dataclass
 class FirstLast(TupleVariant):
     ENUM_DATA = (ENUM_VARIANT_TUPLE, "FirstLast")
@@ -22,7 +27,6 @@ class FirstLast(TupleVariant):
     _0: str
     _1: str
 
-
 @dataclass
 class FirstMiddleLast:
     ENUM_DATA = (ENUM_VARIANT_STRUCT, "FirstMiddleLast")
@@ -34,7 +38,6 @@ class FirstMiddleLast:
     middle: list[str]
     last: str
 
-
 Name = None_ | First | FirstLast | FirstMiddleLast
 
 
@@ -45,5 +48,7 @@ class User:
     }
 
     name: Name
-    birthday: tuple[int, int, int | None]
-    age: int = 0
+    birthday: tuple[Annotated[int, U32], Annotated[int, U32], Annotated[int, U32] | None]
+    age: Annotated[int, U32] = 0
+
+
@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+use marshal::codegen::{Mode, Options};
+use marshal::config::{self, Config};
+use marshal::mapping::TypeMapping;
+use marshal::{Destination, Frontend, Target};
+
+const EXIT_CODES: &str = "\
+Without a command, `generate` runs with the settings in `marshal.toml`
+
+Exit codes:
+  0  success
+  1  the inputs have errors
+  2  the command line or `marshal.toml` is invalid
+  3  a file couldn't be read or written
+  4  `generate --check` found out of date files";
+
+/// Generates Python dataclasses compatible with your Rust serde types
+#[derive(Debug, Parser)]
+#[command(version, after_help = EXIT_CODES)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Read settings from this file instead of `marshal.toml`, command line options override them
+    #[arg(short, long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Only print errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Print what's being read and written
+    #[arg(short, long, global = true)]
+    pub verbose: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Generate code from the inputs
+    Generate {
+        #[command(flatten)]
+        inputs: Inputs,
+
+        #[command(flatten)]
+        output: Output,
+
+        #[command(flatten)]
+        codegen: Codegen,
+    },
+
+    /// Check the inputs, including everything code generation needs, without writing anything
+    Check {
+        #[command(flatten)]
+        inputs: Inputs,
+
+        #[command(flatten)]
+        codegen: Codegen,
+    },
+
+    /// Print the declarations as marshal understands them
+    Dump {
+        #[command(flatten)]
+        inputs: Inputs,
+
+        /// Print the tokens instead
+        #[arg(long)]
+        tokens: bool,
+    },
+
+    /// Check that the inputs parse
+    Validate {
+        #[command(flatten)]
+        inputs: Inputs,
+    },
+}
+
+#[derive(Debug, Default, Args)]
+pub struct Inputs {
+    /// Rust files to read, or what `--frontend` reads, `-` reads stdin, glob patterns like `src/**/*.rs` are expanded
+    #[arg(value_name = "INPUT")]
+    pub paths: Vec<String>,
+
+    /// Features enabled for `#[cfg(feature = "...")]`, comma separated
+    #[arg(short = 'F', long, value_name = "FEATURES", value_delimiter = ',')]
+    pub features: Option<Vec<String>>,
+
+    /// Only generate types whose names match one of these patterns, like `User*`
+    #[arg(long, value_name = "PATTERN")]
+    pub include: Vec<String>,
+
+    /// Don't generate types whose names match one of these patterns
+    #[arg(long, value_name = "PATTERN")]
+    pub exclude: Vec<String>,
+
+    /// What reads the inputs [default: native]
+    #[arg(long, value_enum)]
+    pub frontend: Option<Frontend>,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct Output {
+    /// Write every input's types into one module, `-` writes to stdout, the default
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<String>,
+
+    /// Write a module per input into this directory instead, named after the input
+    #[arg(short = 'd', long, value_name = "DIR", conflicts_with = "output")]
+    pub out_dir: Option<PathBuf>,
+
+    /// Don't write anything, print a diff against the existing files and fail if they're out of date
+    #[arg(long)]
+    pub check: bool,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct Codegen {
+    /// [default: python]
+    #[arg(short, long, value_enum)]
+    pub target: Option<Target>,
+
+    /// A Python callable for a custom `#[serde(default = "path")]` function, can be repeated
+    #[arg(long, value_name = "PATH=CALLABLE", value_parser = parse_mapping)]
+    pub default: Vec<(String, String)>,
+
+    /// The Python type for a Rust type, like `rust_decimal::Decimal=decimal.Decimal`, can be repeated
+    #[arg(long = "type", value_name = "PATH=TYPE", value_parser = parse_mapping)]
+    pub types: Vec<(String, String)>,
+
+    /// A callable turning a mapped type's values into plain data, can be repeated
+    #[arg(long, value_name = "PATH=CALLABLE", value_parser = parse_mapping)]
+    pub encode: Vec<(String, String)>,
+
+    /// A callable turning plain data into a mapped type's values, can be repeated
+    #[arg(long, value_name = "PATH=CALLABLE", value_parser = parse_mapping)]
+    pub decode: Vec<(String, String)>,
+
+    /// A callable standing in for a `serialize_with` function, or the `serialize` of a `with` module, can be repeated
+    #[arg(long, value_name = "PATH=CALLABLE", value_parser = parse_mapping)]
+    pub serialize_with: Vec<(String, String)>,
+
+    /// A callable standing in for a `deserialize_with` function, or the `deserialize` of a `with` module, can be repeated
+    #[arg(long, value_name = "PATH=CALLABLE", value_parser = parse_mapping)]
+    pub deserialize_with: Vec<(String, String)>,
+
+    /// Generate `kw_only` dataclasses which keep the Rust field order
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+    pub kw_only: Option<bool>,
+
+    /// Leave bytes as `bytes` for binary formats, instead of arrays of numbers for JSON
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+    pub raw_bytes: Option<bool>,
+
+    /// Generate `frozenset`s for Rust sets instead of `set`s
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+    pub frozen_sets: Option<bool>,
+
+    /// Generate explicit encode and decode functions instead of using reflection
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+    pub direct: Option<bool>,
+
+    /// Where generated code finds the runtime: a module path, `write` or `inline` [default: lib.marshal]
+    #[arg(long, value_name = "RUNTIME")]
+    pub runtime: Option<String>,
+}
+
+fn parse_mapping(mapping: &str) -> Result<(String, String), String> {
+    let (from, to) = mapping.split_once('=').ok_or("expected `from=to`")?;
+
+    Ok((from.to_string(), to.to_string()))
+}
+
+impl Inputs {
+    pub fn paths(&self, config: &Config) -> Vec<String> {
+        if !self.paths.is_empty() {
+            self.paths.clone()
+        } else if !config.inputs.is_empty() {
+            config.inputs.clone()
+        } else if self.frontend(config) == Frontend::Crate {
+            vec![marshal::krate::ROOT.to_string()]
+        } else {
+            Vec::new()
+        }
+    }
+
+    pub fn features<'a>(&'a self, config: &'a Config) -> &'a [String] {
+        self.features.as_deref().unwrap_or(&config.features)
+    }
+
+    pub fn frontend(&self, config: &Config) -> Frontend {
+        self.frontend.or(config.frontend).unwrap_or_default()
+    }
+
+    pub fn include<'a>(&'a self, config: &'a Config) -> &'a [String] {
+        if self.include.is_empty() {
+            &config.include
+        } else {
+            &self.include
+        }
+    }
+
+    pub fn exclude<'a>(&'a self, config: &'a Config) -> &'a [String] {
+        if self.exclude.is_empty() {
+            &config.exclude
+        } else {
+            &self.exclude
+        }
+    }
+}
+
+impl Output {
+    pub fn destination(&self, config: &Config) -> Destination {
+        // the command line wins, whichever of them it sets
+        let (output, out_dir) = if self.output.is_some() || self.out_dir.is_some() {
+            (self.output.as_deref(), self.out_dir.as_ref())
+        } else {
+            (config.output.as_deref(), config.out_dir.as_ref())
+        };
+
+        match (output, out_dir) {
+            (_, Some(dir)) => Destination::Dir(dir.clone()),
+            (Some("-") | None, None) => Destination::File(None),
+            (Some(file), None) => Destination::File(Some(PathBuf::from(file))),
+        }
+    }
+}
+
+impl Codegen {
+    pub fn target(&self, config: &Config) -> Target {
+        self.target.or(config.target).unwrap_or_default()
+    }
+
+    pub fn options(&self, config: &Config) -> Result<Options, String> {
+        let mut options = config.options();
+
+        // the command line's mappings are added over the config's
+        options.default_fns.extend(self.default.iter().cloned());
+
+        for (path, python) in &self.types {
+            options
+                .type_mappings
+                .insert(path.clone(), TypeMapping::new(python));
+        }
+
+        for (path, hook) in &self.encode {
+            mapped(&mut options.type_mappings, "encode", path)?.encode = Some(hook.clone());
+        }
+
+        for (path, hook) in &self.decode {
+            mapped(&mut options.type_mappings, "decode", path)?.decode = Some(hook.clone());
+        }
+
+        for (path, hook) in &self.serialize_with {
+            options.codecs.entry(path.clone()).or_default().encode = Some(hook.clone());
+        }
+
+        for (path, hook) in &self.deserialize_with {
+            options.codecs.entry(path.clone()).or_default().decode = Some(hook.clone());
+        }
+
+        if let Some(runtime) = &self.runtime {
+            options.runtime = config::runtime(runtime);
+        }
+
+        if let Some(direct) = self.direct {
+            options.mode = if direct {
+                Mode::Direct
+            } else {
+                Mode::Reflection
+            };
+        }
+
+        options.kw_only = self.kw_only.unwrap_or(options.kw_only);
+        options.raw_bytes = self.raw_bytes.unwrap_or(options.raw_bytes);
+        options.frozen_sets = self.frozen_sets.unwrap_or(options.frozen_sets);
+
+        Ok(options)
+    }
+}
+
+// the mapping `--encode` or `--decode` adds a hook to
+fn mapped<'m>(
+    mappings: &'m mut HashMap<String, TypeMapping>,
+    flag: &str,
+    path: &str,
+) -> Result<&'m mut TypeMapping, String> {
+    mappings.get_mut(path).ok_or_else(|| {
+        format!("`--{flag} {path}=...` needs a type for `{path}`, pass `--type {path}=<type>`")
+    })
+}
@@ -1,10 +1,35 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::rc::Rc;
+
 use indoc::formatdoc;
 use itertools::Itertools;
 
+mod direct;
+
+use direct::{skips_deserializing, skips_serializing};
+
+use crate::deps;
+use crate::diagnostic::Diagnostic;
+use crate::mapping::{self, Codec, TypeMapping};
 use crate::parser::{
-    DeclaredType, Enum, EnumVariant, EnumVariantInner, InnerType, SerdeAttribute, Struct,
-    StructField, Type,
+    is_bare_bytes, Bytes, DeclaredType, Enum, EnumVariant, EnumVariantInner, InnerType,
+    SerdeAttribute, Struct, StructField, Type, TypePath,
 };
+use crate::runtime;
+
+// Python types whose values are all alike to the runtime
+const BUILTIN_TYPES: &[&str] = &[
+    "bool",
+    "bytes",
+    "dict",
+    "float",
+    "frozenset",
+    "int",
+    "list",
+    "set",
+    "str",
+    "tuple",
+];
 
 fn indent(s: &str, level: usize) -> String {
     let indent = "    ".repeat(level);
@@ -22,7 +47,67 @@ impl StringExt for str {
     }
 }
 
-fn support_struct(struc: &Struct) -> String {
+// the runtime's equivalent of common Rust predicates
+// anything else is assumed to be a Python callable with the same name
+fn skip_predicate<'b>(field: &StructField, expr: &'b str) -> &'b str {
+    match expr {
+        // these look at the outer option
+        "Option::is_none" if double_option(field).is_some() => "is_absent",
+        "Option::is_some" if double_option(field).is_some() => "is_present",
+        "Option::is_none" => "is_none",
+        "Option::is_some" => "is_some",
+        "Vec::is_empty"
+        | "String::is_empty"
+        | "str::is_empty"
+        | "HashMap::is_empty"
+        | "BTreeMap::is_empty"
+        | "HashSet::is_empty"
+        | "BTreeSet::is_empty"
+        | "IndexSet::is_empty"
+        | "VecDeque::is_empty"
+        | "LinkedList::is_empty"
+        | "BinaryHeap::is_empty" => "is_empty",
+        _ => expr,
+    }
+}
+
+// `Option<Option<T>>` fields using `serde_with`'s `double_option`
+// these tell an absent field apart from `null`
+fn double_option<'a, 'b>(field: &'b StructField<'a>) -> Option<&'b Type<'a>> {
+    let with_double_option = field.attrs.iter().any(|attr| {
+        matches!(
+            attr,
+            SerdeAttribute::With(
+                "::serde_with::rust::double_option" | "serde_with::rust::double_option"
+            )
+        )
+    });
+
+    match &field.ty {
+        Type::Option(ty) if with_double_option => match &**ty {
+            Type::Option(ty) => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// `with` modules codegen understands itself
+fn builtin_with(path: &str) -> bool {
+    matches!(
+        path.trim_start_matches("::"),
+        "serde_bytes" | "serde_with::rust::double_option"
+    )
+}
+
+/// The Python callables standing in for a field's custom serialization
+#[derive(Debug, Default)]
+struct FieldHooks {
+    encode: Option<String>,
+    decode: Option<String>,
+}
+
+fn support_struct(struc: &Struct, hooks: &[(&str, FieldHooks)]) -> String {
     let skip_serializing: Vec<_> = struc
         .fields
         .iter()
@@ -76,7 +161,11 @@ fn support_struct(struc: &Struct) -> String {
             ",
                 skip_serializing_if
                     .into_iter()
-                    .map(|(field, expr)| format!("\"{}\": {},", field.name, expr))
+                    .map(|(field, expr)| format!(
+                        "\"{}\": {},",
+                        field.name,
+                        skip_predicate(field, expr)
+                    ))
                     .join("\n")
             )
             .indent(1),
@@ -176,27 +265,43 @@ fn support_struct(struc: &Struct) -> String {
         )
     };
 
-    let mut support = String::new();
-
-    if let Some(skip_serializing) = skip_serializing {
-        support.push_str(&skip_serializing);
-    }
-
-    if let Some(skip_serializing_if) = skip_serializing_if {
-        support.push_str(&skip_serializing_if);
-    }
-
-    if let Some(skip_deserializing) = skip_deserializing {
-        support.push_str(&skip_deserializing);
-    }
+    let field_codecs = if hooks.is_empty() {
+        None
+    } else {
+        let hook = |hook: &Option<String>| hook.clone().unwrap_or_else(|| "None".to_string());
 
-    if let Some(skip_deserializing_if) = skip_deserializing_if {
-        support.push_str(&skip_deserializing_if);
-    }
+        Some(
+            formatdoc!(
+                "
+            FIELD_CODECS = {{
+                {}
+            }}
+            ",
+                hooks
+                    .iter()
+                    .map(|(name, hooks)| format!(
+                        "\"{name}\": ({}, {}),",
+                        hook(&hooks.encode),
+                        hook(&hooks.decode)
+                    ))
+                    .join("\n")
+            )
+            .indent(1),
+        )
+    };
 
-    if let Some(rename) = rename {
-        support.push_str(&rename);
-    }
+    // the first line is indented by the caller
+    let mut support = [
+        skip_serializing,
+        skip_serializing_if,
+        skip_deserializing,
+        skip_deserializing_if,
+        rename,
+        field_codecs,
+    ]
+    .into_iter()
+    .flatten()
+    .join("\n    ");
 
     support.push('\n');
 
@@ -209,93 +314,980 @@ macro_rules! output {
     };
 }
 
+/// How the generated classes are converted to and from plain data
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// The runtime introspects the dataclasses on every call
+    #[default]
+    Reflection,
+
+    /// Every type gets its own `encode_*` and `decode_*` functions, with no runtime reflection
+    Direct,
+}
+
+/// Where generated code finds the runtime
+#[derive(Debug, Clone, PartialEq)]
+pub enum Runtime {
+    /// Import it from an existing module, like `lib.marshal`
+    Import(String),
+
+    /// Import it from `marshal_runtime.py`, written next to the generated code
+    Write,
+
+    /// Paste it into the generated module
+    Inline,
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Runtime::Import("lib.marshal".to_string())
+    }
+}
+
+/// Knobs that change the shape of the generated code
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    /// Python callables for custom `#[serde(default = "path")]` functions, keyed by Rust path
+    ///
+    /// A dotted callable like `defaults.make_tags` imports its module
+    pub default_fns: HashMap<String, String>,
+
+    /// Generate `kw_only` dataclasses which keep fields in Rust declaration order
+    ///
+    /// Otherwise fields with defaults are moved last, as `dataclasses` requires for positional arguments
+    pub kw_only: bool,
+
+    /// Bytes serialized with `serialize_bytes` are left as `bytes` for binary formats like MessagePack
+    ///
+    /// Otherwise they're arrays of numbers, as in JSON
+    pub raw_bytes: bool,
+
+    /// Python callables for the functions in `#[serde(with = "...")]`, `serialize_with` and `deserialize_with`, see `mapping`
+    ///
+    /// A dotted callable imports its module
+    pub codecs: HashMap<String, Codec>,
+
+    /// `HashSet`s and `BTreeSet`s are `frozenset`s, which can be dict keys and set items themselves
+    pub frozen_sets: bool,
+
+    pub mode: Mode,
+
+    pub runtime: Runtime,
+
+    /// Python types for Rust types, keyed by Rust path, see `mapping`
+    ///
+    /// Declared types with a mapping aren't generated
+    pub type_mappings: HashMap<String, TypeMapping>,
+}
+
+/// The Python value of a field default
+enum DefaultValue {
+    /// an immutable expression, used as is
+    Value(String),
+
+    /// a callable producing a fresh value per instance
+    /// needed for mutable values, which `dataclasses` rejects as plain defaults
+    Factory(String),
+
+    /// a mutable expression, evaluated anew for every instance
+    Mutable(String),
+}
+
+impl DefaultValue {
+    // an expression evaluating to the default
+    fn expr(self) -> String {
+        match self {
+            DefaultValue::Value(value) => value,
+            DefaultValue::Factory(factory) => format!("{factory}()"),
+            DefaultValue::Mutable(expr) => expr,
+        }
+    }
+}
+
 pub struct Codegen<'a> {
-    types: Vec<DeclaredType<'a>>,
+    // the types this module declares, then the ones it can import from its siblings
+    types: Rc<[DeclaredType<'a>]>,
+    declared: usize,
+    options: Options,
+
+    // the sibling module each imported type is declared in
+    siblings: HashMap<&'a str, String>,
+
+    imports: BTreeSet<String>,
+
+    // built-in mappings, for the types the user hasn't mapped or declared
+    builtins: HashMap<String, TypeMapping>,
+
+    // `register_codec(...)` calls for mappings with hooks, keyed by class
+    // with the Rust path they were registered for
+    codecs: BTreeMap<String, (String, String)>,
 
     output: String,
 }
 
 impl<'a> Codegen<'a> {
-    pub fn new(types: Vec<DeclaredType<'a>>) -> Self {
+    pub fn new(types: Vec<DeclaredType<'a>>, options: Options) -> Self {
+        // mapped types aren't generated
+        let types = types
+            .into_iter()
+            .filter(|decl| mapping::find(&options.type_mappings, &[decl.name], 0).is_none())
+            .collect_vec();
+
+        let types: Rc<[_]> = deps::sort(types).into();
+
         Codegen {
+            declared: types.len(),
             types,
+            options,
+            siblings: HashMap::new(),
+            imports: BTreeSet::new(),
+            builtins: mapping::builtins(),
+            codecs: BTreeMap::new(),
             output: String::new(),
         }
     }
 
+    /// Makes the types the sibling module `module` declares usable here, imported from it
+    ///
+    /// Types this module declares itself win over a sibling's of the same name
+    pub fn sibling(mut self, module: &str, types: Vec<DeclaredType<'a>>) -> Self {
+        let types = types.into_iter().filter(|decl| {
+            mapping::find(&self.options.type_mappings, &[decl.name], 0).is_none()
+                && !self.types.iter().any(|known| known.name == decl.name)
+        });
+
+        let mut all = self.types.to_vec();
+
+        for decl in types {
+            self.siblings.insert(decl.name, module.to_string());
+            all.push(decl);
+        }
+
+        self.types = all.into();
+        self
+    }
+
     fn write(&mut self, s: &str) {
         self.output.push_str(s);
     }
 
-    fn generate_type(&self, type_: &Type<'a>) -> String {
-        match type_ {
+    // imports the module of a dotted Python name like `datetime.datetime`
+    fn import_dotted(&mut self, name: &str) {
+        let dotted = name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '.');
+
+        if let Some((module, _)) = name.rsplit_once('.').filter(|_| dotted) {
+            self.imports.insert(module.to_string());
+        }
+    }
+
+    // imports the modules of the dotted names in a Python type like `list[decimal.Decimal]`
+    fn import_type(&mut self, python: &str) {
+        let names = python
+            .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+            .filter(|name| !name.is_empty())
+            .collect_vec();
+
+        for name in names {
+            self.import_dotted(name);
+        }
+    }
+
+    // `decl` with the bare `Bytes`, `BytesMut` and `ByteBuf` nobody declared or mapped as byte buffers
+    fn bare_bytes(&self, mut decl: DeclaredType<'a>) -> DeclaredType<'a> {
+        let lower = |ty: &mut Type<'a>| self.lower_bare_bytes(ty);
+
+        match &mut decl.inner {
+            InnerType::Struct(struc) => struc.fields.iter_mut().for_each(|f| lower(&mut f.ty)),
+            InnerType::Enum(enum_) => {
+                for variant in &mut enum_.variants {
+                    match &mut variant.inner {
+                        EnumVariantInner::Unit => {}
+                        EnumVariantInner::Tuple(types) => types.iter_mut().for_each(lower),
+                        EnumVariantInner::Struct(struc) => {
+                            struc.fields.iter_mut().for_each(|f| lower(&mut f.ty))
+                        }
+                    }
+                }
+            }
+        }
+
+        decl
+    }
+
+    fn lower_bare_bytes(&self, ty: &mut Type<'a>) {
+        match ty {
+            Type::User(path) if is_bare_bytes(path) => {
+                let declared = self.types.iter().any(|decl| decl.name == path.name());
+
+                if !declared
+                    && mapping::find(&self.options.type_mappings, &path.segments, 0).is_none()
+                {
+                    *ty = Type::Bytes(Bytes::Native);
+                }
+            }
+            Type::User(path) => path
+                .args
+                .iter_mut()
+                .for_each(|ty| self.lower_bare_bytes(ty)),
+            Type::Option(ty) | Type::Array(ty) | Type::Vec(ty) | Type::Set(ty) => {
+                self.lower_bare_bytes(ty)
+            }
+            Type::Map(key, value) => {
+                self.lower_bare_bytes(key);
+                self.lower_bare_bytes(value);
+            }
+            Type::Tuple(types) => types.iter_mut().for_each(|ty| self.lower_bare_bytes(ty)),
+            _ => {}
+        }
+    }
+
+    // the user's mapping for a path, or a built-in one if it isn't one of the declared types
+    fn mapping(&self, path: &TypePath) -> Option<mapping::Found<'_>> {
+        let args = path.args.len();
+
+        mapping::find(&self.options.type_mappings, &path.segments, args).or_else(|| {
+            let declared = self.types.iter().any(|decl| decl.name == path.name());
+
+            (!declared)
+                .then(|| mapping::find(&self.builtins, &path.segments, args))
+                .flatten()
+        })
+    }
+
+    // the Python type of a mapped Rust type
+    fn generate_mapped_type(&mut self, path: &TypePath<'a>) -> Result<Option<String>, Diagnostic> {
+        let Some(found) = self.mapping(path) else {
+            return Ok(None);
+        };
+
+        let mapping = found.mapping.clone();
+        let params = found
+            .params
+            .iter()
+            .map(|param| param.to_string())
+            .collect_vec();
+
+        // arguments the Python type doesn't use needn't be Python types at all, like `Utc`
+        let used = params
+            .iter()
+            .map(|param| mapping::uses(&mapping.python, param))
+            .collect_vec();
+
+        let args: Vec<_> = path
+            .args
+            .iter()
+            .zip(&used)
+            .map(|(arg, used)| match used {
+                true => self.generate_type(arg),
+                false => Ok(String::new()),
+            })
+            .try_collect()?;
+
+        let params = params.iter().map(String::as_str).collect_vec();
+        let python = mapping::substitute(&mapping.python, &params, &args);
+
+        self.import_type(&python);
+
+        let hooks = [&mapping.encode, &mapping.decode];
+
+        for hook in hooks.into_iter().flatten() {
+            self.import_dotted(hook);
+        }
+
+        // direct mode can only convert the arguments of lists, the runtime follows the annotations
+        let opaque = mapping.encode.is_none() || mapping.decode.is_none();
+
+        if self.options.mode == Mode::Direct && opaque && !mapping::is_list(&python) {
+            let converted = path
+                .args
+                .iter()
+                .zip(&used)
+                .any(|(arg, used)| *used && self.converts(arg));
+
+            if converted {
+                return Err(Diagnostic::error(format!(
+                    "direct mode can't convert the type arguments of `{}`",
+                    path.segments.join("::")
+                ))
+                .with_note(format!("it's mapped to `{python}`, which isn't a `list`"))
+                .with_note("give the mapping `encode` and `decode` hooks"));
+            }
+        }
+
+        // direct mode calls the hooks itself
+        if self.options.mode == Mode::Reflection && hooks.iter().any(|hook| hook.is_some()) {
+            let rust = path.segments.join("::");
+
+            let mut hooks = String::new();
+
+            if let Some(encode) = &mapping.encode {
+                hooks.push_str(&format!(", encode={encode}"));
+            }
+
+            if let Some(decode) = &mapping.decode {
+                hooks.push_str(&format!(", decode={decode}"));
+            }
+
+            // the runtime looks hooks up by class, ignoring generic arguments
+            for class in mapping::classes(&python) {
+                if BUILTIN_TYPES.contains(&class) {
+                    return Err(Diagnostic::error(format!(
+                        "the mapping for `{rust}` has hooks for the builtin type `{class}`"
+                    ))
+                    .with_note("the runtime would use them for every value of that type")
+                    .with_note("map it to a type of its own, or use `--direct`"));
+                }
+
+                let codec = format!("register_codec({class}{hooks})");
+
+                match self.codecs.get(class) {
+                    Some((registered, other)) if *registered != codec => {
+                        return Err(Diagnostic::error(format!(
+                            "`{rust}` and `{other}` are both mapped to `{class}`, with different hooks"
+                        ))
+                        .with_note("the runtime looks hooks up by class, so one would be used for both")
+                        .with_note("map one of them to another type, or use `--direct`"));
+                    }
+                    Some(_) => {}
+                    None => {
+                        self.codecs.insert(class.to_string(), (codec, rust.clone()));
+                    }
+                }
+            }
+        }
+
+        Ok(Some(python))
+    }
+
+    // the callable registered for the Rust function `path`
+    // or for its module, when it's the module's `serialize` or `deserialize`
+    fn registered_hook(&self, path: &str, function: &str) -> Option<String> {
+        let codecs = &self.options.codecs;
+
+        let side: fn(&Codec) -> &Option<String> = match function {
+            "serialize" => |codec| &codec.encode,
+            _ => |codec| &codec.decode,
+        };
+
+        mapping::find_codec(codecs, path, side)
+            .or_else(|| {
+                let module = path.strip_suffix(function)?.strip_suffix("::")?;
+                mapping::find_codec(codecs, module, side)
+            })
+            .map(str::to_string)
+    }
+
+    // the registered hooks for a field's `with`, `serialize_with` and `deserialize_with`
+    fn field_hooks(&self, field: &StructField) -> FieldHooks {
+        let mut hooks = FieldHooks::default();
+
+        for attr in &field.attrs {
+            match attr {
+                SerdeAttribute::With(module) if !builtin_with(module) => {
+                    hooks.encode =
+                        self.registered_hook(&format!("{module}::serialize"), "serialize");
+                    hooks.decode =
+                        self.registered_hook(&format!("{module}::deserialize"), "deserialize");
+                }
+                SerdeAttribute::SerializeWith(path) => {
+                    hooks.encode = self.registered_hook(path, "serialize");
+                }
+                SerdeAttribute::DeserializeWith(path) => {
+                    hooks.decode = self.registered_hook(path, "deserialize");
+                }
+                _ => {}
+            }
+        }
+
+        hooks
+    }
+
+    // every custom serialization the field needs has a hook, and their modules are imported
+    fn check_field_hooks(&mut self, field: &StructField) -> Result<FieldHooks, Diagnostic> {
+        let hooks = self.field_hooks(field);
+
+        let needs_encode = !skips_serializing(field) && hooks.encode.is_none();
+        let needs_decode = !skips_deserializing(field) && hooks.decode.is_none();
+
+        for attr in &field.attrs {
+            let (attr, path, missing) = match attr {
+                SerdeAttribute::With(path) if !builtin_with(path) => {
+                    ("with", path, needs_encode || needs_decode)
+                }
+                SerdeAttribute::SerializeWith(path) => ("serialize_with", path, needs_encode),
+                SerdeAttribute::DeserializeWith(path) => ("deserialize_with", path, needs_decode),
+                _ => continue,
+            };
+
+            if missing {
+                let register = match attr {
+                    "with" => format!(
+                        "register them with `--serialize-with {path}=<callable> --deserialize-with {path}=<callable>`"
+                    ),
+                    _ => format!("register one with `--{}-with {path}=<callable>`", &attr[..attr.len() - 5]),
+                };
+
+                return Err(Diagnostic::error(format!(
+                    "no Python equivalent for `{attr} = \"{path}\"`"
+                ))
+                .with_label("has custom serialization")
+                .with_note(register)
+                .with_note("or in `[python.codecs]` in marshal.toml"));
+            }
+        }
+
+        for hook in [&hooks.encode, &hooks.decode].into_iter().flatten() {
+            self.import_dotted(hook);
+        }
+
+        Ok(hooks)
+    }
+
+    // the Python class for sets
+    fn set_class(&self) -> &'static str {
+        if self.options.frozen_sets {
+            "frozenset"
+        } else {
+            "set"
+        }
+    }
+
+    // the runtime's encoding for a byte buffer
+    fn bytes_encoding(&self, bytes: Bytes) -> &'static str {
+        match bytes {
+            Bytes::Native if self.options.raw_bytes => "RAW_BYTES",
+            Bytes::Native => "BYTE_ARRAY",
+            Bytes::Base64 {
+                url_safe: false,
+                padded: true,
+            } => "BASE64",
+            Bytes::Base64 {
+                url_safe: false,
+                padded: false,
+            } => "BASE64_UNPADDED",
+            Bytes::Base64 {
+                url_safe: true,
+                padded: true,
+            } => "BASE64_URL_SAFE",
+            Bytes::Base64 {
+                url_safe: true,
+                padded: false,
+            } => "BASE64_URL_SAFE_UNPADDED",
+        }
+    }
+
+    // values of `ty` can be set items, mapped types are assumed to be unless they're lists
+    fn hashable(&self, ty: &Type<'a>) -> bool {
+        match ty {
+            Type::Unit
+            | Type::Bool
+            | Type::Int(_)
+            | Type::Float(_)
+            | Type::Char
+            | Type::String
+            | Type::Bytes(_) => true,
+            Type::Json | Type::Vec(_) | Type::Array(_) | Type::Map(..) => false,
+            Type::Set(_) => self.options.frozen_sets,
+            Type::Option(ty) => self.hashable(ty),
+            Type::Tuple(types) => types.iter().all(|ty| self.hashable(ty)),
+            Type::User(path) => self
+                .mapping(path)
+                .is_some_and(|found| !mapping::is_list(&found.mapping.python)),
+        }
+    }
+
+    fn generate_type(&mut self, type_: &Type<'a>) -> Result<String, Diagnostic> {
+        let python = match type_ {
             Type::String => "str".to_string(),
-            Type::Int => "int".to_string(),
+            // the runtime checks the length
+            Type::Char => "Annotated[str, CHAR]".to_string(),
+            // a recursive alias, defined by the runtime
+            Type::Json => "JsonValue".to_string(),
+            Type::Bytes(bytes) => format!("Annotated[bytes, {}]", self.bytes_encoding(*bytes)),
+            // the runtime checks the range
+            Type::Int(int) => format!("Annotated[int, {}]", int.name().to_uppercase()),
             Type::Bool => "bool".to_string(),
             Type::Unit => "None".to_string(),
-            Type::Float => "float".to_string(),
+            Type::Float(float) => format!("Annotated[float, {}]", float.name().to_uppercase()),
             Type::Tuple(types) => {
-                let types: Vec<_> = types.iter().map(|t| self.generate_type(t)).collect();
+                let types: Vec<_> = types.iter().map(|t| self.generate_type(t)).try_collect()?;
                 format!("tuple[{}]", types.join(", "))
             }
-            Type::Vec(ty) => format!("list[{}]", self.generate_type(ty)),
-            Type::Array(ty) => format!("list[{}]", self.generate_type(ty)),
-            Type::User(name) => name.to_string(),
-            Type::Option(ty) => format!("{} | None", self.generate_type(&*ty)),
+            Type::Vec(ty) => format!("list[{}]", self.generate_type(ty)?),
+            Type::Array(ty) => format!("list[{}]", self.generate_type(ty)?),
+            Type::Set(ty) => {
+                if !self.hashable(ty) {
+                    return Err(Diagnostic::error(format!(
+                        "the items of a `{}` have to be hashable",
+                        self.set_class()
+                    ))
+                    .with_label("in this set")
+                    .with_note("the generated dataclasses, `list`s, `dict`s and `set`s aren't")
+                    .with_note("use a `Vec` instead, or `--frozen-sets` for sets of sets"));
+                }
+
+                format!("{}[{}]", self.set_class(), self.generate_type(ty)?)
+            }
+            Type::Map(key, value) => {
+                if !self.hashable(key) {
+                    return Err(
+                        Diagnostic::error("the keys of a `dict` have to be hashable")
+                            .with_label("in this map")
+                            .with_note(
+                                "the generated dataclasses, `list`s, `dict`s and `set`s aren't",
+                            )
+                            .with_note("`--frozen-sets` makes sets hashable"),
+                    );
+                }
+
+                format!(
+                    "dict[{}, {}]",
+                    self.generate_type(key)?,
+                    self.generate_type(value)?
+                )
+            }
+            Type::User(path) => match self.generate_mapped_type(path)? {
+                Some(python) => python,
+                None if self.types.iter().any(|decl| decl.name == path.name()) => {
+                    Self::safe_name(path.name()).to_string()
+                }
+                None => {
+                    let path = path.segments.join("::");
+
+                    return Err(Diagnostic::error(format!("cannot find type `{path}`"))
+                        .with_label("used here")
+                        .with_note("it isn't declared in the inputs")
+                        .with_note(format!(
+                            "map it to a Python type with `--type {path}=<type>`, or in `[types]` in marshal.toml"
+                        )));
+                }
+            },
+            Type::Option(ty) => format!("{} | None", self.generate_type(ty)?),
+        };
+
+        Ok(python)
+    }
+
+    // the value `Default::default()` produces for `type_`
+    fn type_default(&mut self, type_: &Type<'a>) -> Result<DefaultValue, Diagnostic> {
+        use DefaultValue::*;
+
+        let default = match type_ {
+            Type::String => Value("\"\"".to_string()),
+            Type::Char => Value("\"\\0\"".to_string()),
+            Type::Json => Value("None".to_string()),
+            Type::Bytes(_) => Value("b\"\"".to_string()),
+            Type::Int(_) => Value("0".to_string()),
+            Type::Bool => Value("False".to_string()),
+            Type::Unit => Value("None".to_string()),
+            Type::Float(_) => Value("0.0".to_string()),
+            Type::Tuple(types) => {
+                let values: Vec<_> = types
+                    .iter()
+                    .map(|ty| self.type_default(ty))
+                    .collect::<Result<_, Diagnostic>>()?;
+
+                // a tuple holding a list is made anew for every instance, so they don't share it
+                let immutable = values.iter().all(|value| matches!(value, Value(_)));
+                let values = values.into_iter().map(DefaultValue::expr).collect_vec();
+
+                let tuple = match &values[..] {
+                    [value] => format!("({value},)"),
+                    _ => format!("({})", values.join(", ")),
+                };
+
+                if immutable {
+                    Value(tuple)
+                } else {
+                    Mutable(tuple)
+                }
+            }
+            Type::Vec(_) => Factory("list".to_string()),
+            Type::Array(_) => Factory("list".to_string()),
+            Type::Set(_) => Factory(self.set_class().to_string()),
+            Type::Map(..) => Factory("dict".to_string()),
+            Type::Option(_) => Value("None".to_string()),
+            Type::User(path) => {
+                let mapped = self
+                    .mapping(path)
+                    .and_then(|found| found.mapping.default.clone());
+
+                match mapped {
+                    Some(default) => {
+                        self.import_type(&default);
+                        Value(default)
+                    }
+                    None => self.user_default(path.name())?,
+                }
+            }
+        };
+
+        Ok(default)
+    }
+
+    // `Default::default()` for a declared type
+    // either registered by the user, or derived
+    fn user_default(&mut self, name: &str) -> Result<DefaultValue, Diagnostic> {
+        if let Some(default) = self.registered_default(&format!("{name}::default")) {
+            return Ok(default);
+        }
+
+        let register = format!("register one with `--default {name}::default=<callable>`");
+
+        let types = Rc::clone(&self.types);
+
+        let Some(decl) = types.iter().find(|decl| decl.name == name) else {
+            return Err(
+                Diagnostic::error(format!("cannot find `{name}` to compute its default"))
+                    .with_note(register),
+            );
+        };
+
+        if !decl.has_derive("Default") {
+            return Err(
+                Diagnostic::error(format!("`{name}` does not derive `Default`"))
+                    .with_span(decl.span)
+                    .with_file(decl.file)
+                    .with_label("needed for a default value")
+                    .with_note(register),
+            );
+        }
+
+        match &decl.inner {
+            InnerType::Struct(struc) => {
+                let args: Vec<_> = struc
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        let default = self.type_default(&field.ty)?.expr();
+                        Ok(format!("{}={}", field.name, default))
+                    })
+                    .collect::<Result<_, Diagnostic>>()?;
+
+                Ok(DefaultValue::Mutable(format!(
+                    "{}({})",
+                    Self::safe_name(name),
+                    args.join(", ")
+                )))
+            }
+            InnerType::Enum(enum_) => {
+                let Some(variant) = enum_.variants.iter().find(|variant| variant.default) else {
+                    return Err(Diagnostic::error(format!(
+                        "`{name}` derives `Default` without a `#[default]` variant"
+                    ))
+                    .with_span(decl.span)
+                    .with_file(decl.file));
+                };
+
+                Ok(DefaultValue::Factory(
+                    Self::safe_name(variant.name).to_string(),
+                ))
+            }
+        }
+    }
+
+    // a callable the user registered for the Rust function `path`
+    fn registered_default(&mut self, path: &str) -> Option<DefaultValue> {
+        let callable = self.options.default_fns.get(path)?.clone();
+
+        self.import_dotted(&callable);
+
+        Some(DefaultValue::Factory(callable))
+    }
+
+    // `path` is one of the spellings of `Default::default`, itself or on a type, like `u32::default` or `<Vec<u8>>::default`
+    // `defaults::default` is a function of the user's, modules aren't capitalized
+    fn is_default_fn(path: &str) -> bool {
+        const PRIMITIVES: &[&str] = &[
+            "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32",
+            "i64", "i128", "isize", "f32", "f64",
+        ];
+
+        match path.strip_suffix("::default") {
+            Some("Default" | "std::default::Default" | "core::default::Default") => true,
+            Some(ty) if ty.starts_with('<') && ty.ends_with('>') => true,
+            Some(ty) => {
+                !ty.contains("::")
+                    && (ty.starts_with(char::is_uppercase) || PRIMITIVES.contains(&ty))
+            }
+            None => false,
         }
     }
 
-    fn generate_field(&mut self, field: &StructField<'a>) {
-        let tyname = self.generate_type(&field.ty);
+    // the Python equivalent of calling the Rust function `path`
+    fn path_default(&mut self, path: &str, type_: &Type<'a>) -> Result<DefaultValue, Diagnostic> {
+        use DefaultValue::*;
+
+        if let Some(default) = self.registered_default(path) {
+            return Ok(default);
+        }
 
+        match path {
+            "Vec::new" | "VecDeque::new" => Ok(Factory("list".to_string())),
+            "String::new" => Ok(Value("\"\"".to_string())),
+            "HashMap::new" | "BTreeMap::new" => Ok(Factory("dict".to_string())),
+            "HashSet::new" | "BTreeSet::new" => Ok(Factory(self.set_class().to_string())),
+            // `Default::default`, `u32::default`, `std::default::Default::default`, ...
+            _ if Self::is_default_fn(path) => self.type_default(type_),
+            _ => Err(Diagnostic::error(format!(
+                "no Python equivalent for default function `{path}`"
+            ))
+            .with_label("has a custom default")
+            .with_note(format!("register one with `--default {path}=<callable>`"))),
+        }
+    }
+
+    // the default of a field which isn't marked `#[serde(default)]` itself
+    // but whose container is, taken from the container's default value
+    fn container_default(
+        &mut self,
+        container: &str,
+        path: Option<&str>,
+        field: &StructField<'a>,
+    ) -> Result<DefaultValue, Diagnostic> {
+        let path = path
+            .filter(|path| !Self::is_default_fn(path))
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{container}::default"));
+
+        if let Some(default) = self.registered_default(&path) {
+            return Ok(DefaultValue::Mutable(format!(
+                "{}.{}",
+                default.expr(),
+                field.name
+            )));
+        }
+
+        let decl = self.types.iter().find(|decl| decl.name == container);
+
+        match decl {
+            Some(decl) if decl.has_derive("Default") => self.type_default(&field.ty),
+            _ => Err(Diagnostic::error(format!(
+                "`{container}` has a container default but no derived `Default`"
+            ))
+            .with_span(decl.map(|decl| decl.span).unwrap_or(field.span))
+            .with_label("needs a default value")
+            .with_note(format!("register one with `--default {path}=<callable>`"))),
+        }
+    }
+
+    fn field_default(
+        &mut self,
+        container: &str,
+        container_attrs: &[SerdeAttribute<'a>],
+        field: &StructField<'a>,
+    ) -> Result<Option<DefaultValue>, Diagnostic> {
+        self.field_default_inner(container, container_attrs, field)
+            .map_err(|err| err.or_span(field.span))
+    }
+
+    fn field_default_inner(
+        &mut self,
+        container: &str,
+        container_attrs: &[SerdeAttribute<'a>],
+        field: &StructField<'a>,
+    ) -> Result<Option<DefaultValue>, Diagnostic> {
         let mut default = None;
 
         for attr in &field.attrs {
-            match attr {
-                SerdeAttribute::Default(val) => {
-                    if let Some(val) = val {
-                        default = Some(*val);
-                    } else {
-                        default = match &field.ty {
-                            Type::String => Some("\"\""),
-                            Type::Int => Some("0"),
-                            Type::Bool => Some("False"),
-                            Type::Unit => Some("None"),
-                            Type::Float => Some("0.0"),
-                            Type::Tuple(_) => Some("()"),
-                            Type::Vec(_) => Some("[]"),
-                            Type::Array(_) => Some("[]"),
-                            Type::Option(_) => Some("None"),
-                            Type::User(_) => todo!("default for user type"),
-                        };
+            if let SerdeAttribute::Default(val) = attr {
+                default = Some(match val {
+                    // the outer `None` is an absent field
+                    None if double_option(field).is_some() => {
+                        DefaultValue::Value("ABSENT".to_string())
                     }
-                }
-                _ => {} // ignore other attributes for now,
+                    Some(path) => self.path_default(path, &field.ty)?,
+                    None => self.type_default(&field.ty)?,
+                });
+            }
+        }
+
+        if default.is_some() {
+            return Ok(default);
+        }
+
+        // skipped fields are always `Default::default()`
+        let skipped = field.attrs.iter().any(|attr| {
+            matches!(
+                attr,
+                SerdeAttribute::Skip | SerdeAttribute::SkipDeserializing
+            )
+        });
+
+        if skipped {
+            return self.type_default(&field.ty).map(Some);
+        }
+
+        // `#[serde(default)]` on the container
+        // makes every field optional
+        let container_default = container_attrs.iter().find_map(|attr| match attr {
+            SerdeAttribute::Default(path) => Some(*path),
+            _ => None,
+        });
+
+        if let Some(path) = container_default {
+            return self.container_default(container, path, field).map(Some);
+        }
+
+        // serde treats a missing `Option` as `None`
+        // unless it's deserialized by custom code
+        let custom = field.attrs.iter().any(|attr| {
+            matches!(
+                attr,
+                SerdeAttribute::DeserializeWith(..) | SerdeAttribute::With(..)
+            )
+        });
+
+        match &field.ty {
+            Type::Option(_) if !custom => Ok(Some(DefaultValue::Value("None".to_string()))),
+            _ => Ok(None),
+        }
+    }
+
+    fn generate_field(
+        &mut self,
+        field: &StructField<'a>,
+        default: Option<DefaultValue>,
+    ) -> Result<(), Diagnostic> {
+        let tyname = match double_option(field) {
+            Some(ty) => format!("{} | None | Absent", self.generate_type(ty)?),
+            None => self.generate_type(&field.ty)?,
+        };
+
+        match default {
+            Some(DefaultValue::Value(value)) => {
+                output!(self, "    {}: {} = {}\n", field.name, tyname, value);
+            }
+            Some(DefaultValue::Factory(factory)) => {
+                output!(
+                    self,
+                    "    {}: {} = field(default_factory={})\n",
+                    field.name,
+                    tyname,
+                    factory
+                );
+            }
+            Some(DefaultValue::Mutable(expr)) => {
+                output!(
+                    self,
+                    "    {}: {} = field(default_factory=lambda: {})\n",
+                    field.name,
+                    tyname,
+                    expr
+                );
+            }
+            None => {
+                output!(self, "    {}: {}\n", field.name, tyname);
             }
         }
 
-        if let Some(default) = default {
-            output!(self, "    {}: {} = {}\n", field.name, tyname, default);
+        Ok(())
+    }
+
+    // `positional` classes are built from a sequence, so they're never `kw_only`
+    fn generate_dataclass_decorator(&mut self, positional: bool) {
+        // direct mode doesn't need the runtime's hooks
+        let decorator = match self.options.mode {
+            Mode::Reflection => "dataclass",
+            Mode::Direct => "dataclasses.dataclass",
+        };
+
+        if self.options.kw_only && !positional {
+            output!(self, "@{decorator}(kw_only=True)\n");
         } else {
-            output!(self, "    {}: {}\n", field.name, tyname);
+            output!(self, "@{decorator}\n");
         }
     }
 
-    fn generate_struct(&mut self, name: &str, struc: &Struct<'a>) {
-        output!(self, "@dataclass\n");
+    // returns the fields in the order they were generated
+    // and whether they have a default
+    // the hooks of the fields with custom serialization
+    fn struct_hooks<'b>(
+        &mut self,
+        struc: &'b Struct<'a>,
+    ) -> Result<Vec<(&'b str, FieldHooks)>, Diagnostic> {
+        let mut hooks = Vec::new();
+
+        for field in &struc.fields {
+            let field_hooks = self
+                .check_field_hooks(field)
+                .map_err(|err| err.or_span(field.span))?;
+
+            if field_hooks.encode.is_some() || field_hooks.decode.is_some() {
+                hooks.push((field.name, field_hooks));
+            }
+        }
+
+        Ok(hooks)
+    }
+
+    fn generate_fields<'b>(
+        &mut self,
+        container: &str,
+        attrs: &[SerdeAttribute<'a>],
+        struc: &'b Struct<'a>,
+    ) -> Result<Vec<(&'b StructField<'a>, bool)>, Diagnostic> {
+        let fields: Vec<_> = struc
+            .fields
+            .iter()
+            .map(|field| Ok((field, self.field_default(container, attrs, field)?)))
+            .collect::<Result<_, Diagnostic>>()?;
+
+        let fields = if self.options.kw_only {
+            fields
+        } else {
+            fields
+                .into_iter()
+                .sorted_by_key(|(_, default)| default.is_some())
+                .collect()
+        };
+
+        let fields = fields
+            .into_iter()
+            .map(|(field, default)| {
+                let has_default = default.is_some();
+
+                if self.options.mode == Mode::Direct {
+                    self.check_field_hooks(field)
+                        .map_err(|err| err.or_span(field.span))?;
+                }
+
+                self.generate_field(field, default)
+                    .map_err(|err| err.or_span(field.span))?;
+
+                Ok((field, has_default))
+            })
+            .collect::<Result<_, Diagnostic>>()?;
+
+        Ok(fields)
+    }
+
+    fn generate_struct(
+        &mut self,
+        name: &str,
+        attrs: &[SerdeAttribute<'a>],
+        struc: &Struct<'a>,
+    ) -> Result<(), Diagnostic> {
+        self.generate_dataclass_decorator(false);
         output!(self, "class {}:\n", name);
-        output!(self, "    {}\n", support_struct(struc));
 
-        let fields = struc.fields.iter().sorted_by_key(|field| {
-            field
-                .attrs
-                .iter()
-                .any(|attr| matches!(attr, SerdeAttribute::Default(..)))
-        });
+        if self.options.mode == Mode::Reflection {
+            let hooks = self.struct_hooks(struc)?;
+            output!(self, "    {}\n", support_struct(struc, &hooks));
+        } else {
+            output!(self, "\n");
+        }
+
+        let fields = self.generate_fields(name, attrs, struc)?;
 
-        for field in fields {
-            self.generate_field(&field);
+        if self.options.mode == Mode::Direct {
+            output!(self, "\n\n");
+            self.generate_struct_codec(name, &fields);
         }
+
+        Ok(())
     }
 
     fn generate_enum_tuple(
@@ -303,43 +1295,53 @@ impl<'a> Codegen<'a> {
         name: &str,
         _attrs: &[SerdeAttribute<'a>],
         types: &[Type<'a>],
-    ) {
-        output!(self, "@dataclass\n");
-        output!(self, "class {}(TupleVariant):\n", name);
-        output!(
-            self,
-            "    ENUM_DATA = (ENUM_VARIANT_TUPLE, \"{}\")\n\n",
-            Self::safe_name(name)
-        );
+    ) -> Result<(), Diagnostic> {
+        // its fields are numbered and have no defaults, so their order is always the declared one
+        self.generate_dataclass_decorator(true);
+        output!(self, "class {}(TupleVariant):\n", Self::safe_name(name));
+
+        if self.options.mode == Mode::Reflection {
+            output!(self, "    ENUM_DATA = (ENUM_VARIANT_TUPLE, \"{}\")\n", name);
+        }
+
+        output!(self, "\n");
 
         for (i, ty) in types.iter().enumerate() {
-            let tyname = self.generate_type(ty);
+            let tyname = self.generate_type(ty)?;
             output!(self, "    _{}: {}\n", i, tyname);
         }
 
         output!(self, "\n");
+
+        Ok(())
     }
 
-    fn generate_enum_struct(
+    fn generate_enum_struct<'b>(
         &mut self,
         name: &str,
         _attrs: &[SerdeAttribute<'a>],
-        struc: &Struct<'a>,
-    ) {
-        output!(self, "@dataclass\n");
-        output!(self, "class {}:\n", name);
-        output!(
-            self,
-            "    ENUM_DATA = (ENUM_VARIANT_STRUCT, \"{}\")\n",
-            Self::safe_name(name)
-        );
-        output!(self, "    {}\n", support_struct(struc));
+        struc: &'b Struct<'a>,
+    ) -> Result<Vec<(&'b StructField<'a>, bool)>, Diagnostic> {
+        self.generate_dataclass_decorator(false);
+        output!(self, "class {}:\n", Self::safe_name(name));
 
-        for field in &struc.fields {
-            self.generate_field(&field);
+        if self.options.mode == Mode::Reflection {
+            let hooks = self.struct_hooks(struc)?;
+            output!(
+                self,
+                "    ENUM_DATA = (ENUM_VARIANT_STRUCT, \"{}\")\n",
+                name
+            );
+            output!(self, "    {}\n", support_struct(struc, &hooks));
+        } else {
+            output!(self, "\n");
         }
 
+        let fields = self.generate_fields(name, &[], struc)?;
+
         output!(self, "\n");
+
+        Ok(fields)
     }
 
     fn safe_name(name: &str) -> &str {
@@ -352,29 +1354,46 @@ impl<'a> Codegen<'a> {
         }
     }
 
-    fn generate_enum_variant(&mut self, variant: &EnumVariant<'a>) {
+    // returns the generated fields of a struct variant
+    fn generate_enum_variant<'b>(
+        &mut self,
+        variant: &'b EnumVariant<'a>,
+    ) -> Result<Option<Vec<(&'b StructField<'a>, bool)>>, Diagnostic> {
         let name = &variant.name;
 
         match &variant.inner {
             EnumVariantInner::Unit => {
-                output!(self, "@dataclass\n");
+                self.generate_dataclass_decorator(false);
                 output!(self, "class {}:\n", Self::safe_name(name));
-                output!(
-                    self,
-                    "    ENUM_DATA = (ENUM_VARIANT_UNIT, \"{}\")\n\n",
-                    variant.name
-                );
+
+                if self.options.mode == Mode::Reflection {
+                    output!(
+                        self,
+                        "    ENUM_DATA = (ENUM_VARIANT_UNIT, \"{}\")\n\n",
+                        variant.name
+                    );
+                } else {
+                    output!(self, "    pass\n\n");
+                }
+
+                Ok(None)
             }
-            EnumVariantInner::Tuple(types) => self.generate_enum_tuple(name, &variant.attrs, types),
-            EnumVariantInner::Struct(struc) => {
-                self.generate_enum_struct(name, &variant.attrs, struc)
+            EnumVariantInner::Tuple(types) => {
+                self.generate_enum_tuple(name, &variant.attrs, types)?;
+
+                Ok(None)
             }
+            EnumVariantInner::Struct(struc) => self
+                .generate_enum_struct(name, &variant.attrs, struc)
+                .map(Some),
         }
     }
 
-    fn generate_enum(&mut self, name: &str, enum_: &Enum<'a>) {
+    fn generate_enum(&mut self, name: &str, enum_: &Enum<'a>) -> Result<(), Diagnostic> {
+        let mut fields = Vec::new();
+
         for variant in &enum_.variants {
-            self.generate_enum_variant(variant);
+            fields.extend(self.generate_enum_variant(variant)?);
         }
 
         let variants = enum_
@@ -384,29 +1403,173 @@ impl<'a> Codegen<'a> {
             .join(" | ");
 
         output!(self, "{name} = {variants}\n");
+
+        if self.options.mode == Mode::Direct {
+            output!(self, "\n\n");
+            self.generate_enum_codec(name, enum_, &fields);
+        }
+
+        Ok(())
     }
 
-    fn generate_decl_type(&mut self, type_: &DeclaredType<'a>) {
-        let name = Self::safe_name(&type_.name);
+    fn generate_decl_type(&mut self, type_: &DeclaredType<'a>) -> Result<(), Diagnostic> {
+        let name = Self::safe_name(type_.name);
+
+        // errors without a file or span are about this type
+        let in_file = |err: Diagnostic| err.or_file(type_.file).or_span(type_.span);
 
         match &type_.inner {
-            InnerType::Struct(struc) => self.generate_struct(name, struc),
-            InnerType::Enum(enum_) => self.generate_enum(name, enum_),
+            InnerType::Struct(struc) => self
+                .generate_struct(name, &type_.attrs, struc)
+                .map_err(in_file)?,
+            InnerType::Enum(enum_) => self.generate_enum(name, enum_).map_err(in_file)?,
         }
 
         output!(self, "\n\n");
+
+        Ok(())
+    }
+
+    fn generate_runtime_import(&mut self) {
+        let version = runtime::VERSION;
+
+        match self.options.runtime.clone() {
+            Runtime::Import(module) => {
+                output!(self, "from {module} import *\n\n");
+            }
+            Runtime::Write => {
+                let module = runtime::MODULE;
+
+                // works both inside a package and as a top-level module
+                output!(
+                    self,
+                    "{}",
+                    formatdoc!(
+                        "
+                        try:
+                            from .{module} import *
+                        except ImportError:
+                            from {module} import *
+
+                        "
+                    )
+                );
+            }
+            Runtime::Inline => {
+                output!(self, "\n# marshal runtime, protocol version {version}\n\n");
+                output!(self, "{}\n", runtime::SOURCE);
+                output!(self, "# end of marshal runtime\n\n");
+
+                // it can't be out of date
+                return;
+            }
+        }
+
+        output!(
+            self,
+            "{}",
+            formatdoc!(
+                "
+                if globals().get(\"MARSHAL_RUNTIME_VERSION\") != {version}:
+                    raise ImportError(
+                        \"this module needs marshal runtime protocol version {version}, \"
+                        \"regenerate it or update the runtime\"
+                    )
+
+                "
+            )
+        );
+    }
+
+    // `from .sibling import ...` for the siblings' types this module uses
+    // the functions direct mode calls are imported along with the classes
+    fn generate_sibling_imports(&mut self) {
+        let mut imports: BTreeMap<&str, BTreeSet<String>> = BTreeMap::new();
+
+        for decl in &self.types[..self.declared] {
+            for dep in deps::decl_deps(decl) {
+                let Some(module) = self.siblings.get(dep) else {
+                    continue;
+                };
+
+                let name = Self::safe_name(dep);
+                let names = imports.entry(module).or_default();
+
+                names.insert(name.to_string());
+
+                if self.options.mode == Mode::Direct {
+                    names.insert(format!("encode_{name}"));
+                    names.insert(format!("decode_{name}"));
+                }
+            }
+        }
+
+        if imports.is_empty() {
+            return;
+        }
+
+        // like the runtime, works both inside a package and as top-level modules
+        let lines = |relative: &str| {
+            imports
+                .iter()
+                .map(|(module, names)| {
+                    format!(
+                        "    from {relative}{module} import {}\n",
+                        names.iter().join(", ")
+                    )
+                })
+                .collect::<String>()
+        };
+
+        let code = format!("try:\n{}except ImportError:\n{}\n", lines("."), lines(""));
+
+        self.write(&code);
     }
 
-    pub fn generate(mut self) -> String {
-        output!(self, "# Generated code\n\n");
-        output!(self, "from lib.marshal import *\n\n");
+    pub fn generate(mut self) -> Result<String, Diagnostic> {
+        // every declaration is known now, the siblings' too
+        let types = self.types.iter().cloned().map(|decl| self.bare_bytes(decl));
+        self.types = types.collect();
 
-        let types = std::mem::take(&mut self.types);
+        let types = Rc::clone(&self.types);
 
-        for type_ in &types {
-            self.generate_decl_type(type_);
+        for type_ in &types[..self.declared] {
+            self.generate_decl_type(type_)?;
         }
 
-        self.output
+        let body = std::mem::take(&mut self.output);
+
+        output!(
+            self,
+            "# Generated code, marshal runtime protocol version {}\n\n",
+            runtime::VERSION
+        );
+
+        // annotations are evaluated lazily by the runtime
+        // so (mutually) recursive types can refer to each other
+        output!(self, "from __future__ import annotations\n\n");
+
+        if self.options.mode == Mode::Direct {
+            self.imports.insert("dataclasses".to_string());
+        }
+
+        for module in std::mem::take(&mut self.imports) {
+            output!(self, "import {module}\n");
+        }
+
+        self.generate_runtime_import();
+        self.generate_sibling_imports();
+
+        for (codec, _) in std::mem::take(&mut self.codecs).into_values() {
+            output!(self, "{codec}\n");
+        }
+
+        if !self.output.ends_with("\n\n") {
+            output!(self, "\n");
+        }
+
+        self.output.push_str(&body);
+
+        Ok(self.output)
     }
 }
@@ -0,0 +1,490 @@
+// Straight-line encoders and decoders for `Mode::Direct`
+//
+// every declared type gets an `encode_<Type>` and a `decode_<Type>` function
+// which spell out the work the runtime would otherwise do by introspecting
+// the dataclasses, so they're fast and don't depend on `typing` internals
+//
+// they have to produce the same data as the reflection runtime does
+
+use itertools::Itertools;
+
+use super::{double_option, skip_predicate, Codegen};
+use crate::mapping;
+use crate::parser::{Enum, EnumVariantInner, SerdeAttribute, StructField, Type};
+
+// the key a field is serialized as
+fn key<'a>(field: &StructField<'a>) -> &'a str {
+    field
+        .attrs
+        .iter()
+        .find_map(|attr| match attr {
+            SerdeAttribute::Rename(name) => Some(*name),
+            _ => None,
+        })
+        .unwrap_or(field.name)
+}
+
+pub(super) fn skips_serializing(field: &StructField) -> bool {
+    field
+        .attrs
+        .iter()
+        .any(|attr| matches!(attr, SerdeAttribute::Skip | SerdeAttribute::SkipSerializing))
+}
+
+pub(super) fn skips_deserializing(field: &StructField) -> bool {
+    field.attrs.iter().any(|attr| {
+        matches!(
+            attr,
+            SerdeAttribute::Skip | SerdeAttribute::SkipDeserializing
+        )
+    })
+}
+
+fn line(code: &mut String, level: usize, s: &str) {
+    code.push_str(&"    ".repeat(level));
+    code.push_str(s);
+    code.push('\n');
+}
+
+impl<'a> Codegen<'a> {
+    // an expression converting the list `value` of `ty`
+    fn encode_items(&self, ty: &Type<'a>, value: &str, depth: usize) -> String {
+        let item = format!("item{depth}");
+        let inner = self.encode_value(ty, &item, depth + 1);
+
+        if inner == item {
+            format!("list({value})")
+        } else {
+            format!("[{inner} for {item} in {value}]")
+        }
+    }
+
+    fn decode_items(&self, ty: &Type<'a>, value: &str, depth: usize) -> String {
+        let item = format!("item{depth}");
+        let inner = self.decode_value(ty, &item, depth + 1);
+
+        if inner == item {
+            format!("list({value})")
+        } else {
+            format!("[{inner} for {item} in {value}]")
+        }
+    }
+
+    // values of `ty` have to be converted, not just checked
+    pub(super) fn converts(&self, ty: &Type<'a>) -> bool {
+        match ty {
+            Type::Unit
+            | Type::Bool
+            | Type::Int(_)
+            | Type::Float(_)
+            | Type::Char
+            | Type::String
+            | Type::Json => false,
+            Type::Bytes(_) => true,
+            Type::Option(ty) | Type::Vec(ty) | Type::Array(ty) => self.converts(ty),
+            // sets are lists in the data
+            Type::Set(_) => true,
+            Type::Map(key, value) => self.converts(key) || self.converts(value),
+            Type::Tuple(types) => types.iter().any(|ty| self.converts(ty)),
+            Type::User(path) => match self.mapping(path) {
+                Some(found) => {
+                    let hooked = found.mapping.encode.is_some() || found.mapping.decode.is_some();
+
+                    match &path.args[..] {
+                        [arg] if !hooked && mapping::is_list(&found.mapping.python) => {
+                            self.converts(arg)
+                        }
+                        _ => hooked,
+                    }
+                }
+                None => true,
+            },
+        }
+    }
+
+    // an expression turning `value`, of type `ty`, into plain data
+    // identity conversions are returned unchanged
+    pub(super) fn encode_value(&self, ty: &Type<'a>, value: &str, depth: usize) -> String {
+        match ty {
+            Type::Unit | Type::Bool | Type::String | Type::Json => value.to_string(),
+            Type::Int(int) => format!("{}.check({value})", int.name().to_uppercase()),
+            Type::Float(float) => format!("{}.check({value})", float.name().to_uppercase()),
+            Type::Char => format!("CHAR.check({value})"),
+            Type::Bytes(bytes) => format!("{}.encode({value})", self.bytes_encoding(*bytes)),
+            Type::Option(ty) => {
+                let inner = self.encode_value(ty, value, depth);
+
+                if inner == value {
+                    inner
+                } else {
+                    format!("None if {value} is None else {inner}")
+                }
+            }
+            Type::Vec(ty) | Type::Array(ty) | Type::Set(ty) => self.encode_items(ty, value, depth),
+            Type::Map(key, item) => {
+                let (k, v) = (format!("key{depth}"), format!("item{depth}"));
+                let key = self.encode_value(key, &k, depth + 1);
+                let item = self.encode_value(item, &v, depth + 1);
+
+                if key == k && item == v {
+                    format!("dict({value})")
+                } else {
+                    format!("{{{key}: {item} for {k}, {v} in {value}.items()}}")
+                }
+            }
+            Type::Tuple(types) => {
+                let items = types
+                    .iter()
+                    .enumerate()
+                    .map(|(i, ty)| self.encode_value(ty, &format!("{value}[{i}]"), depth))
+                    .collect_vec();
+
+                match &items[..] {
+                    [item] => format!("({item},)"),
+                    _ => format!("({})", items.join(", ")),
+                }
+            }
+            Type::User(path) => match self.mapping(path) {
+                Some(found) => match (&found.mapping.encode, &path.args[..]) {
+                    (Some(hook), _) => format!("{hook}({value})"),
+                    // like `SmallVec<T>` as `list[T]`, the items may need converting
+                    (None, [arg]) if mapping::is_list(&found.mapping.python) => {
+                        self.encode_items(arg, value, depth)
+                    }
+                    _ => value.to_string(),
+                },
+                None => format!("encode_{}({value})", Self::safe_name(path.name())),
+            },
+        }
+    }
+
+    // an expression turning the plain data `value` into type `ty`
+    // identity conversions are returned unchanged
+    pub(super) fn decode_value(&self, ty: &Type<'a>, value: &str, depth: usize) -> String {
+        match ty {
+            Type::Unit | Type::Bool | Type::String | Type::Json => value.to_string(),
+            Type::Int(int) => format!("{}.check({value})", int.name().to_uppercase()),
+            Type::Float(float) => format!("{}.check({value})", float.name().to_uppercase()),
+            Type::Char => format!("CHAR.check({value})"),
+            Type::Bytes(bytes) => format!("{}.decode({value})", self.bytes_encoding(*bytes)),
+            Type::Option(ty) => {
+                let inner = self.decode_value(ty, value, depth);
+
+                if inner == value {
+                    inner
+                } else {
+                    format!("None if {value} is None else {inner}")
+                }
+            }
+            Type::Vec(ty) | Type::Array(ty) => self.decode_items(ty, value, depth),
+            Type::Set(ty) => {
+                let items = self.decode_items(ty, value, depth);
+                format!("to_set({}, {items})", self.set_class())
+            }
+            Type::Map(key, item) => {
+                let (k, v) = (format!("key{depth}"), format!("item{depth}"));
+                let item = self.decode_value(item, &v, depth + 1);
+
+                // JSON's keys are strings, serde writes integers as their digits
+                let key = match **key {
+                    Type::Int(_) => self.decode_value(key, &format!("int_key({k})"), depth + 1),
+                    _ => self.decode_value(key, &k, depth + 1),
+                };
+
+                if key == k && item == v {
+                    format!("dict({value})")
+                } else {
+                    format!("{{{key}: {item} for {k}, {v} in {value}.items()}}")
+                }
+            }
+            Type::Tuple(types) => {
+                let items = types
+                    .iter()
+                    .enumerate()
+                    .map(|(i, ty)| self.decode_value(ty, &format!("{value}[{i}]"), depth))
+                    .collect_vec();
+
+                match &items[..] {
+                    [item] => format!("({item},)"),
+                    _ => format!("({})", items.join(", ")),
+                }
+            }
+            Type::User(path) => match self.mapping(path) {
+                Some(found) => match (&found.mapping.decode, &path.args[..]) {
+                    (Some(hook), _) => format!("{hook}({value})"),
+                    // like `SmallVec<T>` as `list[T]`, the items may need converting
+                    (None, [arg]) if mapping::is_list(&found.mapping.python) => {
+                        self.decode_items(arg, value, depth)
+                    }
+                    _ => value.to_string(),
+                },
+                None => format!("decode_{}({value})", Self::safe_name(path.name())),
+            },
+        }
+    }
+
+    // fill the dict `data` from the fields of the object `value`
+    fn encode_fields(&self, code: &mut String, level: usize, fields: &[&StructField<'a>]) {
+        line(code, level, "data = {}");
+
+        for field in fields {
+            if skips_serializing(field) {
+                continue;
+            }
+
+            let attr = format!("value.{}", field.name);
+
+            let encoded = match (self.field_hooks(field).encode, double_option(field)) {
+                (Some(encode), _) => format!("{encode}({attr})"),
+                (None, Some(ty)) => {
+                    let inner = self.encode_value(ty, &attr, 0);
+
+                    // both of a double option's `None`s are `null`
+                    if inner == attr {
+                        format!("None if {attr} is ABSENT else {attr}")
+                    } else {
+                        format!("None if {attr} is ABSENT or {attr} is None else {inner}")
+                    }
+                }
+                (None, None) => self.encode_value(&field.ty, &attr, 0),
+            };
+
+            let assign = format!("data[\"{}\"] = {encoded}", key(field));
+
+            let skip_if = field.attrs.iter().find_map(|attr| match attr {
+                SerdeAttribute::SkipSerializingIf(expr) => Some(skip_predicate(field, expr)),
+                _ => None,
+            });
+
+            if let Some(predicate) = skip_if {
+                line(code, level, &format!("if not {predicate}({attr}):"));
+                line(code, level + 1, &assign);
+            } else {
+                line(code, level, &assign);
+            }
+        }
+    }
+
+    // fill the dict `kwargs` from the plain dict `source`
+    // fields with a default may be missing
+    fn decode_fields(
+        &self,
+        code: &mut String,
+        level: usize,
+        source: &str,
+        fields: &[(&StructField<'a>, bool)],
+    ) {
+        line(code, level, "kwargs = {}");
+
+        for (field, has_default) in fields {
+            if skips_deserializing(field) {
+                continue;
+            }
+
+            let item = format!("{source}[\"{}\"]", key(field));
+            let decoded = match self.field_hooks(field).decode {
+                Some(decode) => format!("{decode}({item})"),
+                None => self.decode_value(&field.ty, &item, 0),
+            };
+            let assign = format!("kwargs[\"{}\"] = {decoded}", field.name);
+
+            let mut conditions = Vec::new();
+
+            if *has_default {
+                conditions.push(format!("\"{}\" in {source}", key(field)));
+            }
+
+            for attr in &field.attrs {
+                if let SerdeAttribute::SkipDeserializingIf(expr) = attr {
+                    conditions.push(format!("not {}({item})", skip_predicate(field, expr)));
+                }
+            }
+
+            if conditions.is_empty() {
+                line(code, level, &assign);
+            } else {
+                line(code, level, &format!("if {}:", conditions.join(" and ")));
+                line(code, level + 1, &assign);
+            }
+        }
+    }
+
+    pub(super) fn generate_struct_codec(
+        &mut self,
+        name: &str,
+        fields: &[(&StructField<'a>, bool)],
+    ) {
+        let mut code = String::new();
+
+        let encoded = fields.iter().map(|(field, _)| *field).collect_vec();
+
+        line(
+            &mut code,
+            0,
+            &format!("def encode_{name}(value: {name}) -> dict:"),
+        );
+        self.encode_fields(&mut code, 1, &encoded);
+        line(&mut code, 1, "return data");
+        line(&mut code, 0, "");
+        line(&mut code, 0, "");
+
+        line(
+            &mut code,
+            0,
+            &format!("def decode_{name}(data: dict) -> {name}:"),
+        );
+        self.decode_fields(&mut code, 1, "data", fields);
+        line(&mut code, 1, &format!("return {name}(**kwargs)"));
+        line(&mut code, 0, "");
+        line(&mut code, 0, "");
+
+        line(&mut code, 0, &format!("{name}.to_dict = encode_{name}"));
+        line(
+            &mut code,
+            0,
+            &format!("{name}.from_dict = staticmethod(decode_{name})"),
+        );
+
+        self.write(&code);
+    }
+
+    // `fields` holds the field order and defaults of each struct variant
+    pub(super) fn generate_enum_codec(
+        &mut self,
+        name: &str,
+        enum_: &Enum<'a>,
+        fields: &[Vec<(&StructField<'a>, bool)>],
+    ) {
+        let mut code = String::new();
+
+        line(
+            &mut code,
+            0,
+            &format!("def encode_{name}(value: {name}) -> Any:"),
+        );
+
+        let mut struct_fields = fields.iter();
+
+        for variant in &enum_.variants {
+            let class = Self::safe_name(variant.name);
+            let tag = variant.name;
+
+            line(&mut code, 1, &format!("if isinstance(value, {class}):"));
+
+            match &variant.inner {
+                EnumVariantInner::Unit => line(&mut code, 2, &format!("return \"{tag}\"")),
+                EnumVariantInner::Tuple(types) => {
+                    let items = types
+                        .iter()
+                        .enumerate()
+                        .map(|(i, ty)| self.encode_value(ty, &format!("value._{i}"), 0))
+                        .collect_vec();
+
+                    // a newtype variant is what it holds, serde writes `{"Leaf": 1}`
+                    let items = match &items[..] {
+                        [item] => item.clone(),
+                        _ => format!("({})", items.join(", ")),
+                    };
+
+                    line(&mut code, 2, &format!("return {{\"{tag}\": {items}}}"));
+                }
+                EnumVariantInner::Struct(_) => {
+                    let fields = struct_fields
+                        .next()
+                        .expect("fields for every struct variant")
+                        .iter()
+                        .map(|(field, _)| *field)
+                        .collect_vec();
+
+                    self.encode_fields(&mut code, 2, &fields);
+                    line(&mut code, 2, &format!("return {{\"{tag}\": data}}"));
+                }
+            }
+        }
+
+        line(
+            &mut code,
+            1,
+            &format!("raise ValueError(f\"cannot serialize {{value!r}} as {name}\")"),
+        );
+        line(&mut code, 0, "");
+        line(&mut code, 0, "");
+
+        line(
+            &mut code,
+            0,
+            &format!("def decode_{name}(data: Any) -> {name}:"),
+        );
+
+        for variant in &enum_.variants {
+            if let EnumVariantInner::Unit = variant.inner {
+                let class = Self::safe_name(variant.name);
+
+                line(&mut code, 1, &format!("if data == \"{}\":", variant.name));
+                line(&mut code, 2, &format!("return {class}()"));
+            }
+        }
+
+        let mut struct_fields = fields.iter();
+
+        let tagged = enum_
+            .variants
+            .iter()
+            .filter(|variant| !matches!(variant.inner, EnumVariantInner::Unit))
+            .collect_vec();
+
+        if !tagged.is_empty() {
+            line(&mut code, 1, "if isinstance(data, dict):");
+        }
+
+        for variant in tagged {
+            let class = Self::safe_name(variant.name);
+            let tag = variant.name;
+
+            match &variant.inner {
+                EnumVariantInner::Unit => unreachable!(),
+                EnumVariantInner::Tuple(types) => {
+                    line(&mut code, 2, &format!("if \"{tag}\" in data:"));
+                    line(&mut code, 3, &format!("items = data[\"{tag}\"]"));
+
+                    let items = match &types[..] {
+                        [ty] => self.decode_value(ty, "items", 0),
+                        _ => types
+                            .iter()
+                            .enumerate()
+                            .map(|(i, ty)| self.decode_value(ty, &format!("items[{i}]"), 0))
+                            .join(", "),
+                    };
+
+                    line(&mut code, 3, &format!("return {class}({items})"));
+                }
+                EnumVariantInner::Struct(_) => {
+                    let fields = struct_fields
+                        .next()
+                        .expect("fields for every struct variant");
+
+                    line(&mut code, 2, &format!("if \"{tag}\" in data:"));
+                    line(&mut code, 3, &format!("inner = data[\"{tag}\"]"));
+                    self.decode_fields(&mut code, 3, "inner", fields);
+                    line(&mut code, 3, &format!("return {class}(**kwargs)"));
+                }
+            }
+        }
+
+        line(
+            &mut code,
+            1,
+            &format!("raise ValueError(f\"cannot deserialize {{data!r}} as {name}\")"),
+        );
+        line(&mut code, 0, "");
+        line(&mut code, 0, "");
+
+        for variant in &enum_.variants {
+            let class = Self::safe_name(variant.name);
+
+            line(&mut code, 0, &format!("{class}.to_dict = encode_{name}"));
+        }
+
+        self.write(&code);
+    }
+}
@@ -0,0 +1,228 @@
+// `marshal.toml`, the project's settings
+//
+// ```toml
+// inputs = ["src/models/*.rs"]
+// out-dir = "python/models"
+// features = ["chrono"]
+// frontend = "syn"
+// exclude = ["*Internal"]
+//
+// [types]
+// Uuid = "uuid.UUID"
+// "rust_decimal::Decimal" = { type = "decimal.Decimal", encode = "str", decode = "decimal.Decimal" }
+//
+// [python]
+// runtime = "write"
+// kw-only = true
+//
+// [python.defaults]
+// "crate::defaults::owner" = "defaults.owner"
+//
+// [python.codecs]
+// "crate::hex" = { encode = "codecs.to_hex", decode = "codecs.from_hex" }
+// ```
+//
+// every setting can be overridden from the command line
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::codegen::{Mode, Options, Runtime};
+use crate::diagnostic::Diagnostic;
+use crate::lexer::Span;
+use crate::mapping::{Codec, TypeMapping};
+use crate::{Frontend, Target};
+
+pub const FILE: &str = "marshal.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// Rust files or glob patterns
+    pub inputs: Vec<String>,
+
+    /// One module for every input's types
+    pub output: Option<String>,
+
+    /// A module per input
+    pub out_dir: Option<PathBuf>,
+
+    pub target: Option<Target>,
+
+    /// Enabled for `#[cfg(feature = "...")]`
+    pub features: Vec<String>,
+
+    /// What reads the inputs
+    pub frontend: Option<Frontend>,
+
+    /// Patterns of the type names to generate, all of them when empty
+    pub include: Vec<String>,
+
+    /// Patterns of the type names not to generate
+    pub exclude: Vec<String>,
+
+    /// Python types for Rust types, see `mapping`
+    pub types: HashMap<String, Mapping>,
+
+    pub python: Python,
+}
+
+/// A `[types]` entry, the Python type on its own or with hooks
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Mapping {
+    Type(String),
+    Table(MappingTable),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MappingTable {
+    #[serde(rename = "type")]
+    pub python: String,
+    pub encode: Option<String>,
+    pub decode: Option<String>,
+}
+
+impl From<Mapping> for TypeMapping {
+    fn from(mapping: Mapping) -> Self {
+        match mapping {
+            Mapping::Type(python) => TypeMapping::new(python),
+            Mapping::Table(table) => TypeMapping {
+                python: table.python,
+                encode: table.encode,
+                decode: table.decode,
+                default: None,
+            },
+        }
+    }
+}
+
+/// Options for the Python backend
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Python {
+    pub runtime: Option<String>,
+    pub kw_only: Option<bool>,
+    pub raw_bytes: Option<bool>,
+    pub frozen_sets: Option<bool>,
+    pub direct: Option<bool>,
+
+    /// Python callables for `#[serde(default = "path")]` functions, see `Options::default_fns`
+    pub defaults: HashMap<String, String>,
+
+    /// Python callables for `with`, `serialize_with` and `deserialize_with`, see `Options::codecs`
+    pub codecs: HashMap<String, CodecTable>,
+}
+
+/// A `[python.codecs]` entry
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CodecTable {
+    pub encode: Option<String>,
+    pub decode: Option<String>,
+}
+
+impl From<CodecTable> for Codec {
+    fn from(table: CodecTable) -> Self {
+        Codec {
+            encode: table.encode,
+            decode: table.decode,
+        }
+    }
+}
+
+impl Config {
+    /// Parses the config in `text`, which was read from `path`
+    ///
+    /// Paths in it are made relative to the current directory
+    pub fn parse(text: &str, path: &Path) -> Result<Self, Diagnostic> {
+        let mut config: Config = toml::from_str(text).map_err(|err| {
+            let diagnostic = Diagnostic::error(err.message().to_string());
+
+            match err.span() {
+                Some(range) => diagnostic
+                    .with_span(span(text, range.start, range.end))
+                    .with_label("here"),
+                None => diagnostic,
+            }
+        })?;
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+
+        // `-` is stdin or stdout, wherever the config is
+        let relative = |path: &mut String| {
+            if path != "-" {
+                *path = dir.join(&*path).to_string_lossy().into_owned();
+            }
+        };
+
+        config.inputs.iter_mut().for_each(relative);
+        config.output.iter_mut().for_each(relative);
+
+        if let Some(out_dir) = &mut config.out_dir {
+            *out_dir = dir.join(&*out_dir);
+        }
+
+        if config.output.is_some() && config.out_dir.is_some() {
+            return Err(
+                Diagnostic::error("`output` and `out-dir` can't both be set")
+                    .with_note("one module for every input, or one per input, pick one"),
+            );
+        }
+
+        Ok(config)
+    }
+
+    /// The options these settings give, before the command line's
+    pub fn options(&self) -> Options {
+        let python = &self.python;
+
+        Options {
+            default_fns: python.defaults.clone(),
+            kw_only: python.kw_only.unwrap_or_default(),
+            raw_bytes: python.raw_bytes.unwrap_or_default(),
+            frozen_sets: python.frozen_sets.unwrap_or_default(),
+            mode: if python.direct.unwrap_or_default() {
+                Mode::Direct
+            } else {
+                Mode::Reflection
+            },
+            runtime: python.runtime.as_deref().map(runtime).unwrap_or_default(),
+            type_mappings: self
+                .types
+                .iter()
+                .map(|(path, mapping)| (path.clone(), TypeMapping::from(mapping.clone())))
+                .collect(),
+            codecs: python
+                .codecs
+                .iter()
+                .map(|(path, codec)| (path.clone(), Codec::from(codec.clone())))
+                .collect(),
+        }
+    }
+}
+
+/// `write`, `inline`, or the module to import it from
+pub fn runtime(name: &str) -> Runtime {
+    match name {
+        "write" => Runtime::Write,
+        "inline" => Runtime::Inline,
+        module => Runtime::Import(module.to_string()),
+    }
+}
+
+// the span of the bytes `start..end` of `text`
+fn span(text: &str, start: usize, end: usize) -> Span {
+    let before = &text[..start];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+    Span {
+        start,
+        end,
+        line: before.matches('\n').count() + 1,
+        column: start - line_start + 1,
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use crate::parser::{DeclaredType, EnumVariantInner, InnerType, Type};
+
+// collect the names of every user type referenced by `ty`
+fn type_deps<'a>(ty: &Type<'a>, deps: &mut Vec<&'a str>) {
+    match ty {
+        Type::Option(ty) | Type::Array(ty) | Type::Vec(ty) | Type::Set(ty) => type_deps(ty, deps),
+        Type::Map(key, value) => {
+            type_deps(key, deps);
+            type_deps(value, deps);
+        }
+        Type::Tuple(types) => types.iter().for_each(|ty| type_deps(ty, deps)),
+        Type::User(path) => {
+            deps.push(path.name());
+            path.args.iter().for_each(|ty| type_deps(ty, deps));
+        }
+        Type::Unit
+        | Type::Bool
+        | Type::Int(_)
+        | Type::Float(_)
+        | Type::Char
+        | Type::String
+        | Type::Bytes(_)
+        | Type::Json => {}
+    }
+}
+
+/// The names of the user types `decl` refers to, in the order they appear
+pub fn decl_deps<'a>(decl: &DeclaredType<'a>) -> Vec<&'a str> {
+    let mut deps = Vec::new();
+
+    match &decl.inner {
+        InnerType::Struct(struc) => {
+            for field in &struc.fields {
+                type_deps(&field.ty, &mut deps);
+            }
+        }
+        InnerType::Enum(enum_) => {
+            for variant in &enum_.variants {
+                match &variant.inner {
+                    EnumVariantInner::Unit => {}
+                    EnumVariantInner::Tuple(types) => {
+                        types.iter().for_each(|ty| type_deps(ty, &mut deps))
+                    }
+                    EnumVariantInner::Struct(struc) => {
+                        for field in &struc.fields {
+                            type_deps(&field.ty, &mut deps);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    deps
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mark {
+    Unvisited,
+    Visiting,
+    Done,
+}
+
+struct Sorter<'a, 'b> {
+    types: &'b [DeclaredType<'a>],
+    index: HashMap<&'a str, usize>,
+    marks: Vec<Mark>,
+    order: Vec<usize>,
+}
+
+impl<'a, 'b> Sorter<'a, 'b> {
+    fn visit(&mut self, i: usize) {
+        if self.marks[i] != Mark::Unvisited {
+            // either already emitted, or we're in a cycle
+            // cycles are fine, the generated module postpones
+            // evaluation of annotations so forward references resolve
+            return;
+        }
+
+        self.marks[i] = Mark::Visiting;
+
+        for dep in decl_deps(&self.types[i]) {
+            if let Some(&j) = self.index.get(dep) {
+                self.visit(j);
+            }
+        }
+
+        self.marks[i] = Mark::Done;
+        self.order.push(i);
+    }
+}
+
+/// Reorders `types` so that every type is declared after the types it depends on
+///
+/// Source order is kept wherever the dependencies allow it, and
+/// (mutually) recursive types are left in the order they were first reached
+pub fn sort(types: Vec<DeclaredType>) -> Vec<DeclaredType> {
+    let index = types
+        .iter()
+        .enumerate()
+        .map(|(i, decl)| (decl.name, i))
+        .collect();
+
+    let mut sorter = Sorter {
+        types: &types,
+        index,
+        marks: vec![Mark::Unvisited; types.len()],
+        order: Vec::with_capacity(types.len()),
+    };
+
+    for i in 0..types.len() {
+        sorter.visit(i);
+    }
+
+    let order = sorter.order;
+
+    let mut types: Vec<_> = types.into_iter().map(Some).collect();
+
+    order
+        .into_iter()
+        .map(|i| types[i].take().expect("each type is emitted once"))
+        .collect()
+}
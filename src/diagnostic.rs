@@ -0,0 +1,157 @@
+use std::fmt::Write;
+use std::ops::{Deref, DerefMut};
+
+use crate::lexer::Span;
+
+/// A problem with the input, pointing at where it happened
+///
+/// It's what every `Result` in the frontends and codegen fails with, so its [`Details`] are boxed to keep those small
+#[derive(Debug, Clone)]
+pub struct Diagnostic(Box<Details>);
+
+/// What a [`Diagnostic`] says, which it derefs to
+#[derive(Debug, Clone)]
+pub struct Details {
+    pub message: String,
+
+    /// The file the span points into, if we know it
+    pub file: Option<String>,
+    pub span: Option<Span>,
+
+    /// Shown next to the caret under the span
+    pub label: Option<String>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>) -> Self {
+        Diagnostic(Box::new(Details {
+            message: message.into(),
+            file: None,
+            span: None,
+            label: None,
+            notes: Vec::new(),
+        }))
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    /// Point at `span`, unless the diagnostic already points somewhere more specific
+    pub fn or_span(mut self, span: Span) -> Self {
+        self.span.get_or_insert(span);
+        self
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_file(mut self, file: impl Into<String>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    /// Point into `file`, unless the diagnostic already knows its file
+    pub fn or_file(mut self, file: impl Into<String>) -> Self {
+        self.file.get_or_insert_with(|| file.into());
+        self
+    }
+
+    /// Renders the diagnostic, quoting the line of `source` it points at
+    ///
+    /// ```text
+    /// error: unexpected token `;`
+    ///  --> models.rs:3:13
+    ///   |
+    /// 3 |     age: u32;
+    ///   |             ^ expected `,` or `}`
+    ///   |
+    ///   = note: ...
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        let mut out = String::new();
+
+        let _ = writeln!(out, "error: {}", self.message);
+
+        let Some(span) = &self.span else {
+            for note in &self.notes {
+                let _ = writeln!(out, "  = note: {note}");
+            }
+
+            return out;
+        };
+
+        let file = self.file.as_deref().unwrap_or("<input>");
+
+        // a default span doesn't point at any line, so there's nothing to quote
+        let Some(index) = span.line.checked_sub(1) else {
+            let _ = writeln!(out, " --> {file}");
+
+            for note in &self.notes {
+                let _ = writeln!(out, "  = note: {note}");
+            }
+
+            return out;
+        };
+
+        let text = source.lines().nth(index).unwrap_or_default();
+
+        let gutter = " ".repeat(span.line.to_string().len());
+
+        let _ = writeln!(out, "{gutter}--> {file}:{}:{}", span.line, span.column);
+        let _ = writeln!(out, "{gutter} |");
+        let _ = writeln!(out, "{} | {text}", span.line);
+
+        // keep tabs so the caret lines up
+        let before: String = text
+            .bytes()
+            .take(span.column.saturating_sub(1))
+            .map(|c| if c == b'\t' { '\t' } else { ' ' })
+            .collect();
+
+        // the caret stops at the end of the line
+        let rest = text.len().saturating_sub(span.column.saturating_sub(1));
+        let width = span.end.saturating_sub(span.start).min(rest).max(1);
+
+        let _ = write!(out, "{gutter} | {before}{}", "^".repeat(width));
+
+        if let Some(label) = &self.label {
+            let _ = write!(out, " {label}");
+        }
+
+        out.push('\n');
+
+        if !self.notes.is_empty() {
+            let _ = writeln!(out, "{gutter} |");
+        }
+
+        for note in &self.notes {
+            let _ = writeln!(out, "{gutter} = note: {note}");
+        }
+
+        out
+    }
+}
+
+impl Deref for Diagnostic {
+    type Target = Details;
+
+    fn deref(&self) -> &Details {
+        &self.0
+    }
+}
+
+impl DerefMut for Diagnostic {
+    fn deref_mut(&mut self) -> &mut Details {
+        &mut self.0
+    }
+}
@@ -0,0 +1,920 @@
+// A whole crate, read from its root like `src/lib.rs` by following its `mod` declarations the way rustc does
+//
+// `mod foo;` is in `foo.rs` or `foo/mod.rs`, next to a crate root or a `mod.rs`, and in a directory named
+// after any other file, unless a `#[path = "..."]` says where it is
+//
+// every module's types, child modules and `use`s make up one scope, keyed by module path,
+// and the paths of user types are resolved in it, through `use`s, globs, `self`, `super` and `crate`,
+// to the full path of the type they name, `crate::models::User`, or of one from another crate, `chrono::Utc`
+//
+// a type alias is what it stands for, with its paths resolved in the module it's declared in,
+// and its type parameters replaced by the arguments it's used with
+//
+// like rustdoc's, only the types deriving `Serialize` or `Deserialize` are lowered, by `syn_parser`
+// names are slices of the files, so a path is made of the segments it was declared or imported with
+
+use std::cell::OnceCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use syn::spanned::Spanned;
+use syn::{Item, ItemMod, UseTree};
+
+use crate::diagnostic::Diagnostic;
+use crate::lexer::Span;
+use crate::parser::{DeclaredType, Type, TypePath};
+use crate::syn_parser::SynParser;
+use crate::Source;
+
+/// Where a crate is read from when there are no inputs
+pub const ROOT: &str = "src/lib.rs";
+
+// types that are in scope without a `use`, which aren't turned into anything of the model's
+const PRELUDE: &[&str] = &["Result", "Self"];
+
+/// The files of the modules `root` declares, and of the ones they declare, that exist
+///
+/// `features` decide which `#[cfg]`d modules there are, the parser reports the files that are missing
+pub fn files(root: &Source, features: &[String]) -> Vec<Source> {
+    let mut files: Vec<Source> = Vec::new();
+    let mut declared: VecDeque<_> = declared_in(root, Dirs::root(root), features).into();
+
+    while let Some((candidates, owned)) = declared.pop_front() {
+        let Some(path) = candidates.into_iter().find(|path| path.is_file()) else {
+            continue;
+        };
+
+        if files.iter().any(|file| file.path.as_ref() == Some(&path)) {
+            continue;
+        }
+
+        let Ok(file) = Source::from_path(&path) else {
+            continue;
+        };
+
+        declared.extend(declared_in(&file, Dirs::of(&path, owned), features));
+        files.push(file);
+    }
+
+    files
+}
+
+// the files each `mod foo;` in `source` could be in, and whether a `#[path]` said where
+fn declared_in(source: &Source, dirs: Dirs, features: &[String]) -> Vec<(Vec<PathBuf>, bool)> {
+    fn walk(
+        parser: &mut SynParser,
+        items: &[Item],
+        dirs: &Dirs,
+        declared: &mut Vec<(Vec<PathBuf>, bool)>,
+    ) {
+        for module in items.iter().filter_map(|item| match item {
+            Item::Mod(module) => Some(module),
+            _ => None,
+        }) {
+            if parser.peek_attributes(&module.attrs).disabled {
+                continue;
+            }
+
+            let name = parser.ident(&module.ident);
+            let path = path_attribute(module);
+
+            match &module.content {
+                Some((_, items)) => {
+                    walk(parser, items, &dirs.inline(name, path.as_deref()), declared)
+                }
+                None => declared.push((dirs.candidates(name, path.as_deref()), path.is_some())),
+            }
+        }
+    }
+
+    let mut parser = SynParser::new(&source.text, &source.name).with_features(features);
+    let mut declared = Vec::new();
+
+    if let Some(file) = parser.file() {
+        walk(&mut parser, &file.items, &dirs, &mut declared);
+    }
+
+    declared
+}
+
+// `#[path = "..."]`
+fn path_attribute(module: &ItemMod) -> Option<String> {
+    module.attrs.iter().find_map(|attr| match &attr.meta {
+        syn::Meta::NameValue(meta) if meta.path.is_ident("path") => match &meta.value {
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(lit),
+                ..
+            }) => Some(lit.value()),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+// where a module's `mod foo;`s are
+#[derive(Clone)]
+struct Dirs {
+    /// the directory of the module's file
+    file: PathBuf,
+
+    /// where the files of its children are
+    children: PathBuf,
+
+    /// it's a `mod foo { ... }`, whose `#[path]`s are relative to `children` rather than `file`
+    inline: bool,
+}
+
+impl Dirs {
+    fn root(root: &Source) -> Self {
+        Dirs::of(root.path.as_deref().unwrap_or(Path::new("")), true)
+    }
+
+    // a file's, `owned` when it's a crate root or a `#[path]` said where it is,
+    // which like a `mod.rs` has its children next to it
+    fn of(path: &Path, owned: bool) -> Self {
+        let file = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let owned = owned || path.file_name().is_some_and(|name| name == "mod.rs");
+
+        let children = match path.file_stem() {
+            Some(stem) if !owned => file.join(stem),
+            _ => file.clone(),
+        };
+
+        Dirs {
+            file,
+            children,
+            inline: false,
+        }
+    }
+
+    // `mod name { ... }`'s
+    fn inline(&self, name: &str, path: Option<&str>) -> Self {
+        let children = match path {
+            Some(path) => self.relative().join(path),
+            None => self.children.join(name),
+        };
+
+        Dirs {
+            file: self.file.clone(),
+            children,
+            inline: true,
+        }
+    }
+
+    // what `#[path]`s are relative to
+    fn relative(&self) -> &Path {
+        if self.inline {
+            &self.children
+        } else {
+            &self.file
+        }
+    }
+
+    // the files `mod name;` could be in
+    fn candidates(&self, name: &str, path: Option<&str>) -> Vec<PathBuf> {
+        match path {
+            Some(path) => vec![self.relative().join(path)],
+            None => vec![
+                self.children.join(format!("{name}.rs")),
+                self.children.join(name).join("mod.rs"),
+            ],
+        }
+    }
+}
+
+// what's in a module
+struct Module<'a> {
+    /// like `crate::models`
+    path: Vec<&'a str>,
+    parent: Option<usize>,
+    children: HashMap<&'a str, usize>,
+
+    /// structs, enums and type aliases
+    types: HashSet<&'a str>,
+
+    /// `type Name<T> = ...;`
+    aliases: HashMap<&'a str, Alias<'a>>,
+
+    /// `use a::b;` and `use a::b as c;`, by what they're called here
+    imports: Vec<(&'a str, Vec<&'a str>)>,
+
+    /// `use a::*;`
+    globs: Vec<Vec<&'a str>>,
+
+    /// its file could be parsed, otherwise anything could be in it
+    complete: bool,
+}
+
+// `type Name<T> = ...;`
+struct Alias<'a> {
+    /// the names of its type parameters
+    params: Vec<&'a str>,
+
+    /// what it stands for, with its paths as they're written
+    target: Result<Type<'a>, Diagnostic>,
+
+    /// where what it stands for is
+    span: Span,
+    file: &'a str,
+}
+
+// what a path names
+#[derive(Clone, PartialEq)]
+enum Resolved<'a> {
+    Type(usize, &'a str),
+    Module(usize),
+
+    /// something in another crate
+    External(Vec<&'a str>),
+
+    /// something we couldn't see, in a module whose file has a syntax error
+    Unknown,
+}
+
+// every module in the crate, the root first
+struct Scope<'a> {
+    modules: Vec<Module<'a>>,
+}
+
+impl<'a> Scope<'a> {
+    fn display(&self, resolved: &Resolved<'a>) -> String {
+        match resolved {
+            Resolved::Type(module, name) => {
+                format!("{}::{name}", self.modules[*module].path.join("::"))
+            }
+            Resolved::Module(module) => self.modules[*module].path.join("::"),
+            Resolved::External(path) => path.join("::"),
+            Resolved::Unknown => "?".to_string(),
+        }
+    }
+
+    // the type `path` names in `module`, by its full path, or what it stands for if it's an alias
+    // `aliases` are the ones being replaced, one of which standing for itself would never end
+    fn ty(
+        &self,
+        module: usize,
+        path: TypePath<'a>,
+        aliases: &mut Vec<(usize, &'a str)>,
+    ) -> Result<Type<'a>, Diagnostic> {
+        let segments = match self.path(module, &path.segments, true, &mut Vec::new())? {
+            Resolved::Type(module, name) => match self.modules[module].aliases.get(name) {
+                Some(alias) => return self.alias(module, name, alias, path.args, aliases),
+                None => {
+                    let mut segments = self.modules[module].path.clone();
+                    segments.push(name);
+
+                    segments
+                }
+            },
+            Resolved::External(segments) => segments,
+            Resolved::Unknown => path.segments,
+            resolved @ Resolved::Module(_) => {
+                return Err(Diagnostic::error(format!(
+                    "expected a type, found module `{}`",
+                    self.display(&resolved)
+                ))
+                .with_label("not a type"))
+            }
+        };
+
+        Ok(Type::User(TypePath {
+            segments,
+            args: path.args,
+        }))
+    }
+
+    // what `alias`, declared in `module` as `name`, stands for with `args`
+    fn alias(
+        &self,
+        module: usize,
+        name: &'a str,
+        alias: &Alias<'a>,
+        args: Vec<Type<'a>>,
+        aliases: &mut Vec<(usize, &'a str)>,
+    ) -> Result<Type<'a>, Diagnostic> {
+        let display = self.display(&Resolved::Type(module, name));
+
+        if aliases.contains(&(module, name)) {
+            return Err(Diagnostic::error(format!("`{display}` stands for itself"))
+                .with_span(alias.span)
+                .with_label("in here")
+                .with_file(alias.file));
+        }
+
+        if args.len() != alias.params.len() {
+            return Err(Diagnostic::error(format!(
+                "`{display}` takes {} type arguments, but {} were given",
+                alias.params.len(),
+                args.len()
+            ))
+            .with_label("used here"));
+        }
+
+        // its target's errors are in its own file
+        let target = alias
+            .target
+            .clone()
+            .map_err(|err| err.or_file(alias.file))?;
+        let params: Vec<_> = alias.params.iter().copied().zip(args).collect();
+
+        aliases.push((module, name));
+
+        let ty = self
+            .substitute(module, target, &params, aliases)
+            .map_err(|err| match err.span {
+                // a path in it, the aliases it uses point at their own errors
+                None => err
+                    .with_span(alias.span)
+                    .with_file(alias.file)
+                    .with_note(format!("in what `{display}` stands for")),
+                Some(_) => err,
+            });
+
+        aliases.pop();
+
+        ty
+    }
+
+    // `ty` from an alias declared in `module`, with its paths resolved and its `params` replaced
+    fn substitute(
+        &self,
+        module: usize,
+        ty: Type<'a>,
+        params: &[(&'a str, Type<'a>)],
+        aliases: &mut Vec<(usize, &'a str)>,
+    ) -> Result<Type<'a>, Diagnostic> {
+        let mut boxed =
+            |ty: Box<Type<'a>>| Ok(Box::new(self.substitute(module, *ty, params, aliases)?));
+
+        Ok(match ty {
+            Type::User(path) => {
+                if let ([name], true) = (&path.segments[..], path.args.is_empty()) {
+                    if let Some((_, arg)) = params.iter().find(|(param, _)| param == name) {
+                        return Ok(arg.clone());
+                    }
+                }
+
+                let args = path
+                    .args
+                    .into_iter()
+                    .map(|ty| self.substitute(module, ty, params, aliases))
+                    .collect::<Result<_, _>>()?;
+
+                self.ty(
+                    module,
+                    TypePath {
+                        segments: path.segments,
+                        args,
+                    },
+                    aliases,
+                )?
+            }
+            Type::Option(ty) => Type::Option(boxed(ty)?),
+            Type::Array(ty) => Type::Array(boxed(ty)?),
+            Type::Vec(ty) => Type::Vec(boxed(ty)?),
+            Type::Set(ty) => Type::Set(boxed(ty)?),
+            Type::Map(key, value) => {
+                let key = boxed(key)?;
+                Type::Map(key, boxed(value)?)
+            }
+            Type::Tuple(types) => Type::Tuple(
+                types
+                    .into_iter()
+                    .map(|ty| self.substitute(module, ty, params, aliases))
+                    .collect::<Result<_, _>>()?,
+            ),
+            ty => ty,
+        })
+    }
+
+    // what `segments` names in `module`, a type if `want_type`, otherwise a module
+    // `seen` are the names already looked for, which globs importing each other would look for forever
+    fn path(
+        &self,
+        module: usize,
+        segments: &[&'a str],
+        want_type: bool,
+        seen: &mut Vec<(usize, &'a str, bool)>,
+    ) -> Result<Resolved<'a>, Diagnostic> {
+        let mut at = module;
+        let mut rest = segments;
+
+        match rest.first() {
+            Some(&"crate") => {
+                at = 0;
+                rest = &rest[1..];
+            }
+            Some(&"self") => rest = &rest[1..],
+            _ => {}
+        }
+
+        while rest.first() == Some(&"super") {
+            at = self.modules[at].parent.ok_or_else(|| {
+                Diagnostic::error("too many `super`s").with_label("the crate's root has no parent")
+            })?;
+
+            rest = &rest[1..];
+        }
+
+        // the first segment is a name in scope, rather than `crate`, `self` or `super`
+        let scoped = rest.len() == segments.len();
+        let mut resolved = Resolved::Module(at);
+
+        for (i, &name) in rest.iter().enumerate() {
+            let last = i + 1 == rest.len();
+
+            resolved = match resolved {
+                Resolved::Module(module) => {
+                    match self.member(module, name, want_type && last, seen)? {
+                        Some(found) => found,
+                        // not in scope, so another crate, or a type from the prelude
+                        None if scoped
+                            && i == 0
+                            && (!last || !want_type || PRELUDE.contains(&name)) =>
+                        {
+                            Resolved::External(vec![name])
+                        }
+                        None if last
+                            && want_type
+                            && self.modules[module].children.contains_key(name) =>
+                        {
+                            return Err(Diagnostic::error(format!(
+                                "expected a type, found module `{}::{name}`",
+                                self.modules[module].path.join("::")
+                            ))
+                            .with_label("not a type"))
+                        }
+                        None if scoped && i == 0 => {
+                            return Err(Diagnostic::error(format!(
+                                "cannot find type `{name}` in `{}`",
+                                self.modules[module].path.join("::")
+                            ))
+                            .with_label("not declared or imported in this module")
+                            .with_note(
+                                "a type from another crate needs a `use`, or its full path",
+                            ))
+                        }
+                        None => {
+                            return Err(Diagnostic::error(format!(
+                                "cannot find `{name}` in `{}`",
+                                self.modules[module].path.join("::")
+                            ))
+                            .with_label("not declared or imported there"))
+                        }
+                    }
+                }
+                Resolved::External(mut path) => {
+                    path.push(name);
+                    Resolved::External(path)
+                }
+                Resolved::Unknown => Resolved::Unknown,
+                resolved @ Resolved::Type(..) => {
+                    return Err(Diagnostic::error(format!(
+                        "expected a module, found type `{}`",
+                        self.display(&resolved)
+                    ))
+                    .with_label("not a module"))
+                }
+            };
+        }
+
+        Ok(resolved)
+    }
+
+    // what `name` is in `module`, declared or imported, as rustc looks for it
+    fn member(
+        &self,
+        module: usize,
+        name: &'a str,
+        want_type: bool,
+        seen: &mut Vec<(usize, &'a str, bool)>,
+    ) -> Result<Option<Resolved<'a>>, Diagnostic> {
+        if seen.contains(&(module, name, want_type)) {
+            return Ok(None);
+        }
+
+        seen.push((module, name, want_type));
+
+        let this = &self.modules[module];
+
+        if want_type && this.types.contains(name) {
+            return Ok(Some(Resolved::Type(module, name)));
+        }
+
+        if let (false, Some(&child)) = (want_type, this.children.get(name)) {
+            return Ok(Some(Resolved::Module(child)));
+        }
+
+        if let Some((_, path)) = this.imports.iter().find(|(alias, _)| *alias == name) {
+            return self.path(module, path, want_type, seen).map(Some);
+        }
+
+        // declarations and `use`s shadow what globs import
+        let mut found = Vec::new();
+        let mut external = Vec::new();
+
+        for glob in &this.globs {
+            match self.path(module, glob, false, seen) {
+                Ok(Resolved::Module(imported)) => {
+                    if let Some(resolved) = self.member(imported, name, want_type, seen)? {
+                        if !found.contains(&resolved) {
+                            found.push(resolved);
+                        }
+                    }
+                }
+                Ok(Resolved::External(path)) => external.push(path),
+                Ok(Resolved::Unknown) => found.push(Resolved::Unknown),
+                // an enum's variants aren't types, and rustc reports globs that aren't there
+                Ok(Resolved::Type(..)) | Err(_) => {}
+            }
+        }
+
+        if found.len() > 1 {
+            found.retain(|resolved| *resolved != Resolved::Unknown);
+        }
+
+        if found.len() > 1 {
+            let candidates = found
+                .iter()
+                .map(|resolved| format!("`{}`", self.display(resolved)))
+                .collect::<Vec<_>>();
+
+            return Err(Diagnostic::error(format!("`{name}` is ambiguous"))
+                .with_label("imported by more than one glob")
+                .with_note(format!("it could be {}", candidates.join(" or ")))
+                .with_note("import the one you mean by name"));
+        }
+
+        if let Some(resolved) = found.pop() {
+            return Ok(Some(resolved));
+        }
+
+        // what another crate's glob imports could be anything
+        let resolved = match &external[..] {
+            [] if this.complete => return Ok(None),
+            [path] => {
+                let mut path = path.clone();
+                path.push(name);
+
+                Resolved::External(path)
+            }
+            _ => Resolved::Unknown,
+        };
+
+        Ok(Some(resolved))
+    }
+}
+
+pub struct CrateParser<'a> {
+    root: &'a Source,
+
+    // the files of its modules, see [`files`]
+    files: &'a [Source],
+
+    // enabled for `#[cfg(feature = "...")]`
+    features: &'a [String],
+}
+
+impl<'a> CrateParser<'a> {
+    /// `root` is the crate's root, like `src/lib.rs`, and `files` its modules', see [`files`]
+    pub fn new(root: &'a Source, files: &'a [Source]) -> Self {
+        CrateParser {
+            root,
+            files,
+            features: &[],
+        }
+    }
+
+    /// The cargo features to consider enabled when evaluating `#[cfg]` and `#[cfg_attr]`
+    pub fn with_features(mut self, features: &'a [String]) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// Every type in the crate deriving `Serialize` or `Deserialize` whose name is `selected`,
+    /// in the order of their modules and their positions in them, with their paths resolved
+    pub fn parse(
+        self,
+        selected: impl Fn(&str) -> bool,
+    ) -> Result<Vec<DeclaredType<'a>>, Vec<Diagnostic>> {
+        let sources: Vec<&'a Source> = std::iter::once(self.root).chain(self.files).collect();
+
+        let mut parsers: Vec<_> = sources
+            .iter()
+            .map(|source| SynParser::new(&source.text, &source.name).with_features(self.features))
+            .collect();
+
+        let trees: Vec<OnceCell<Option<syn::File>>> =
+            sources.iter().map(|_| OnceCell::new()).collect();
+
+        let mut walk = Walk {
+            sources: &sources,
+            parsers: &mut parsers,
+            trees: &trees,
+            modules: Vec::new(),
+            items: Vec::new(),
+            files: HashMap::from([(0, 0)]),
+            errors: Vec::new(),
+        };
+
+        let root = walk.trees[0].get_or_init(|| walk.parsers[0].file());
+        let root_items = root.as_ref().map_or(&[][..], |root| &root.items[..]);
+
+        walk.module(0, root_items, vec!["crate"], None, Dirs::root(self.root));
+
+        let Walk {
+            modules,
+            items,
+            mut errors,
+            ..
+        } = walk;
+
+        let scope = Rc::new(Scope { modules });
+        let mut found = Vec::new();
+
+        for (module, (file, items)) in items.into_iter().enumerate() {
+            let parser = &mut parsers[file];
+            let resolver = Rc::clone(&scope);
+
+            parser.set_resolver(Box::new(move |path, span| {
+                resolver
+                    .ty(module, path, &mut Vec::new())
+                    .map_err(|err| err.or_span(span))
+            }));
+
+            for item in items {
+                let (attrs, ident) = match item {
+                    Item::Struct(item) => (&item.attrs, &item.ident),
+                    Item::Enum(item) => (&item.attrs, &item.ident),
+                    _ => continue,
+                };
+
+                let attrs = parser.peek_attributes(attrs);
+
+                // a hand written impl could serialize it as anything
+                let serde = attrs
+                    .derives
+                    .iter()
+                    .any(|derive| matches!(*derive, "Serialize" | "Deserialize"));
+
+                if serde && !attrs.disabled && selected(parser.ident(ident)) {
+                    parser.item(item);
+                }
+            }
+
+            found.extend(parser.take_types().into_iter().map(|ty| (module, ty)));
+        }
+
+        for (parser, source) in parsers.iter_mut().zip(&sources) {
+            errors.extend(
+                parser
+                    .take_errors()
+                    .into_iter()
+                    .map(|err| err.or_file(&source.name)),
+            );
+        }
+
+        let mut names: HashMap<&str, usize> = HashMap::new();
+        let mut types = Vec::new();
+
+        for (module, ty) in found {
+            if let Some(first) = names.insert(ty.name, module) {
+                let path = |module: usize| scope.display(&Resolved::Type(module, ty.name));
+
+                errors.push(
+                    Diagnostic::error(format!("there are two types named `{}`", ty.name))
+                        .with_span(ty.span)
+                        .with_label("declared again here")
+                        .with_note(format!("`{}` and `{}`", path(first), path(module)))
+                        .with_note("the Python classes are named after the Rust types")
+                        .with_file(ty.file),
+                );
+
+                continue;
+            }
+
+            types.push(ty);
+        }
+
+        if errors.is_empty() {
+            Ok(types)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+// the modules, followed from the root
+struct Walk<'a, 't, 'p> {
+    sources: &'t [&'a Source],
+    parsers: &'p mut [SynParser<'a>],
+    trees: &'t [OnceCell<Option<syn::File>>],
+
+    modules: Vec<Module<'a>>,
+
+    /// each module's file and items
+    items: Vec<(usize, &'t [Item])>,
+
+    /// the module each file is
+    files: HashMap<usize, usize>,
+
+    errors: Vec<Diagnostic>,
+}
+
+impl<'a, 't> Walk<'a, 't, '_> {
+    // adds a module and its children, returning its index
+    fn module(
+        &mut self,
+        file: usize,
+        items: &'t [Item],
+        path: Vec<&'a str>,
+        parent: Option<usize>,
+        dirs: Dirs,
+    ) -> usize {
+        let index = self.modules.len();
+
+        self.modules.push(Module {
+            path,
+            parent,
+            children: HashMap::new(),
+            types: HashSet::new(),
+            aliases: HashMap::new(),
+            imports: Vec::new(),
+            globs: Vec::new(),
+            complete: self.trees[file].get().is_some_and(Option::is_some),
+        });
+
+        self.items.push((file, items));
+
+        for item in items {
+            let parser = &mut self.parsers[file];
+
+            let (attrs, ident) = match item {
+                Item::Struct(item) => (&item.attrs, &item.ident),
+                Item::Enum(item) => (&item.attrs, &item.ident),
+                Item::Type(item) => {
+                    if !parser.peek_attributes(&item.attrs).disabled {
+                        let name = parser.ident(&item.ident);
+
+                        let alias = Alias {
+                            params: item
+                                .generics
+                                .type_params()
+                                .map(|param| parser.ident(&param.ident))
+                                .collect(),
+                            target: parser.ty(&item.ty),
+                            span: parser.span(item.ty.span()),
+                            file: &self.sources[file].name,
+                        };
+
+                        self.modules[index].types.insert(name);
+                        self.modules[index].aliases.insert(name, alias);
+                    }
+
+                    continue;
+                }
+                Item::Use(item) => {
+                    if !parser.peek_attributes(&item.attrs).disabled {
+                        uses(parser, &item.tree, Vec::new(), &mut self.modules[index]);
+                    }
+
+                    continue;
+                }
+                Item::Mod(module) => {
+                    if !parser.peek_attributes(&module.attrs).disabled {
+                        self.child(index, file, module, &dirs);
+                    }
+
+                    continue;
+                }
+                _ => continue,
+            };
+
+            if !parser.peek_attributes(attrs).disabled {
+                let name = parser.ident(ident);
+                self.modules[index].types.insert(name);
+            }
+        }
+
+        index
+    }
+
+    // `mod name { ... }` or `mod name;` in `parent`
+    fn child(&mut self, parent: usize, file: usize, module: &'t ItemMod, dirs: &Dirs) {
+        let name = self.parsers[file].ident(&module.ident);
+        let attribute = path_attribute(module);
+
+        let mut path = self.modules[parent].path.clone();
+        path.push(name);
+
+        let child = match &module.content {
+            Some((_, items)) => {
+                let dirs = dirs.inline(name, attribute.as_deref());
+
+                self.module(file, items, path, Some(parent), dirs)
+            }
+            None => {
+                let candidates = dirs.candidates(name, attribute.as_deref());
+                let span = self.parsers[file].span(module.ident.span());
+                let source = &self.sources[file].name;
+
+                let found = candidates.iter().find_map(|candidate| {
+                    self.sources
+                        .iter()
+                        .position(|source| source.path.as_ref() == Some(candidate))
+                        .map(|found| (found, candidate))
+                });
+
+                let Some((found, candidate)) = found else {
+                    let expected = candidates
+                        .iter()
+                        .map(|candidate| format!("`{}`", candidate.display()))
+                        .collect::<Vec<_>>();
+
+                    self.errors.push(
+                        Diagnostic::error(format!("cannot find the file of module `{name}`"))
+                            .with_span(span)
+                            .with_label("declared here")
+                            .with_note(format!("expected {}", expected.join(" or ")))
+                            .with_file(source),
+                    );
+
+                    return;
+                };
+
+                if let Some(&module) = self.files.get(&found) {
+                    self.errors.push(
+                        Diagnostic::error(format!(
+                            "`{}` is already module `{}`",
+                            candidate.display(),
+                            self.modules[module].path.join("::")
+                        ))
+                        .with_span(span)
+                        .with_label("declared again here")
+                        .with_file(source),
+                    );
+
+                    return;
+                }
+
+                self.files.insert(found, self.modules.len());
+
+                let parser = &mut self.parsers[found];
+                let tree = self.trees[found].get_or_init(|| parser.file());
+                let items = tree.as_ref().map_or(&[][..], |tree| &tree.items[..]);
+
+                let dirs = Dirs::of(candidate, attribute.is_some());
+
+                self.module(found, items, path, Some(parent), dirs)
+            }
+        };
+
+        self.modules[parent].children.insert(name, child);
+    }
+}
+
+// the names a `use` brings into `module`, after `prefix`
+fn uses<'a>(
+    parser: &SynParser<'a>,
+    tree: &UseTree,
+    mut prefix: Vec<&'a str>,
+    module: &mut Module<'a>,
+) {
+    match tree {
+        UseTree::Path(path) => {
+            prefix.push(parser.ident(&path.ident));
+            uses(parser, &path.tree, prefix, module);
+        }
+        // `use a::{self}` imports `a`
+        UseTree::Name(name) if name.ident == "self" => {
+            if let Some(&last) = prefix.last() {
+                module.imports.push((last, prefix));
+            }
+        }
+        UseTree::Name(name) => {
+            let name = parser.ident(&name.ident);
+
+            prefix.push(name);
+            module.imports.push((name, prefix));
+        }
+        UseTree::Rename(rename) => {
+            let alias = parser.ident(&rename.rename);
+
+            if rename.ident != "self" {
+                prefix.push(parser.ident(&rename.ident));
+            }
+
+            // `use a::Trait as _` doesn't bind anything
+            if alias != "_" {
+                module.imports.push((alias, prefix));
+            }
+        }
+        UseTree::Glob(_) => module.globs.push(prefix),
+        UseTree::Group(group) => {
+            for tree in &group.items {
+                uses(parser, tree, prefix.clone(), module);
+            }
+        }
+    }
+}
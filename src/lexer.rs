@@ -19,6 +19,8 @@
 
 use std::fmt::{self, Display, Formatter};
 
+use crate::diagnostic::Diagnostic;
+
 #[derive(Debug, PartialEq)]
 pub enum TokenType<'a> {
     Hash,
@@ -40,10 +42,48 @@ pub enum TokenType<'a> {
     Rangle,
 }
 
-#[derive(Debug)]
+/// Where a token is in the input
+///
+/// `start` and `end` are byte offsets, `line` and `column` are where it starts, counting from 1
+#[derive(Debug, Clone, Copy, Default)]
 pub struct Span {
     pub start: usize,
     pub end: usize,
+
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    /// The character at `line` and `column` of `text`, for inputs which only say where things are in those
+    ///
+    /// `column` counts characters, the span's counts bytes
+    pub fn at(text: &str, line: usize, column: usize) -> Span {
+        let line_start = text
+            .split_inclusive('\n')
+            .take(line.saturating_sub(1))
+            .map(str::len)
+            .sum::<usize>();
+
+        let start = line_start
+            + text[line_start..]
+                .chars()
+                .take(column.saturating_sub(1))
+                .map(char::len_utf8)
+                .sum::<usize>();
+
+        let end = text[start..]
+            .chars()
+            .next()
+            .map_or(start, |c| start + c.len_utf8());
+
+        Span {
+            start,
+            end,
+            line,
+            column: start - line_start + 1,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -55,11 +95,16 @@ pub struct LexicalToken<'a> {
 pub struct Lexer<'a> {
     input: &'a str,
     tokens: Vec<LexicalToken<'a>>,
+    errors: Vec<Diagnostic>,
     cursor: usize,
     start: usize,
 
     line: usize,
     column: usize,
+
+    // where the current token started
+    start_line: usize,
+    start_column: usize,
 }
 
 impl<'a> Lexer<'a> {
@@ -67,23 +112,31 @@ impl<'a> Lexer<'a> {
         Lexer {
             input,
             tokens: Vec::new(),
+            errors: Vec::new(),
             cursor: 0,
             start: 0,
             line: 1,
             column: 1,
+            start_line: 1,
+            start_column: 1,
         }
     }
 
-    pub fn lex(mut self) -> Vec<LexicalToken<'a>> {
+    /// Splits the input into tokens
+    ///
+    /// Characters which can't start a token are reported and skipped,
+    /// so the tokens are still worth parsing when there are errors
+    pub fn lex(mut self) -> (Vec<LexicalToken<'a>>, Vec<Diagnostic>) {
         use TokenType::*;
 
         let input = self.input.as_bytes();
 
         while self.cursor < input.len() {
-            let column = self.column;
             let c = input[self.cursor];
 
             self.start = self.cursor;
+            self.start_line = self.line;
+            self.start_column = self.column;
             self.advance();
 
             match c {
@@ -103,11 +156,7 @@ impl<'a> Lexer<'a> {
                     self.column = 1;
                 }
                 b'_' | b'a'..=b'z' | b'A'..=b'Z' => self.lex_identifier(),
-                b'"' => {
-                    self.advance();
-                    self.start += 1;
-                    self.lex_string()
-                }
+                b'"' => self.lex_string(),
                 b'/' if self.cursor < input.len() && input[self.cursor] == b'/' => {
                     self.advance();
 
@@ -143,17 +192,34 @@ impl<'a> Lexer<'a> {
                 }
                 b'<' => self.push_token(Langle),
                 b'>' => self.push_token(Rangle),
-                _ => panic!(
-                    "Unexpected character {} at {}:{}",
-                    c as char, self.line, column
-                ),
+                _ => {
+                    // report the whole character, not just its first byte
+                    let c = self.input[self.start..].chars().next().unwrap_or_default();
+
+                    self.errors.push(
+                        Diagnostic::error(format!("unexpected character `{c}`"))
+                            .with_span(self.span(self.start + c.len_utf8()))
+                            .with_label("not valid here"),
+                    );
+
+                    self.cursor = self.start + c.len_utf8();
+                }
             }
         }
 
         self.cursor = 0;
         self.start = 0;
 
-        self.tokens
+        (self.tokens, self.errors)
+    }
+
+    fn span(&self, end: usize) -> Span {
+        Span {
+            start: self.start,
+            end,
+            line: self.start_line,
+            column: self.start_column,
+        }
     }
 
     fn advance(&mut self) {
@@ -164,10 +230,7 @@ impl<'a> Lexer<'a> {
     fn push_token(&mut self, token: TokenType<'a>) {
         self.tokens.push(LexicalToken {
             token,
-            span: Span {
-                start: self.start,
-                end: self.cursor,
-            },
+            span: self.span(self.cursor),
         });
     }
 
@@ -175,24 +238,37 @@ impl<'a> Lexer<'a> {
         &self.input[self.start..self.cursor]
     }
 
+    // the opening quote was just eaten
     fn lex_string(&mut self) {
         let input = self.input.as_bytes();
 
         loop {
+            if self.cursor >= input.len() {
+                self.errors.push(
+                    Diagnostic::error("unterminated string")
+                        .with_span(self.span(self.start + 1))
+                        .with_label("this string is never closed"),
+                );
+
+                return;
+            }
+
             let c = input[self.cursor];
 
             if c == b'"' {
                 break;
             }
 
-            if self.cursor >= input.len() {
-                panic!("Unterminated string");
+            if c == b'\n' {
+                self.line += 1;
+                self.column = 0;
             }
 
             self.advance();
         }
 
-        let lexeme = self.lexeme();
+        // skip the opening quote
+        let lexeme = &self.input[self.start + 1..self.cursor];
 
         // Skip the closing quote
         self.advance();
@@ -0,0 +1,551 @@
+//! Generates Python dataclasses compatible with Rust serde types
+//!
+//! [`Builder`] runs the whole pipeline, for build scripts and other tools
+//!
+//! ```no_run
+//! let builder = marshal::Builder::new()
+//!     .source(marshal::Source::from_path("src/models.rs")?)
+//!     .destination(marshal::Destination::File(Some("python/models.py".into())));
+//!
+//! let generated = builder.generate();
+//!
+//! if !generated.diagnostics.is_empty() {
+//!     panic!("{}", generated.render_diagnostics());
+//! }
+//!
+//! generated.write()?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! The stages are public too, [`lexer::Lexer`] turns source text into tokens,
+//! [`parser::Parser`] turns those into [`parser::DeclaredType`]s, and [`codegen::Codegen`] writes their Python
+//!
+//! [`syn_parser::SynParser`] reads the same types with `syn`, [`krate::CrateParser`] a whole crate's
+//! from its modules, [`rustdoc::RustdocParser`] from rustdoc's JSON, and [`registry::RegistryParser`]
+//! traced ones, see [`Frontend`]
+
+pub mod codegen;
+pub mod config;
+pub mod diagnostic;
+pub mod krate;
+pub mod lexer;
+pub mod mapping;
+pub mod parser;
+pub mod registry;
+pub mod runtime;
+pub mod rustdoc;
+pub mod syn_parser;
+
+mod deps;
+
+use std::cell::OnceCell;
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+use serde::Deserialize;
+
+use codegen::Options;
+use diagnostic::Diagnostic;
+use lexer::LexicalToken;
+use parser::DeclaredType;
+
+/// The language to generate
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    /// Python dataclasses
+    #[default]
+    Python,
+}
+
+/// What reads the inputs
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Frontend {
+    /// marshal's own lexer and parser, which only understand type declarations
+    #[default]
+    Native,
+
+    /// `syn`, which understands all of Rust and skips what isn't a struct or an enum
+    Syn,
+
+    /// A crate from its root, `src/lib.rs` by default, following its `mod`s and resolving paths between them
+    Crate,
+
+    /// rustdoc's JSON output for a whole crate, with every path resolved
+    Rustdoc,
+
+    /// A `serde_reflection::Registry` traced from the types, as YAML or JSON
+    Registry,
+}
+
+/// Where generated code goes
+#[derive(Debug, Clone, PartialEq)]
+pub enum Destination {
+    /// One module, `None` when it isn't written anywhere, like stdout
+    File(Option<PathBuf>),
+
+    /// A module per input, named after it
+    Dir(PathBuf),
+}
+
+impl Default for Destination {
+    fn default() -> Self {
+        Destination::File(None)
+    }
+}
+
+/// An input file
+#[derive(Debug, Clone)]
+pub struct Source {
+    /// `None` when it wasn't read from a file, like stdin
+    pub path: Option<PathBuf>,
+
+    /// What diagnostics call it
+    pub name: String,
+    pub text: String,
+}
+
+impl Source {
+    pub fn new(name: impl Into<String>, text: impl Into<String>) -> Self {
+        Source {
+            path: None,
+            name: name.into(),
+            text: text.into(),
+        }
+    }
+
+    pub fn from_path(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref();
+
+        Ok(Source {
+            path: Some(path.to_path_buf()),
+            name: path.display().to_string(),
+            text: std::fs::read_to_string(path)?,
+        })
+    }
+}
+
+/// A generated file
+#[derive(Debug, Clone, PartialEq)]
+pub struct File {
+    /// `None` for a `Destination::File(None)` module
+    pub path: Option<PathBuf>,
+    pub contents: String,
+}
+
+/// The tokens of a source, and the errors lexing it
+pub type Lexed<'a> = (Vec<LexicalToken<'a>>, Vec<Diagnostic>);
+
+/// What a run produced, the files are only there when there are no diagnostics
+#[derive(Debug)]
+pub struct Generated<'s> {
+    pub files: Vec<File>,
+
+    /// Every error in the inputs, or the first one code generation ran into
+    pub diagnostics: Vec<Diagnostic>,
+
+    sources: Vec<&'s Source>,
+}
+
+impl Generated<'_> {
+    /// The diagnostics, quoting the sources they point into
+    pub fn render_diagnostics(&self) -> String {
+        render(&self.sources, &self.diagnostics)
+    }
+
+    /// Writes the files with paths, creating their directories
+    pub fn write(&self) -> std::io::Result<()> {
+        for file in &self.files {
+            if let Some(path) = &file.path {
+                if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                    std::fs::create_dir_all(dir)?;
+                }
+
+                std::fs::write(path, &file.contents)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs marshal on some sources
+#[derive(Debug, Default)]
+pub struct Builder {
+    sources: Vec<Source>,
+    options: Options,
+    target: Target,
+    frontend: Frontend,
+    destination: Destination,
+    features: Vec<String>,
+    include: Vec<glob::Pattern>,
+    exclude: Vec<glob::Pattern>,
+
+    /// The files rustdoc's types are declared in, read for their attributes,
+    /// or the files of the modules of the crates
+    crate_files: OnceCell<Vec<Source>>,
+
+    /// Each source's registry, which the types borrow their names from
+    registries: OnceCell<Vec<Result<serde_reflection::Registry, Diagnostic>>>,
+
+    /// The names of the instances of generic types in each rustdoc source, which the types borrow
+    instances: OnceCell<Vec<Vec<String>>>,
+}
+
+// a type name pattern, like `User*`
+fn pattern(pattern: &str) -> Result<glob::Pattern, Diagnostic> {
+    glob::Pattern::new(pattern)
+        .map_err(|err| Diagnostic::error(format!("invalid pattern `{pattern}`: {err}")))
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Builder::default()
+    }
+
+    pub fn source(mut self, source: Source) -> Self {
+        self.sources.push(source);
+        self
+    }
+
+    pub fn sources(mut self, sources: impl IntoIterator<Item = Source>) -> Self {
+        self.sources.extend(sources);
+        self
+    }
+
+    pub fn options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn target(mut self, target: Target) -> Self {
+        self.target = target;
+        self
+    }
+
+    pub fn frontend(mut self, frontend: Frontend) -> Self {
+        self.frontend = frontend;
+        self
+    }
+
+    pub fn destination(mut self, destination: Destination) -> Self {
+        self.destination = destination;
+        self
+    }
+
+    /// Enabled for `#[cfg(feature = "...")]`
+    pub fn features(mut self, features: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.features = features.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Only generate types whose names match one of these patterns, all of them when there are none
+    pub fn include(mut self, patterns: &[impl AsRef<str>]) -> Result<Self, Diagnostic> {
+        self.include = patterns.iter().map(|p| pattern(p.as_ref())).try_collect()?;
+        Ok(self)
+    }
+
+    /// Don't generate types whose names match one of these patterns
+    pub fn exclude(mut self, patterns: &[impl AsRef<str>]) -> Result<Self, Diagnostic> {
+        self.exclude = patterns.iter().map(|p| pattern(p.as_ref())).try_collect()?;
+        Ok(self)
+    }
+
+    /// Renders diagnostics from `lex` and `parse`, quoting the sources they point into
+    pub fn render(&self, diagnostics: &[Diagnostic]) -> String {
+        render(&self.all_sources(), diagnostics)
+    }
+
+    // the inputs, and the crate files rustdoc's types were found in, or the crates' modules
+    fn all_sources(&self) -> Vec<&Source> {
+        self.sources
+            .iter()
+            .chain(self.crate_files.get().into_iter().flatten())
+            .collect()
+    }
+
+    // read the first time they're needed, next to the JSON or in the directories above it
+    // which is where cargo runs rustdoc from, or where the roots' `mod`s say they are
+    fn crate_files(&self) -> &[Source] {
+        self.crate_files.get_or_init(|| {
+            let mut files: Vec<Source> = Vec::new();
+
+            if self.frontend == Frontend::Crate {
+                for source in &self.sources {
+                    for file in krate::files(source, &self.features) {
+                        if !files.iter().any(|known| known.path == file.path) {
+                            files.push(file);
+                        }
+                    }
+                }
+
+                return files;
+            }
+
+            for source in &self.sources {
+                let dirs: Vec<_> = match &source.path {
+                    Some(path) => path.ancestors().skip(1).collect(),
+                    None => vec![Path::new("")],
+                };
+
+                for name in rustdoc::files(&source.text) {
+                    let found = dirs
+                        .iter()
+                        .map(|dir| dir.join(&name))
+                        .find(|path| path.is_file());
+
+                    let Some(path) = found else {
+                        continue;
+                    };
+
+                    if files.iter().any(|file| file.path.as_ref() == Some(&path)) {
+                        continue;
+                    }
+
+                    if let Ok(file) = Source::from_path(&path) {
+                        files.push(file);
+                    }
+                }
+            }
+
+            files
+        })
+    }
+
+    // found by walking each crate once without them
+    fn instances(&self, selected: &impl Fn(&str) -> bool) -> &[Vec<String>] {
+        self.instances.get_or_init(|| {
+            self.sources
+                .iter()
+                .map(|source| {
+                    rustdoc::RustdocParser::new(&source.text, &source.name, self.crate_files())
+                        .with_features(&self.features)
+                        .instances(selected)
+                })
+                .collect()
+        })
+    }
+
+    fn registries(&self) -> &[Result<serde_reflection::Registry, Diagnostic>] {
+        self.registries
+            .get_or_init(|| self.sources.iter().map(registry::read).collect())
+    }
+
+    /// Lexes every source, rustdoc's JSON, registries and crates aren't
+    pub fn lex(&self) -> Vec<Lexed<'_>> {
+        self.sources
+            .iter()
+            .map(|source| {
+                if matches!(
+                    self.frontend,
+                    Frontend::Crate | Frontend::Rustdoc | Frontend::Registry
+                ) {
+                    return (Vec::new(), Vec::new());
+                }
+
+                let (tokens, errors) = lexer::Lexer::new(&source.text).lex();
+                let errors = errors
+                    .into_iter()
+                    .map(|err| err.with_file(&source.name))
+                    .collect();
+
+                (tokens, errors)
+            })
+            .collect()
+    }
+
+    /// The selected types of each source, or every error in all of them
+    ///
+    /// The other frontends read the sources themselves, so `lexed` is only used by the native one
+    pub fn parse<'a>(
+        &'a self,
+        lexed: &'a [Lexed<'a>],
+    ) -> Result<Vec<Vec<DeclaredType<'a>>>, Vec<Diagnostic>> {
+        let selected = |name: &str| {
+            (self.include.is_empty() || self.include.iter().any(|p| p.matches(name)))
+                && !self.exclude.iter().any(|p| p.matches(name))
+        };
+
+        let mut errors = Vec::new();
+        let mut types = Vec::new();
+
+        for (i, (source, (tokens, lex_errors))) in self.sources.iter().zip(lexed).enumerate() {
+            let parsed = match self.frontend {
+                Frontend::Native => {
+                    errors.extend(lex_errors.iter().cloned());
+
+                    parser::Parser::new(tokens, &source.name)
+                        .with_features(&self.features)
+                        .parse()
+                }
+                Frontend::Syn => syn_parser::SynParser::new(&source.text, &source.name)
+                    .with_features(&self.features)
+                    .parse(),
+                // whole crates, so what isn't selected isn't even looked at
+                Frontend::Crate => krate::CrateParser::new(source, self.crate_files())
+                    .with_features(&self.features)
+                    .parse(selected),
+                Frontend::Rustdoc => {
+                    rustdoc::RustdocParser::new(&source.text, &source.name, self.crate_files())
+                        .with_features(&self.features)
+                        .with_names(&self.instances(&selected)[i])
+                        .parse(selected)
+                }
+                Frontend::Registry => match &self.registries()[i] {
+                    Ok(registry) => registry::RegistryParser::new(registry, source).parse(),
+                    Err(err) => Err(vec![err.clone()]),
+                },
+            };
+
+            match parsed {
+                Ok(mut parsed) => {
+                    parsed.retain(|decl| selected(decl.name));
+                    types.push(parsed);
+                }
+                // a crate's and rustdoc's point into the crate's files
+                Err(parse_errors) => errors.extend(
+                    parse_errors
+                        .into_iter()
+                        .map(|err| err.or_file(&source.name)),
+                ),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(types)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Generates everything, or nothing if there are any errors
+    pub fn generate(&self) -> Generated<'_> {
+        let failed = |diagnostics| Generated {
+            files: Vec::new(),
+            diagnostics,
+            sources: self.all_sources(),
+        };
+
+        let lexed = self.lex();
+
+        let types = match self.parse(&lexed) {
+            Ok(types) => types,
+            Err(errors) => return failed(errors),
+        };
+
+        // the only backend, for now
+        // with the types of the sibling modules it can import from
+        let generate = |types, siblings: Vec<(&str, Vec<DeclaredType<'_>>)>| match self.target {
+            Target::Python => siblings
+                .into_iter()
+                .fold(
+                    codegen::Codegen::new(types, self.options.clone()),
+                    |codegen, (module, types)| codegen.sibling(module, types),
+                )
+                .generate(),
+        };
+
+        let mut files = Vec::new();
+
+        let runtime_dir = match &self.destination {
+            Destination::Dir(dir) => {
+                let mut modules = Vec::new();
+
+                for source in &self.sources {
+                    let Some(path) = &source.path else {
+                        return failed(vec![Diagnostic::error(format!(
+                            "`{}` has no path to name its module after",
+                            source.name
+                        ))]);
+                    };
+
+                    let stem = path.file_stem().unwrap_or(path.as_os_str());
+
+                    modules.push(stem.to_string_lossy().into_owned());
+                }
+
+                // every module sees every declaration, and imports the ones its siblings declare
+                for (i, declared) in types.iter().enumerate() {
+                    let siblings = modules
+                        .iter()
+                        .zip(&types)
+                        .enumerate()
+                        .filter(|&(j, _)| j != i)
+                        .map(|(_, (module, types))| (module.as_str(), types.to_vec()))
+                        .collect();
+
+                    match generate(declared.to_vec(), siblings) {
+                        Ok(contents) => files.push(File {
+                            path: Some(dir.join(&modules[i]).with_extension("py")),
+                            contents,
+                        }),
+                        Err(err) => return failed(vec![err]),
+                    }
+                }
+
+                Some(dir.clone())
+            }
+            Destination::File(path) => {
+                match generate(types.into_iter().flatten().collect(), Vec::new()) {
+                    Ok(contents) => files.push(File {
+                        path: path.clone(),
+                        contents,
+                    }),
+                    Err(err) => return failed(vec![err]),
+                }
+
+                path.as_ref()
+                    .and_then(|path| path.parent())
+                    .map(Path::to_path_buf)
+            }
+        };
+
+        if self.options.runtime == codegen::Runtime::Write {
+            files.push(File {
+                path: Some(
+                    runtime_dir
+                        .unwrap_or_default()
+                        .join(format!("{}.py", runtime::MODULE)),
+                ),
+                contents: runtime::SOURCE.to_string(),
+            });
+        }
+
+        Generated {
+            files,
+            diagnostics: Vec::new(),
+            sources: self.all_sources(),
+        }
+    }
+}
+
+// the diagnostics in order within each file, and how many there were
+fn render(sources: &[&Source], diagnostics: &[Diagnostic]) -> String {
+    if diagnostics.is_empty() {
+        return String::new();
+    }
+
+    let file_index = |err: &Diagnostic| {
+        sources
+            .iter()
+            .position(|source| err.file.as_deref() == Some(source.name.as_str()))
+    };
+
+    let rendered = diagnostics
+        .iter()
+        .sorted_by_key(|err| (file_index(err), err.span.map(|span| span.start)))
+        .map(|err| {
+            let text = file_index(err).map_or("", |i| sources[i].text.as_str());
+
+            err.render(text)
+        })
+        .join("\n");
+
+    let summary = match diagnostics.len() {
+        1 => "aborting due to 1 previous error".to_string(),
+        n => format!("aborting due to {n} previous errors"),
+    };
+
+    format!("{rendered}\n{}", Diagnostic::error(summary).render(""))
+}
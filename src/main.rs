@@ -1,24 +1,390 @@
-mod codegen;
-mod lexer;
-mod parser;
+mod cli;
 
-fn main() {
-    let mut args = std::env::args();
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
-    let input = args.nth(1).expect("missing filename argument");
+use clap::Parser as _;
+use marshal::config::{self, Config};
+use marshal::diagnostic::Diagnostic;
+use marshal::{Builder, Destination, File, Source};
+use similar::TextDiff;
 
-    let output = args.next();
-    let output = output.as_deref().unwrap_or("out.py");
+use cli::{Cli, Command, Inputs};
 
-    let input = std::fs::read_to_string(&input).expect("cannot read file");
+fn main() -> ExitCode {
+    let cli = Cli::parse();
 
-    let lexer = lexer::Lexer::new(&input);
-    let tokens = lexer.lex();
+    match run(&cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failure) => {
+            let (code, message) = match failure {
+                Failure::Invalid(message) => (1, message),
+                Failure::Usage(message) => (2, message),
+                Failure::Io(message) => (3, message),
+                Failure::Stale(message) => (4, message),
+            };
 
-    let parser = parser::Parser::new(&tokens);
-    let types = parser.parse();
+            eprint!("{message}");
 
-    let code = codegen::Codegen::new(types).generate();
+            ExitCode::from(code)
+        }
+    }
+}
+
+/// Why a run failed, each is its own exit code
+///
+/// The messages are rendered and ready to print
+enum Failure {
+    /// the inputs have errors
+    Invalid(String),
+
+    /// the command line asks for something we can't do
+    Usage(String),
+
+    /// a file couldn't be read or written
+    Io(String),
+
+    /// `--check` found files which don't match what we'd generate
+    Stale(String),
+}
+
+fn usage(message: &str) -> Failure {
+    Failure::Usage(Diagnostic::error(message).render(""))
+}
+
+fn io(message: &str) -> Failure {
+    Failure::Io(Diagnostic::error(message).render(""))
+}
+
+fn run(cli: &Cli) -> Result<(), Failure> {
+    let log = |message: &str| {
+        if cli.verbose {
+            eprintln!("{message}");
+        }
+    };
+
+    let summary = |message: &str| {
+        if !cli.quiet {
+            eprintln!("{message}");
+        }
+    };
+
+    let config = load_config(cli, &log)?;
+
+    // `marshal` on its own generates whatever the config says
+    let default = Command::Generate {
+        inputs: Inputs::default(),
+        output: cli::Output::default(),
+        codegen: cli::Codegen::default(),
+    };
+
+    match cli.command.as_ref().unwrap_or(&default) {
+        Command::Generate {
+            inputs,
+            output,
+            codegen,
+        } => {
+            let options = codegen.options(&config).map_err(|err| usage(&err))?;
+            let write_runtime = options.runtime == marshal::codegen::Runtime::Write;
+
+            let destination = output.destination(&config);
+            let to_stdout = destination == Destination::File(None);
+
+            if write_runtime && to_stdout {
+                return Err(usage(
+                    "`--runtime write` needs somewhere to write to, pass `--output` or `--out-dir`",
+                ));
+            }
+
+            if output.check && to_stdout {
+                return Err(usage(
+                    "`--check` needs files to compare against, pass `--output` or `--out-dir`",
+                ));
+            }
+
+            let sources = read_inputs(&inputs.paths(&config), &log)?;
+
+            if matches!(destination, Destination::Dir(_))
+                && sources.iter().any(|source| source.path.is_none())
+            {
+                return Err(usage(
+                    "stdin has no name to use in `--out-dir`, use `--output` instead",
+                ));
+            }
+
+            let builder = builder(inputs, &config, sources)?
+                .options(options)
+                .target(codegen.target(&config))
+                .destination(destination.clone());
+
+            // everything is generated before anything is written
+            let generated = builder.generate();
+
+            if !generated.diagnostics.is_empty() {
+                return Err(Failure::Invalid(generated.render_diagnostics()));
+            }
+
+            if output.check {
+                return check_files(&generated.files, &log);
+            }
+
+            for file in &generated.files {
+                match &file.path {
+                    Some(path) => {
+                        write_file(path, &file.contents)?;
+                        log(&format!("wrote {}", path.display()));
+                    }
+                    None => write_stdout(&file.contents)?,
+                }
+            }
+        }
+        Command::Check { inputs, codegen } => {
+            let sources = read_inputs(&inputs.paths(&config), &log)?;
+            let count = sources.len();
+
+            let options = codegen.options(&config).map_err(|err| usage(&err))?;
+
+            let builder = builder(inputs, &config, sources)?
+                .options(options)
+                .target(codegen.target(&config));
+
+            let types = parse(&builder)?;
+            let generated = builder.generate();
+
+            if !generated.diagnostics.is_empty() {
+                return Err(Failure::Invalid(generated.render_diagnostics()));
+            }
+
+            summary(&format!(
+                "ok: {} in {}",
+                plural(types, "type"),
+                plural(count, "file")
+            ));
+        }
+        Command::Dump { inputs, tokens } => {
+            let sources = read_inputs(&inputs.paths(&config), &log)?;
+            let names: Vec<_> = sources.iter().map(|source| source.name.clone()).collect();
+
+            let builder = builder(inputs, &config, sources)?;
+            let lexed = builder.lex();
+
+            let mut out = String::new();
+
+            if *tokens {
+                let errors: Vec<_> = lexed
+                    .iter()
+                    .flat_map(|(_, errors)| errors.clone())
+                    .collect();
+
+                if !errors.is_empty() {
+                    return Err(Failure::Invalid(builder.render(&errors)));
+                }
+
+                for (name, (tokens, _)) in names.iter().zip(&lexed) {
+                    out.push_str(&format!("// {name}\n"));
+
+                    for token in tokens {
+                        out.push_str(&format!(
+                            "{}:{} {token}\n",
+                            token.span.line, token.span.column
+                        ));
+                    }
+                }
+            } else {
+                let types = builder
+                    .parse(&lexed)
+                    .map_err(|errors| Failure::Invalid(builder.render(&errors)))?;
+
+                for (name, types) in names.iter().zip(types) {
+                    out.push_str(&format!("// {name}\n{types:#?}\n"));
+                }
+            }
+
+            write_stdout(&out)?;
+        }
+        Command::Validate { inputs } => {
+            let sources = read_inputs(&inputs.paths(&config), &log)?;
+            let count = sources.len();
+
+            let builder = builder(inputs, &config, sources)?;
+            let types = parse(&builder)?;
+
+            summary(&format!(
+                "ok: {} in {}",
+                plural(types, "type"),
+                plural(count, "file")
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// `--config`, or `marshal.toml` if there is one
+fn load_config(cli: &Cli, log: &dyn Fn(&str)) -> Result<Config, Failure> {
+    let path = match &cli.config {
+        Some(path) => path.clone(),
+        None if Path::new(config::FILE).exists() => PathBuf::from(config::FILE),
+        None => return Ok(Config::default()),
+    };
+
+    let text = std::fs::read_to_string(&path)
+        .map_err(|err| io(&format!("cannot read `{}`: {err}", path.display())))?;
+
+    log(&format!("read {}", path.display()));
+
+    Config::parse(&text, &path)
+        .map_err(|err| Failure::Usage(err.with_file(path.display().to_string()).render(&text)))
+}
+
+// `-` is stdin, patterns are expanded in order
+fn read_inputs(patterns: &[String], log: &dyn Fn(&str)) -> Result<Vec<Source>, Failure> {
+    let mut sources = Vec::new();
+
+    if patterns.is_empty() {
+        return Err(usage(&format!(
+            "no inputs, pass some or list them in `{}`",
+            config::FILE
+        )));
+    }
+
+    for pattern in patterns {
+        if pattern == "-" {
+            let mut text = String::new();
+
+            std::io::stdin()
+                .read_to_string(&mut text)
+                .map_err(|err| io(&format!("cannot read stdin: {err}")))?;
+
+            log("read <stdin>");
+
+            sources.push(Source::new("<stdin>", text));
+
+            continue;
+        }
+
+        let paths = if pattern.contains(['*', '?', '[']) {
+            let paths = glob::glob(pattern)
+                .map_err(|err| usage(&format!("invalid pattern `{pattern}`: {err}")))?
+                .map(|path| path.map_err(|err| io(&format!("cannot read `{pattern}`: {err}"))))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            if paths.is_empty() {
+                return Err(usage(&format!("no files match `{pattern}`")));
+            }
+
+            paths
+        } else {
+            vec![PathBuf::from(pattern)]
+        };
+
+        for path in paths {
+            let source = Source::from_path(&path)
+                .map_err(|err| io(&format!("cannot read `{}`: {err}", path.display())))?;
+
+            log(&format!("read {}", path.display()));
+
+            sources.push(source);
+        }
+    }
+
+    Ok(sources)
+}
+
+// the inputs and the types they select
+fn builder(inputs: &Inputs, config: &Config, sources: Vec<Source>) -> Result<Builder, Failure> {
+    Builder::new()
+        .sources(sources)
+        .features(inputs.features(config))
+        .frontend(inputs.frontend(config))
+        .include(inputs.include(config))
+        .and_then(|builder| builder.exclude(inputs.exclude(config)))
+        .map_err(|err| Failure::Usage(err.render("")))
+}
+
+// how many types the inputs select, or every error in them
+fn parse(builder: &Builder) -> Result<usize, Failure> {
+    let lexed = builder.lex();
+
+    let types = builder
+        .parse(&lexed)
+        .map_err(|errors| Failure::Invalid(builder.render(&errors)))?;
+
+    Ok(types.iter().map(Vec::len).sum())
+}
+
+fn plural(count: usize, word: &str) -> String {
+    match count {
+        1 => format!("1 {word}"),
+        n => format!("{n} {word}s"),
+    }
+}
+
+// diffs what's on disk against what we'd write
+fn check_files(files: &[File], log: &dyn Fn(&str)) -> Result<(), Failure> {
+    let mut diff = String::new();
+    let mut stale = 0;
+
+    for File {
+        path,
+        contents: code,
+    } in files
+    {
+        let Some(file) = path else {
+            continue;
+        };
+
+        // a missing file is stale, as if it were empty
+        let existing = match std::fs::read_to_string(file) {
+            Ok(existing) => existing,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(io(&format!("cannot read `{}`: {err}", file.display()))),
+        };
+
+        if existing == *code {
+            log(&format!("{} is up to date", file.display()));
+            continue;
+        }
+
+        let name = file.display().to_string();
+
+        diff.push_str(
+            &TextDiff::from_lines(&existing, code)
+                .unified_diff()
+                .header(&format!("a/{name}"), &format!("b/{name}"))
+                .to_string(),
+        );
+
+        stale += 1;
+    }
+
+    if stale == 0 {
+        return Ok(());
+    }
+
+    write_stdout(&diff)?;
+
+    Err(Failure::Stale(
+        Diagnostic::error(format!("{} out of date", plural(stale, "file")))
+            .with_note("run `marshal generate` without `--check` to update them")
+            .render(""),
+    ))
+}
+
+// creating its directory, like `Generated::write`
+fn write_file(path: &Path, contents: &str) -> Result<(), Failure> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)
+            .map_err(|err| io(&format!("cannot create `{}`: {err}", dir.display())))?;
+    }
+
+    std::fs::write(path, contents)
+        .map_err(|err| io(&format!("cannot write `{}`: {err}", path.display())))
+}
 
-    std::fs::write(output, code).expect("cannot write file");
+fn write_stdout(contents: &str) -> Result<(), Failure> {
+    std::io::stdout()
+        .write_all(contents.as_bytes())
+        .map_err(|err| io(&format!("cannot write to stdout: {err}")))
 }
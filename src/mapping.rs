@@ -0,0 +1,287 @@
+// Python types for Rust types from outside the inputs, like `rust_decimal::Decimal`
+//
+// mappings are keyed by a Rust path, which may have generic parameters like `smallvec::SmallVec<T>`
+// the parameters are substituted into the Python type, `list[T]`
+//
+// a path matches the mappings it's a suffix of, so `Decimal` finds `rust_decimal::Decimal`
+// as we don't know what's been imported, but one naming a crate has to name the mapping's,
+// `time::Duration` isn't `std::time::Duration`
+//
+// there are built-in mappings for common crates too, which the user's mappings and declared types override
+//
+// codecs are the same idea for fields, Python callables standing in for the Rust functions
+// in `#[serde(with = "...")]`, `serialize_with` and `deserialize_with`
+
+use std::collections::HashMap;
+
+/// How a Rust type is represented in Python
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeMapping {
+    /// The Python type, dotted names are imported
+    pub python: String,
+
+    /// A Python callable turning the value into plain data, otherwise it's used as it is
+    pub encode: Option<String>,
+
+    /// A Python callable turning plain data into the value, otherwise it's used as it is
+    pub decode: Option<String>,
+
+    /// An immutable Python expression for `Default::default()`
+    pub default: Option<String>,
+}
+
+impl TypeMapping {
+    pub fn new(python: impl Into<String>) -> Self {
+        TypeMapping {
+            python: python.into(),
+            encode: None,
+            decode: None,
+            default: None,
+        }
+    }
+
+    fn hooked(python: &str, encode: &str, decode: &str) -> Self {
+        TypeMapping {
+            python: python.to_string(),
+            encode: Some(encode.to_string()),
+            decode: Some(decode.to_string()),
+            default: None,
+        }
+    }
+
+    fn with_default(mut self, default: &str) -> Self {
+        self.default = Some(default.to_string());
+        self
+    }
+}
+
+/// Mappings for types from `std` and common crates, in the formats their `Serialize` impls produce
+///
+/// Undotted hooks are defined by the runtime
+pub fn builtins() -> HashMap<String, TypeMapping> {
+    let mappings = [
+        // RFC 3339, `2024-05-01T12:30:00.250Z`
+        (
+            "chrono::DateTime<Tz>",
+            TypeMapping::hooked("datetime.datetime", "encode_datetime", "decode_datetime"),
+        ),
+        (
+            "chrono::NaiveDateTime",
+            TypeMapping::hooked("datetime.datetime", "encode_datetime", "decode_datetime"),
+        ),
+        (
+            "chrono::NaiveDate",
+            TypeMapping::hooked("datetime.date", "encode_date", "decode_date"),
+        ),
+        // `time`'s own format, `2024-05-01 12:30:00.25 +00:00:00`
+        (
+            "time::OffsetDateTime",
+            TypeMapping::hooked(
+                "datetime.datetime",
+                "encode_offset_datetime",
+                "decode_offset_datetime",
+            ),
+        ),
+        (
+            "uuid::Uuid",
+            TypeMapping::hooked("uuid.UUID", "str", "uuid.UUID").with_default("uuid.UUID(int=0)"),
+        ),
+        ("url::Url", TypeMapping::new("str")),
+        (
+            "std::net::IpAddr",
+            TypeMapping::hooked(
+                "ipaddress.IPv4Address | ipaddress.IPv6Address",
+                "str",
+                "ipaddress.ip_address",
+            ),
+        ),
+        (
+            "std::net::Ipv4Addr",
+            TypeMapping::hooked("ipaddress.IPv4Address", "str", "ipaddress.ip_address"),
+        ),
+        (
+            "std::net::Ipv6Addr",
+            TypeMapping::hooked("ipaddress.IPv6Address", "str", "ipaddress.ip_address"),
+        ),
+        ("std::net::SocketAddr", TypeMapping::new("str")),
+        // `Type::Json`, for when it's imported
+        (
+            "serde_json::Value",
+            TypeMapping::new("JsonValue").with_default("None"),
+        ),
+        (
+            "std::path::PathBuf",
+            TypeMapping::hooked("pathlib.Path", "str", "pathlib.Path"),
+        ),
+        // `{"secs": 1, "nanos": 500000000}`
+        (
+            "std::time::Duration",
+            TypeMapping::hooked("datetime.timedelta", "encode_duration", "decode_duration")
+                .with_default("datetime.timedelta()"),
+        ),
+        // `{"secs_since_epoch": 1714566600, "nanos_since_epoch": 0}`
+        (
+            "std::time::SystemTime",
+            TypeMapping::hooked(
+                "datetime.datetime",
+                "encode_system_time",
+                "decode_system_time",
+            ),
+        ),
+    ];
+
+    mappings
+        .into_iter()
+        .map(|(path, mapping)| (path.to_string(), mapping))
+        .collect()
+}
+
+/// Python callables for a field's custom serialization, keyed by the Rust path in its attribute
+///
+/// A `with` module needs both, `serialize_with` and `deserialize_with` functions one each
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Codec {
+    /// Turns the field's value into plain data
+    pub encode: Option<String>,
+
+    /// Turns plain data into the field's value
+    pub decode: Option<String>,
+}
+
+/// The hook for the Rust path in a field's attribute, either of which can end with the other, `hex` is `crate::hex`
+///
+/// `side` picks the hook, the longest path which has it wins
+pub fn find_codec<'c>(
+    codecs: &'c HashMap<String, Codec>,
+    path: &str,
+    side: fn(&Codec) -> &Option<String>,
+) -> Option<&'c str> {
+    let (segments, _) = split_key(path);
+
+    codecs
+        .iter()
+        .filter_map(|(key, codec)| Some((key, side(codec).as_deref()?)))
+        .filter(|(key, _)| is_suffix(&split_key(key).0, &segments))
+        .max_by(|(a, _), (b, _)| {
+            let len = |key: &str| split_key(key).0.len();
+            len(a).cmp(&len(b)).then(b.cmp(a))
+        })
+        .map(|(_, hook)| hook)
+}
+
+/// A mapping which applies to a path, and the names of its generic parameters
+pub struct Found<'m> {
+    pub mapping: &'m TypeMapping,
+    pub params: Vec<&'m str>,
+}
+
+// `smallvec::SmallVec<T>` -> `(["smallvec", "SmallVec"], ["T"])`
+fn split_key(key: &str) -> (Vec<&str>, Vec<&str>) {
+    let (path, params) = match key.split_once('<') {
+        Some((path, params)) => (path, params.trim_end().trim_end_matches('>')),
+        None => (key, ""),
+    };
+
+    let segments = path
+        .trim()
+        .trim_start_matches("::")
+        .split("::")
+        .map(str::trim)
+        .collect();
+
+    let params = params
+        .split(',')
+        .map(str::trim)
+        .filter(|param| !param.is_empty())
+        .collect();
+
+    (segments, params)
+}
+
+fn is_suffix(a: &[&str], b: &[&str]) -> bool {
+    a.ends_with(b) || b.ends_with(a)
+}
+
+// `written` is the end of `key`, `Duration` names `std::time::Duration`,
+// but one naming its crate has to name the same one, `time::Duration` is another type
+fn names(key: &[&str], written: &[&str]) -> bool {
+    key.ends_with(written) && (written.len() == 1 || written.first() == key.first())
+}
+
+/// The mapping for a path with `args` generic arguments
+///
+/// The longest matching path wins, so `a::Decimal` and `b::Decimal` can be told apart when they're written out
+pub fn find<'m>(
+    mappings: &'m HashMap<String, TypeMapping>,
+    segments: &[&str],
+    args: usize,
+) -> Option<Found<'m>> {
+    mappings
+        .iter()
+        .filter_map(|(key, mapping)| {
+            let (key_segments, params) = split_key(key);
+
+            let matches = params.len() == args && names(&key_segments, segments);
+
+            matches.then_some((key_segments.len(), key, Found { mapping, params }))
+        })
+        // ties go to the first key, so the choice doesn't depend on the map's order
+        .max_by(|(a_len, a_key, _), (b_len, b_key, _)| a_len.cmp(b_len).then(b_key.cmp(a_key)))
+        .map(|(_, _, found)| found)
+}
+
+// the identifiers in a Python type
+fn words(python: &str) -> impl Iterator<Item = &str> {
+    python
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| !word.is_empty())
+}
+
+/// The generic parameter `param` appears in `python`, `chrono::DateTime<Tz>` ignores its `Tz`
+pub fn uses(python: &str, param: &str) -> bool {
+    words(python).any(|word| word == param)
+}
+
+/// The classes in the Python type, without their arguments
+///
+/// `ipaddress.IPv4Address | ipaddress.IPv6Address` is both of them
+pub fn classes(python: &str) -> Vec<&str> {
+    python
+        .split('|')
+        .map(|class| class.split('[').next().unwrap_or_default().trim())
+        .filter(|class| *class != "None")
+        .collect()
+}
+
+/// The Python type is a `list`, so its items can be converted like a `Vec`'s
+pub fn is_list(python: &str) -> bool {
+    python.trim().starts_with("list[")
+}
+
+/// Replaces the generic parameters in `python` with the Python types of the arguments
+pub fn substitute(python: &str, params: &[&str], args: &[String]) -> String {
+    let mut out = String::new();
+    let mut word = String::new();
+
+    let flush = |word: &mut String, out: &mut String| {
+        match params.iter().position(|param| param == word) {
+            Some(i) => out.push_str(&args[i]),
+            None => out.push_str(word),
+        }
+
+        word.clear();
+    };
+
+    for c in python.chars() {
+        if c.is_alphanumeric() || c == '_' {
+            word.push(c);
+        } else {
+            flush(&mut word, &mut out);
+            out.push(c);
+        }
+    }
+
+    flush(&mut word, &mut out);
+
+    out
+}
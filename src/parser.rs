@@ -1,41 +1,156 @@
-use crate::lexer::{LexicalToken, TokenType};
+use crate::diagnostic::Diagnostic;
+use crate::lexer::{LexicalToken, Span, TokenType};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Type<'a> {
     Unit,
     Bool,
-    Int,
-    Float,
+    Int(Int),
+    Float(Float),
+    /// a `char`, a string of one character
+    Char,
     String,
+    /// `serde_json::Value`, any JSON
+    Json,
     Option(Box<Type<'a>>),
     Tuple(Vec<Type<'a>>),
     Array(Box<Type<'a>>),
+    /// also `VecDeque`, `LinkedList` and `BinaryHeap`, which serialize the same
     Vec(Box<Type<'a>>),
+    /// `HashSet`, `BTreeSet` or `IndexSet`
+    Set(Box<Type<'a>>),
+    /// `HashMap`, `BTreeMap` or `IndexMap`, its keys and values
+    Map(Box<Type<'a>>, Box<Type<'a>>),
+    /// `bytes::Bytes`, `serde_bytes::ByteBuf`, or a `Vec<u8>` serialized like one
+    Bytes(Bytes),
     // Struct(Rc<Struct<'a>>),
     // Enum(Rc<Enum<'a>>),
-    User(&'a str),
+    /// one of the declared types, or a type from elsewhere
+    User(TypePath<'a>),
 }
 
-#[derive(Debug)]
+/// The width and signedness of an integer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Int {
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    Isize,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    Usize,
+}
+
+impl Int {
+    const ALL: [Int; 12] = [
+        Int::I8,
+        Int::I16,
+        Int::I32,
+        Int::I64,
+        Int::I128,
+        Int::Isize,
+        Int::U8,
+        Int::U16,
+        Int::U32,
+        Int::U64,
+        Int::U128,
+        Int::Usize,
+    ];
+
+    /// The Rust name, like `u8`
+    pub fn name(self) -> &'static str {
+        match self {
+            Int::I8 => "i8",
+            Int::I16 => "i16",
+            Int::I32 => "i32",
+            Int::I64 => "i64",
+            Int::I128 => "i128",
+            Int::Isize => "isize",
+            Int::U8 => "u8",
+            Int::U16 => "u16",
+            Int::U32 => "u32",
+            Int::U64 => "u64",
+            Int::U128 => "u128",
+            Int::Usize => "usize",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Int> {
+        Int::ALL.into_iter().find(|int| int.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Float {
+    F32,
+    F64,
+}
+
+impl Float {
+    /// The Rust name, like `f32`
+    pub fn name(self) -> &'static str {
+        match self {
+            Float::F32 => "f32",
+            Float::F64 => "f64",
+        }
+    }
+}
+
+/// How a byte buffer is serialized
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bytes {
+    /// With `serialize_bytes`, which is up to the format, an array of numbers in JSON
+    Native,
+
+    /// `serde_with`'s `Base64`, a string in every format
+    Base64 { url_safe: bool, padded: bool },
+}
+
+/// A path to a type, like `rust_decimal::Decimal` or `SmallVec<T>`
+#[derive(Debug, Clone)]
+pub struct TypePath<'a> {
+    /// `a::b::C` is `["a", "b", "C"]`, a leading `::` is dropped
+    pub segments: Vec<&'a str>,
+
+    /// the generic arguments of the last segment
+    pub args: Vec<Type<'a>>,
+}
+
+impl<'a> TypePath<'a> {
+    /// The last segment, which is what declared types are called
+    pub fn name(&self) -> &'a str {
+        self.segments.last().copied().unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct StructField<'a> {
     pub attrs: Vec<SerdeAttribute<'a>>,
     pub name: &'a str,
     pub ty: Type<'a>,
+
+    /// where the field's name is
+    pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Struct<'a> {
     pub fields: Vec<StructField<'a>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum EnumVariantInner<'a> {
     Unit,
     Tuple(Vec<Type<'a>>),
     Struct(Struct<'a>),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum SerdeAttribute<'a> {
     Rename(&'a str),
     Default(Option<&'a str>),
@@ -46,369 +161,939 @@ pub enum SerdeAttribute<'a> {
     SkipDeserializingIf(&'a str),
     SerializeWith(&'a str),
     DeserializeWith(&'a str),
+    With(&'a str),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EnumVariant<'a> {
     pub attrs: Vec<SerdeAttribute<'a>>,
     pub name: &'a str,
     pub inner: EnumVariantInner<'a>,
+
+    /// marked `#[default]`, used by `#[derive(Default)]`
+    pub default: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Enum<'a> {
     pub variants: Vec<EnumVariant<'a>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum InnerType<'a> {
     Struct(Struct<'a>),
     Enum(Enum<'a>),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DeclaredType<'a> {
+    pub attrs: Vec<SerdeAttribute<'a>>,
     pub name: &'a str,
     pub inner: InnerType<'a>,
+
+    /// the traits in `#[derive(...)]`, without their paths
+    pub derives: Vec<&'a str>,
+
+    /// where the type's name is
+    pub span: Span,
+
+    /// the file it was declared in
+    pub file: &'a str,
+}
+
+impl<'a> DeclaredType<'a> {
+    pub fn has_derive(&self, trait_: &str) -> bool {
+        self.derives.contains(&trait_)
+    }
+}
+
+// everything we understand from the attributes on an item, variant, or field
+#[derive(Default)]
+pub(crate) struct Attributes<'a> {
+    pub(crate) serde: Vec<SerdeAttribute<'a>>,
+    pub(crate) derives: Vec<&'a str>,
+    pub(crate) default: bool,
+
+    /// a `#[cfg(...)]` on it is false, so it doesn't exist
+    pub(crate) disabled: bool,
+
+    /// `#[serde_as(as = "...")]`, and where it is
+    pub(crate) serde_as: Option<(&'a str, Span)>,
+}
+
+/// A bare `Bytes`, `BytesMut` or `ByteBuf`, which is a byte buffer unless the inputs declare one
+///
+/// codegen knows what's declared, an attribute serializing it as bytes says it's one
+pub fn is_bare_bytes(path: &TypePath) -> bool {
+    matches!(path.segments[..], ["Bytes" | "BytesMut" | "ByteBuf"]) && path.args.is_empty()
+}
+
+// `ty` serialized as bytes, `None` if it isn't a byte buffer
+fn as_bytes(ty: Type, bytes: Bytes) -> Option<Type> {
+    match ty {
+        Type::Vec(item) if matches!(*item, Type::Int(Int::U8)) => Some(Type::Bytes(bytes)),
+        Type::Bytes(_) => Some(Type::Bytes(bytes)),
+        Type::User(path) if is_bare_bytes(&path) => Some(Type::Bytes(bytes)),
+        Type::Option(ty) => Some(Type::Option(Box::new(as_bytes(*ty, bytes)?))),
+        _ => None,
+    }
+}
+
+// `ty` converted as `#[serde_as(as = "...")]` says, like `Option<Base64>`
+// `None` for conversions we don't understand
+fn serde_as_type<'a>(as_: &str, ty: Type<'a>) -> Option<Type<'a>> {
+    let as_ = as_.trim();
+
+    let (path, inner) = match as_.split_once('<') {
+        Some((path, inner)) => (path.trim(), inner.trim_end().strip_suffix('>')?),
+        None => (as_, ""),
+    };
+
+    let last = |path: &'_ str| {
+        path.trim()
+            .rsplit("::")
+            .next()
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    match (last(path).as_str(), ty) {
+        ("_", ty) => Some(ty),
+        ("Option", Type::Option(ty)) => Some(Type::Option(Box::new(serde_as_type(inner, *ty)?))),
+        ("Vec", Type::Vec(ty)) => Some(Type::Vec(Box::new(serde_as_type(inner, *ty)?))),
+        ("Bytes", ty) => as_bytes(ty, Bytes::Native),
+        // `Base64<Alphabet, Padding>`
+        ("Base64", ty) => {
+            let mut url_safe = false;
+            let mut padded = true;
+
+            for arg in inner.split(',').map(last).filter(|arg| !arg.is_empty()) {
+                match arg.as_str() {
+                    "Standard" => url_safe = false,
+                    "UrlSafe" => url_safe = true,
+                    "Padded" => padded = true,
+                    "Unpadded" => padded = false,
+                    _ => return None,
+                }
+            }
+
+            as_bytes(ty, Bytes::Base64 { url_safe, padded })
+        }
+        _ => None,
+    }
+}
+
+// what a path means, `u8` or `Vec<T>`, or a type of the user's
+// `std::vec::Vec<T>` is as good as `Vec<T>`
+pub(crate) fn path_type(mut path: TypePath<'_>, span: Span) -> Result<Type<'_>, Diagnostic> {
+    if let (Some(int), 0) = (Int::parse(path.name()), path.args.len()) {
+        return Ok(Type::Int(int));
+    }
+
+    let ty = match (path.name(), path.args.len()) {
+        ("bool", 0) => Type::Bool,
+        ("f32", 0) => Type::Float(Float::F32),
+        ("f64", 0) => Type::Float(Float::F64),
+        ("char", 0) => Type::Char,
+        ("String", 0) => Type::String,
+        // a bare one could be the user's own, see `is_bare_bytes`
+        ("Bytes" | "BytesMut" | "ByteBuf", 0)
+            if matches!(
+                path.segments[..],
+                ["bytes", "Bytes" | "BytesMut"] | ["serde_bytes", "ByteBuf"]
+            ) =>
+        {
+            Type::Bytes(Bytes::Native)
+        }
+        // a bare `Value` could be anything, undeclared it gets the built-in mapping
+        ("Value", 0) if path.segments == ["serde_json", "Value"] => Type::Json,
+        ("Vec" | "VecDeque" | "LinkedList" | "BinaryHeap", 1) => {
+            Type::Vec(Box::new(path.args.remove(0)))
+        }
+        ("HashSet" | "BTreeSet" | "IndexSet", 1) => Type::Set(Box::new(path.args.remove(0))),
+        ("HashMap" | "BTreeMap" | "IndexMap", 2) => {
+            let value = path.args.remove(1);
+            Type::Map(Box::new(path.args.remove(0)), Box::new(value))
+        }
+        ("Option", 1) => Type::Option(Box::new(path.args.remove(0))),
+        // serde serializes a pointer as what it points to, which is how recursive types are written
+        ("Box" | "Rc" | "Arc", 1) => path.args.remove(0),
+        (
+            name @ ("Vec" | "VecDeque" | "LinkedList" | "BinaryHeap" | "HashSet" | "BTreeSet"
+            | "IndexSet" | "Option" | "Box" | "Rc" | "Arc"),
+            _,
+        ) => {
+            return Err(
+                Diagnostic::error(format!("`{name}` takes one type argument"))
+                    .with_span(span)
+                    .with_label("here"),
+            )
+        }
+        _ => Type::User(path),
+    };
+
+    Ok(ty)
+}
+
+// a field's type, after `serde_bytes` and `serde_as` have changed how byte buffers are serialized
+// `ty_span` is where the type is
+pub(crate) fn field_type<'a>(
+    mut ty: Type<'a>,
+    ty_span: Span,
+    attrs: &[SerdeAttribute<'a>],
+    serde_as: Option<(&'a str, Span)>,
+) -> Result<Type<'a>, Diagnostic> {
+    let with_bytes = attrs
+        .iter()
+        .any(|attr| matches!(attr, SerdeAttribute::With("serde_bytes" | "::serde_bytes")));
+
+    if with_bytes {
+        ty = as_bytes(ty, Bytes::Native).ok_or_else(|| {
+            Diagnostic::error("`serde_bytes` only works on byte buffers")
+                .with_span(ty_span)
+                .with_label("expected `Vec<u8>`, or an `Option` of one")
+        })?;
+    }
+
+    if let Some((as_, as_span)) = serde_as {
+        ty = serde_as_type(as_, ty).ok_or_else(|| {
+            Diagnostic::error(format!("unsupported `serde_as` conversion `{as_}`"))
+                .with_span(as_span)
+                .with_label("not supported")
+                .with_note(
+                    "`Base64` and `Bytes` on byte buffers are supported, in `Option`s and `Vec`s",
+                )
+        })?;
+    }
+
+    Ok(ty)
 }
 
 pub struct Parser<'a> {
     pub tokens: &'a [LexicalToken<'a>],
     pub types: Vec<DeclaredType<'a>>,
     pub cursor: usize,
+
+    // the name of the file being parsed
+    file: &'a str,
+
+    // enabled for `#[cfg(feature = "...")]`
+    features: &'a [String],
+
+    // problems we recovered from
+    errors: Vec<Diagnostic>,
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(tokens: &'a [LexicalToken<'a>]) -> Self {
+    pub fn new(tokens: &'a [LexicalToken<'a>], file: &'a str) -> Self {
         Parser {
             tokens,
             types: Vec::new(),
             cursor: 0,
+            file,
+            features: &[],
+            errors: Vec::new(),
         }
     }
 
-    pub fn parse(mut self) -> Vec<DeclaredType<'a>> {
+    /// The cargo features to consider enabled when evaluating `#[cfg]` and `#[cfg_attr]`
+    pub fn with_features(mut self, features: &'a [String]) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// Parses every declaration, carrying on after errors so they can all be reported at once
+    pub fn parse(mut self) -> Result<Vec<DeclaredType<'a>>, Vec<Diagnostic>> {
         while self.cursor < self.tokens.len() {
-            self.parse_decl();
+            if let Err(err) = self.parse_decl() {
+                self.errors.push(err);
+                self.recover_decl();
+            }
         }
 
-        self.types
+        if self.errors.is_empty() {
+            Ok(self.types)
+        } else {
+            Err(self.errors)
+        }
     }
 
-    fn parse_decl(&mut self) {
-        if let TokenType::Hash = self.tokens[self.cursor].token {
+    // skip tokens until one matching `stop` that isn't inside brackets
+    // the matching token isn't eaten
+    fn skip_to(&mut self, stop: fn(&TokenType) -> bool) {
+        use TokenType::*;
+
+        let mut depth = 0usize;
+
+        while let Some(lex) = self.tokens.get(self.cursor) {
+            if depth == 0 && stop(&lex.token) {
+                break;
+            }
+
+            match lex.token {
+                LParen | LBracket | LBrace => depth += 1,
+                RParen | RBracket | RBrace => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+
+            self.cursor += 1;
+        }
+    }
+
+    // after a broken declaration, resume at whatever looks like the start of the next one
+    fn recover_decl(&mut self) {
+        use TokenType::*;
+
+        // always make progress, the error may be on the token we'd stop at
+        if self
+            .tokens
+            .get(self.cursor)
+            .is_some_and(|lex| lex.token != LBrace)
+        {
             self.cursor += 1;
+        }
+
+        self.skip_to(|token| matches!(token, Struct | Enum | Hash | Pub));
+    }
+
+    // after a broken field or variant, record `err` and resume at the next one
+    // errors at the end of the input can't be recovered from
+    fn recover_member(&mut self, err: Diagnostic) -> Result<(), Diagnostic> {
+        use TokenType::*;
+
+        if self.cursor >= self.tokens.len() {
+            return Err(err);
+        }
+
+        self.errors.push(err);
+        self.skip_to(|token| matches!(token, Comma | RBrace));
 
-            while !matches!(
-                self.tokens[self.cursor].token,
-                TokenType::Struct | TokenType::Enum
-            ) {
+        if self.cursor >= self.tokens.len() {
+            return Err(self.unexpected("`}`"));
+        }
+
+        self.eat(&Comma);
+
+        Ok(())
+    }
+
+    // the current token, or an error at the end of the input
+    fn current(&self) -> Result<&'a LexicalToken<'a>, Diagnostic> {
+        self.tokens.get(self.cursor).ok_or_else(|| {
+            let span = self
+                .tokens
+                .last()
+                .map(|last| Span {
+                    start: last.span.end,
+                    end: last.span.end + 1,
+                    line: last.span.line,
+                    column: last.span.column + (last.span.end - last.span.start),
+                })
+                .unwrap_or_default();
+
+            Diagnostic::error("unexpected end of input").with_span(span)
+        })
+    }
+
+    fn unexpected(&self, expected: &str) -> Diagnostic {
+        match self.current() {
+            Ok(lex) => Diagnostic::error(format!("unexpected token `{}`", lex.token))
+                .with_span(lex.span)
+                .with_label(format!("expected {expected}")),
+            Err(err) => err.with_label(format!("expected {expected}")),
+        }
+    }
+
+    fn parse_decl(&mut self) -> Result<(), Diagnostic> {
+        let attrs = self.parse_attributes()?;
+
+        self.eat_visibility()?;
+
+        let lex = self.current()?;
+
+        match &lex.token {
+            TokenType::Enum => {
+                self.cursor += 1;
+                self.parse_enum(attrs)
+            }
+            TokenType::Struct => {
                 self.cursor += 1;
+                self.parse_struct(attrs)
+            }
+            // like `#[cfg(test)] mod tests { ... }`, it doesn't matter what it is
+            _ if attrs.disabled => {
+                self.recover_decl();
+                Ok(())
             }
+            _ => Err(self
+                .unexpected("`struct` or `enum`")
+                .with_note("only struct and enum declarations are supported")),
         }
+    }
 
-        let lex = &self.tokens[self.cursor];
-        self.cursor += 1;
+    // `pub`, `pub(crate)`, `pub(super)`, ...
+    // we don't care about it
+    fn eat_visibility(&mut self) -> Result<(), Diagnostic> {
+        use TokenType::*;
 
-        match &lex.token {
-            TokenType::Enum => self.parse_enum(),
-            TokenType::Struct => self.parse_struct(),
-            ty => panic!("unexpected token {:?}; expected struct or enum", ty),
+        if self.eat(&Pub) && self.eat(&LParen) {
+            while !self.eat(&RParen) {
+                self.current()?;
+                self.cursor += 1;
+            }
         }
+
+        Ok(())
     }
 
-    fn get_ident(&mut self) -> &'a str {
-        match &self.tokens[self.cursor].token {
-            TokenType::Identifier(name) => name,
-            ty => panic!("unexpected token {:?}; expected identifier", ty),
+    fn get_ident(&mut self) -> Result<&'a str, Diagnostic> {
+        match &self.current()?.token {
+            TokenType::Identifier(name) => Ok(name),
+            _ => Err(self.unexpected("an identifier")),
         }
     }
 
-    fn eat_ident(&mut self) -> &'a str {
-        let name = self.get_ident();
+    fn eat_ident(&mut self) -> Result<&'a str, Diagnostic> {
+        let name = self.get_ident()?;
         self.cursor += 1;
-        name
+        Ok(name)
     }
 
-    fn eat_string(&mut self) -> &'a str {
-        let name = self.get_string();
+    fn eat_string(&mut self) -> Result<&'a str, Diagnostic> {
+        let name = self.get_string()?;
         self.cursor += 1;
-        name
+        Ok(name)
     }
 
-    fn get_string(&mut self) -> &'a str {
-        match &self.tokens[self.cursor].token {
-            TokenType::String(name) => name,
-            ty => panic!("unexpected token {:?}; expected string", ty),
+    fn get_string(&mut self) -> Result<&'a str, Diagnostic> {
+        match &self.current()?.token {
+            TokenType::String(name) => Ok(name),
+            _ => Err(self.unexpected("a string")),
         }
     }
 
-    fn parse_variant(&mut self) -> EnumVariant<'a> {
-        let attrs = self.parse_serde_attributes();
-        let name = self.eat_ident();
+    // `None` when it's disabled by a `#[cfg]`
+    fn parse_variant(&mut self) -> Result<Option<EnumVariant<'a>>, Diagnostic> {
+        let Attributes {
+            serde: attrs,
+            default,
+            disabled,
+            ..
+        } = self.parse_attributes()?;
+        let name = self.eat_ident()?;
 
         let inner = if self.eat(&TokenType::LParen) {
-            let types = self.parse_tuple_inner();
+            let types = self.parse_tuple_inner()?;
 
             EnumVariantInner::Tuple(types)
         } else if self.eat(&TokenType::LBrace) {
-            let fields = self.parse_struct_fields();
+            let fields = self.parse_struct_fields()?;
 
             EnumVariantInner::Struct(Struct { fields })
         } else {
             EnumVariantInner::Unit
         };
 
-        EnumVariant { attrs, name, inner }
+        if disabled {
+            return Ok(None);
+        }
+
+        Ok(Some(EnumVariant {
+            attrs,
+            name,
+            inner,
+            default,
+        }))
     }
 
-    fn parse_enum(&mut self) {
-        let name = self.eat_ident();
+    fn parse_enum(&mut self, attrs: Attributes<'a>) -> Result<(), Diagnostic> {
+        let span = self.current()?.span;
+        let name = self.eat_ident()?;
 
         let mut fields = Vec::new();
 
-        self.must_eat(TokenType::LBrace);
+        self.must_eat(TokenType::LBrace)?;
 
         loop {
             use TokenType::*;
-            let f = self.parse_variant();
-
-            fields.push(f);
 
             if self.eat(&RBrace) {
                 break;
             }
 
-            if self.eat(&Comma) {
-                if self.eat(&RBrace) {
-                    break;
+            match self.parse_variant() {
+                Ok(f) => fields.extend(f),
+                Err(err) => {
+                    self.recover_member(err)?;
+                    continue;
                 }
+            }
 
-                // continue
-            } else {
-                panic!("unexpected token {:?}", self.tokens[self.cursor].token);
+            if self.eat(&RBrace) {
+                break;
+            }
+
+            if !self.eat(&Comma) {
+                let err = self.unexpected("`,` or `}`");
+                self.recover_member(err)?;
             }
         }
 
         let en = DeclaredType {
+            attrs: attrs.serde,
             name,
             inner: InnerType::Enum(Enum { variants: fields }),
+            derives: attrs.derives,
+            span,
+            file: self.file,
         };
 
-        self.types.push(en);
+        if !attrs.disabled {
+            self.types.push(en);
+        }
+
+        Ok(())
     }
 
     // eat { before calling
-    fn parse_struct_fields(&mut self) -> Vec<StructField<'a>> {
+    fn parse_struct_fields(&mut self) -> Result<Vec<StructField<'a>>, Diagnostic> {
         let mut fields = Vec::new();
 
         loop {
             use TokenType::*;
-            let f = self.parse_field();
 
-            fields.push(f);
+            if self.eat(&RBrace) {
+                break;
+            }
 
-            if self.eat(&Comma) {
-                if self.eat(&RBrace) {
-                    break;
+            match self.parse_field() {
+                Ok(f) => fields.extend(f),
+                Err(err) => {
+                    self.recover_member(err)?;
+                    continue;
                 }
+            }
 
-                // continue
-            } else if self.eat(&RBrace) {
+            if self.eat(&RBrace) {
                 break;
-            } else {
-                panic!("unexpected token {:?}", self.tokens[self.cursor].token);
+            }
+
+            if !self.eat(&Comma) {
+                let err = self.unexpected("`,` or `}`");
+                self.recover_member(err)?;
             }
         }
 
-        fields
+        Ok(fields)
     }
 
-    fn parse_struct(&mut self) {
-        let name = self.eat_ident();
+    fn parse_struct(&mut self, attrs: Attributes<'a>) -> Result<(), Diagnostic> {
+        let span = self.current()?.span;
+        let name = self.eat_ident()?;
 
-        self.must_eat(TokenType::LBrace);
+        self.must_eat(TokenType::LBrace)?;
 
-        let fields = self.parse_struct_fields();
+        let fields = self.parse_struct_fields()?;
 
         let struc = DeclaredType {
+            attrs: attrs.serde,
             name,
             inner: InnerType::Struct(Struct { fields }),
+            derives: attrs.derives,
+            span,
+            file: self.file,
         };
 
-        self.types.push(struc);
+        if !attrs.disabled {
+            self.types.push(struc);
+        }
+
+        Ok(())
     }
 
-    fn parse_field(&mut self) -> StructField<'a> {
+    // `None` when it's disabled by a `#[cfg]`
+    fn parse_field(&mut self) -> Result<Option<StructField<'a>>, Diagnostic> {
         use TokenType::*;
 
-        let attrs = self.parse_serde_attributes();
+        let Attributes {
+            serde: attrs,
+            disabled,
+            serde_as,
+            ..
+        } = self.parse_attributes()?;
+
+        self.eat_visibility()?;
+
+        let span = self.current()?.span;
+        let name = self.eat_ident()?;
 
-        // may or may not be present
-        // we don't care about it
-        self.eat(&Pub);
+        self.must_eat(Colon)?;
 
-        let name = self.eat_ident();
+        let ty_span = self.current()?.span;
+        let ty = self.parse_type()?;
 
-        self.must_eat(Colon);
+        let ty = field_type(ty, ty_span, &attrs, serde_as)?;
 
-        let ty = self.parse_type();
+        if disabled {
+            return Ok(None);
+        }
 
-        StructField { attrs, name, ty }
+        Ok(Some(StructField {
+            attrs,
+            name,
+            ty,
+            span,
+        }))
     }
 
     // eat ( before calling
-    fn parse_tuple(&mut self) -> Type<'a> {
-        let types = self.parse_tuple_inner();
+    fn parse_tuple(&mut self) -> Result<Type<'a>, Diagnostic> {
+        let types = self.parse_tuple_inner()?;
 
-        Type::Tuple(types)
+        Ok(Type::Tuple(types))
     }
 
     // eat ( before calling
-    fn parse_tuple_inner(&mut self) -> Vec<Type<'a>> {
+    fn parse_tuple_inner(&mut self) -> Result<Vec<Type<'a>>, Diagnostic> {
         use TokenType::*;
 
         let mut types = Vec::new();
 
         loop {
-            types.push(self.parse_type());
+            types.push(self.parse_type()?);
 
             if self.eat(&RParen) {
                 break;
             }
 
-            self.must_eat(Comma);
+            self.must_eat(Comma)?;
+
+            // trailing comma, as in `(u32,)`
+            if self.eat(&RParen) {
+                break;
+            }
         }
 
-        types
+        Ok(types)
     }
 
-    fn parse_type(&mut self) -> Type<'a> {
+    fn parse_type(&mut self) -> Result<Type<'a>, Diagnostic> {
         use TokenType::*;
 
         if self.eat(&LParen) {
             if self.eat(&RParen) {
-                return Type::Unit;
+                return Ok(Type::Unit);
             }
 
             self.parse_tuple()
         } else {
-            // all other types start with an ident
-            let ident = self.eat_ident();
-
-            match ident {
-                "bool" => return Type::Bool,
-                "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "u64" => return Type::Int,
-                "f32" | "f64" => return Type::Float,
-                "String" => return Type::String,
-                "Vec" => {
-                    self.must_eat(Langle);
-                    let ty = Box::new(self.parse_type());
-                    self.must_eat(Rangle);
-                    return Type::Vec(ty);
-                }
-                "Array" => {
-                    self.must_eat(LBracket);
-                    let ty = Box::new(self.parse_type());
-                    self.must_eat(RBracket);
-                    return Type::Array(ty);
+            // all other types are paths
+            let span = self.current()?.span;
+
+            if self.eat(&Identifier("Array")) {
+                self.must_eat(LBracket)?;
+                let ty = Box::new(self.parse_type()?);
+                self.must_eat(RBracket)?;
+
+                return Ok(Type::Array(ty));
+            }
+
+            let path = self.parse_type_path()?;
+
+            path_type(path, span)
+        }
+    }
+
+    // `a::b::C<T, U>`
+    fn parse_type_path(&mut self) -> Result<TypePath<'a>, Diagnostic> {
+        use TokenType::*;
+
+        let mut segments = Vec::new();
+
+        // a leading `::`
+        self.eat_path_separator()?;
+
+        loop {
+            segments.push(self.eat_ident()?);
+
+            if !self.eat_path_separator()? {
+                break;
+            }
+        }
+
+        let mut args = Vec::new();
+
+        if self.eat(&Langle) {
+            loop {
+                args.push(self.parse_type()?);
+
+                if self.eat(&Rangle) {
+                    break;
                 }
-                "Option" => {
-                    self.must_eat(Langle);
-                    let ty = Box::new(self.parse_type());
-                    self.must_eat(Rangle);
-                    return Type::Option(ty);
+
+                self.must_eat(Comma)?;
+
+                if self.eat(&Rangle) {
+                    break;
                 }
-                _ => return Type::User(ident),
             }
         }
+
+        Ok(TypePath { segments, args })
     }
 
-    fn peek(&self, tok: TokenType<'a>) -> bool {
-        self.tokens[self.cursor].token == tok
+    // `::`, which is lexed as two colons
+    fn eat_path_separator(&mut self) -> Result<bool, Diagnostic> {
+        if !self.eat(&TokenType::Colon) {
+            return Ok(false);
+        }
+
+        self.must_eat(TokenType::Colon)?;
+
+        Ok(true)
     }
 
     fn eat(&mut self, tok: &TokenType<'a>) -> bool {
-        if self.tokens[self.cursor].token == *tok {
-            self.cursor += 1;
-            true
+        match self.tokens.get(self.cursor) {
+            Some(lex) if lex.token == *tok => {
+                self.cursor += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn must_eat(&mut self, tok: TokenType<'a>) -> Result<(), Diagnostic> {
+        if self.eat(&tok) {
+            Ok(())
         } else {
-            false
+            Err(self.unexpected(&format!("`{tok}`")))
         }
     }
 
-    fn must_eat(&mut self, tok: TokenType<'a>) {
-        if !self.eat(&tok) {
-            panic!(
-                "unexpected token {:?}; expected {:?}",
-                self.tokens[self.cursor].token, tok
-            );
+    fn parse_attributes(&mut self) -> Result<Attributes<'a>, Diagnostic> {
+        use TokenType::*;
+
+        let mut attrs = Attributes::default();
+
+        while self.eat(&Hash) {
+            self.must_eat(LBracket)?;
+            self.parse_attribute(&mut attrs)?;
+            self.must_eat(RBracket)?;
         }
+
+        Ok(attrs)
     }
 
-    fn parse_serde_attributes(&mut self) -> Vec<SerdeAttribute<'a>> {
+    // what's inside `#[...]`, or one of the attributes in a `cfg_attr`
+    fn parse_attribute(&mut self, attrs: &mut Attributes<'a>) -> Result<(), Diagnostic> {
         use TokenType::*;
 
-        let mut attrs = Vec::new();
+        match self.eat_ident()? {
+            "serde" => self.parse_serde_attributes(&mut attrs.serde)?,
+            "derive" => self.parse_derives(&mut attrs.derives)?,
+            "default" => attrs.default = true,
+            // a bare `#[serde_as]` on the container only tells the macro to look at the fields
+            "serde_as" if self.eat(&LParen) => {
+                loop {
+                    let span = self.current()?.span;
+
+                    match self.eat_ident()? {
+                        "as" => {
+                            self.must_eat(Equals)?;
+                            let span = self.current()?.span;
+                            attrs.serde_as = Some((self.eat_string()?, span));
+                        }
+                        option => {
+                            self.errors.push(
+                                Diagnostic::error(format!(
+                                    "unsupported `serde_as` option `{option}`"
+                                ))
+                                .with_span(span)
+                                .with_label("only `as` is supported"),
+                            );
+
+                            self.skip_to(|token| matches!(token, Comma | RParen));
+                        }
+                    }
 
-        if self.eat(&Hash) {
-            self.must_eat(LBracket);
-            self.must_eat(Identifier("serde"));
-            self.must_eat(LParen);
+                    if !self.eat(&Comma) || self.current()?.token == RParen {
+                        break;
+                    }
+                }
 
-            loop {
-                use SerdeAttribute::*;
-
-                let name = self.eat_ident();
-
-                if name == "skip" {
-                    attrs.push(Skip);
-                } else if name == "skip_serializing" {
-                    attrs.push(SkipSerializing);
-                } else if name == "skip_deserializing" {
-                    attrs.push(SkipDeserializing);
-                } else if name == "skip_serializing_if" {
-                    self.must_eat(Equals);
-                    let cond = self.eat_string();
-                    attrs.push(SkipSerializingIf(cond));
-                } else if name == "skip_deserializing_if" {
-                    self.must_eat(Equals);
-                    let cond = self.eat_string();
-                    attrs.push(SkipDeserializingIf(cond));
-                } else if name == "serialize_with" {
-                    self.must_eat(Equals);
-                    let cond = self.eat_string();
-                    attrs.push(SerializeWith(cond));
-                } else if name == "deserialize_with" {
-                    self.must_eat(Equals);
-                    let cond = &self.eat_string();
-                    attrs.push(DeserializeWith(cond));
-                } else if name == "rename" {
-                    self.must_eat(Equals);
-                    let cond = self.eat_string();
-                    attrs.push(Rename(cond));
-                } else if name == "default" {
-                    if self.eat(&Equals) {
-                        let cond = self.eat_string();
-
-                        attrs.push(Default(Some(cond)));
-                    } else {
-                        attrs.push(Default(None));
+                self.must_eat(RParen)?;
+            }
+            "cfg" => {
+                self.must_eat(LParen)?;
+
+                if !self.parse_cfg()? {
+                    attrs.disabled = true;
+                }
+
+                self.must_eat(RParen)?;
+            }
+            "cfg_attr" => {
+                self.must_eat(LParen)?;
+
+                if self.parse_cfg()? {
+                    // a trailing comma is allowed
+                    while self.eat(&Comma) && self.current()?.token != RParen {
+                        self.parse_attribute(attrs)?;
                     }
                 } else {
-                    panic!("unexpected serde attribute {:?}", name);
+                    self.skip_to(|token| matches!(token, RParen));
                 }
 
-                if self.eat(&RParen) {
-                    self.must_eat(RBracket);
+                self.must_eat(RParen)?;
+            }
+            _ => {
+                // some attribute we don't care about
+                self.skip_to(|token| matches!(token, Comma | RParen | RBracket));
+            }
+        }
 
-                    break;
+        Ok(())
+    }
+
+    // a `cfg` predicate, like `all(feature = "a", not(feature = "b"))`
+    // anything other than features, like `test` or `unix`, is false as we aren't compiling for anything
+    fn parse_cfg(&mut self) -> Result<bool, Diagnostic> {
+        use TokenType::*;
+
+        let span = self.current()?.span;
+        let name = self.eat_ident()?;
+
+        if self.eat(&Equals) {
+            let value = self.eat_string()?;
+
+            return Ok(name == "feature" && self.features.iter().any(|feature| feature == value));
+        }
+
+        if !matches!(name, "all" | "any" | "not") {
+            return Ok(false);
+        }
+
+        self.must_eat(LParen)?;
+
+        let mut values = Vec::new();
+
+        while !self.eat(&RParen) {
+            values.push(self.parse_cfg()?);
+
+            if !self.eat(&Comma) {
+                self.must_eat(RParen)?;
+                break;
+            }
+        }
+
+        match (name, &values[..]) {
+            ("all", _) => Ok(values.iter().all(|value| *value)),
+            ("any", _) => Ok(values.iter().any(|value| *value)),
+            ("not", [value]) => Ok(!value),
+            _ => Err(Diagnostic::error("`not` takes exactly one predicate")
+                .with_span(span)
+                .with_label("here")),
+        }
+    }
+
+    // `derive` was just eaten
+    fn parse_derives(&mut self, derives: &mut Vec<&'a str>) -> Result<(), Diagnostic> {
+        use TokenType::*;
+
+        self.must_eat(LParen)?;
+
+        while !self.eat(&RParen) {
+            // `serde::Serialize` -> `Serialize`
+            let mut name = self.eat_ident()?;
+
+            while self.eat(&Colon) {
+                self.must_eat(Colon)?;
+                name = self.eat_ident()?;
+            }
+
+            derives.push(name);
+
+            if !self.eat(&Comma) {
+                self.must_eat(RParen)?;
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    // `serde` was just eaten
+    fn parse_serde_attributes(
+        &mut self,
+        attrs: &mut Vec<SerdeAttribute<'a>>,
+    ) -> Result<(), Diagnostic> {
+        use TokenType::*;
+
+        self.must_eat(LParen)?;
+
+        loop {
+            use SerdeAttribute::*;
+
+            let span = self.current()?.span;
+            let name = self.eat_ident()?;
+
+            if name == "skip" {
+                attrs.push(Skip);
+            } else if name == "skip_serializing" {
+                attrs.push(SkipSerializing);
+            } else if name == "skip_deserializing" {
+                attrs.push(SkipDeserializing);
+            } else if name == "skip_serializing_if" {
+                self.must_eat(Equals)?;
+                let cond = self.eat_string()?;
+                attrs.push(SkipSerializingIf(cond));
+            } else if name == "skip_deserializing_if" {
+                self.must_eat(Equals)?;
+                let cond = self.eat_string()?;
+                attrs.push(SkipDeserializingIf(cond));
+            } else if name == "serialize_with" {
+                self.must_eat(Equals)?;
+                let cond = self.eat_string()?;
+                attrs.push(SerializeWith(cond));
+            } else if name == "deserialize_with" {
+                self.must_eat(Equals)?;
+                let cond = self.eat_string()?;
+                attrs.push(DeserializeWith(cond));
+            } else if name == "with" {
+                self.must_eat(Equals)?;
+                let module = self.eat_string()?;
+                attrs.push(With(module));
+            } else if name == "rename" {
+                self.must_eat(Equals)?;
+                let cond = self.eat_string()?;
+                attrs.push(Rename(cond));
+            } else if name == "default" {
+                if self.eat(&Equals) {
+                    let cond = self.eat_string()?;
+
+                    attrs.push(Default(Some(cond)));
+                } else {
+                    attrs.push(Default(None));
                 }
+            } else {
+                self.errors.push(
+                    Diagnostic::error(format!("unsupported serde attribute `{name}`"))
+                        .with_span(span)
+                        .with_label("not supported by marshal"),
+                );
+
+                // carry on with the next one
+                self.skip_to(|token| matches!(token, Comma | RParen));
+            }
 
-                self.must_eat(Comma);
+            if self.eat(&RParen) {
+                break;
             }
+
+            self.must_eat(Comma)?;
         }
 
-        attrs
+        Ok(())
     }
 }
@@ -0,0 +1,346 @@
+// Types from a `serde_reflection::Registry`, traced from the real types rather than read from their source
+//
+// ```rust
+// let mut tracer = Tracer::new(TracerConfig::default());
+// tracer.trace_simple_type::<User>()?;
+// std::fs::write("registry.yaml", serde_yaml::to_string(&tracer.registry()?)?)?;
+// ```
+//
+// the registry is what serde does, whether the types come from macros or have hand written impls,
+// so renames and skips have already been applied, and there are no attributes left to read
+//
+// newtype, tuple and unit structs are written as what they hold, a sequence of it, or `null`,
+// so they aren't declared, but lowered in place, as though a field were written with the type they hold
+//
+// it doesn't say where anything is, so a name's span is where it's first used as a key,
+// looking for members after their container, which is right for anything the tracer writes
+
+use serde_reflection::{ContainerFormat, Format, Named, Registry, VariantFormat};
+
+use crate::diagnostic::Diagnostic;
+use crate::lexer::Span;
+use crate::parser::{
+    Bytes, DeclaredType, Enum, EnumVariant, EnumVariantInner, Float, InnerType, Int, Struct,
+    StructField, Type, TypePath,
+};
+use crate::Source;
+
+/// Reads a registry, as JSON if the file is `.json`, otherwise as YAML
+pub fn read(source: &Source) -> Result<Registry, Diagnostic> {
+    let json = source
+        .path
+        .as_ref()
+        .is_some_and(|path| path.extension().is_some_and(|ext| ext == "json"));
+
+    let (message, span) = if json {
+        match serde_json::from_str(&source.text) {
+            Ok(registry) => return Ok(registry),
+            Err(err) => (
+                err.to_string(),
+                Some(Span::at(&source.text, err.line(), err.column())),
+            ),
+        }
+    } else {
+        match serde_yaml::from_str(&source.text) {
+            Ok(value) => {
+                match serde_json::to_value(untag(value)).and_then(serde_json::from_value) {
+                    Ok(registry) => return Ok(registry),
+                    Err(err) => (err.to_string(), None),
+                }
+            }
+            Err(err) => {
+                let span = err
+                    .location()
+                    .map(|at| Span::at(&source.text, at.line(), at.column()));
+
+                (err.to_string(), span)
+            }
+        }
+    };
+
+    let mut err = Diagnostic::error(format!("cannot read the registry: {message}"))
+        .with_file(&source.name)
+        .with_note(
+            "expected a `serde_reflection::Registry`, serialized with serde_yaml or serde_json",
+        );
+
+    if let Some(span) = span {
+        err = err.with_span(span);
+    }
+
+    Err(err)
+}
+
+// serde_yaml 0.8, which is what can write a registry, writes enums as maps with one key,
+// which 0.9 only reads as tags, `!STRUCT`, so tags are made maps and the registry is read from those
+fn untag(value: serde_yaml::Value) -> serde_yaml::Value {
+    use serde_yaml::{Mapping, Value};
+
+    match value {
+        Value::Tagged(tagged) => {
+            let tag = tagged.tag.to_string();
+            let key = Value::String(tag.trim_start_matches('!').to_string());
+
+            Value::Mapping(Mapping::from_iter([(key, untag(tagged.value))]))
+        }
+        Value::Sequence(items) => Value::Sequence(items.into_iter().map(untag).collect()),
+        Value::Mapping(map) => Value::Mapping(
+            map.into_iter()
+                .map(|(key, value)| (key, untag(value)))
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+pub struct RegistryParser<'a> {
+    registry: &'a Registry,
+
+    // the registry's text, to find names in
+    text: &'a str,
+
+    // the name of the file being parsed
+    file: &'a str,
+
+    types: Vec<DeclaredType<'a>>,
+
+    // problems we carried on after
+    errors: Vec<Diagnostic>,
+}
+
+impl<'a> RegistryParser<'a> {
+    /// `registry` is `source` [`read`]
+    pub fn new(registry: &'a Registry, source: &'a Source) -> Self {
+        RegistryParser {
+            registry,
+            text: &source.text,
+            file: &source.name,
+            types: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Every struct and enum in the registry, in name order, newtype, tuple and unit structs are lowered where they're used
+    pub fn parse(mut self) -> Result<Vec<DeclaredType<'a>>, Vec<Diagnostic>> {
+        for (name, container) in self.registry {
+            if let Err(err) = self.container(name, container) {
+                self.errors.push(err.with_file(self.file));
+            }
+        }
+
+        if self.errors.is_empty() {
+            Ok(self.types)
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    fn container(
+        &mut self,
+        name: &'a str,
+        container: &'a ContainerFormat,
+    ) -> Result<(), Diagnostic> {
+        let span = self.key(name, 0);
+
+        let inner = match container {
+            ContainerFormat::Struct(fields) => InnerType::Struct(self.fields(fields, span)?),
+            ContainerFormat::Enum(variants) => {
+                let mut lowered = Vec::new();
+
+                // keyed by their indexes, which are in declaration order
+                for Named { name, value } in variants.values() {
+                    let variant_span = self.key(name, span.end);
+
+                    let inner = match value {
+                        VariantFormat::Unit => EnumVariantInner::Unit,
+                        VariantFormat::NewType(format) => {
+                            EnumVariantInner::Tuple(vec![self.format(format, variant_span)?])
+                        }
+                        VariantFormat::Tuple(formats) => EnumVariantInner::Tuple(
+                            formats
+                                .iter()
+                                .map(|format| self.format(format, variant_span))
+                                .collect::<Result<_, _>>()?,
+                        ),
+                        VariantFormat::Struct(fields) => {
+                            EnumVariantInner::Struct(self.fields(fields, variant_span)?)
+                        }
+                        VariantFormat::Variable(_) => return Err(incomplete(variant_span)),
+                    };
+
+                    lowered.push(EnumVariant {
+                        attrs: Vec::new(),
+                        name,
+                        inner,
+                        default: false,
+                    });
+                }
+
+                InnerType::Enum(Enum { variants: lowered })
+            }
+            ContainerFormat::UnitStruct
+            | ContainerFormat::NewTypeStruct(_)
+            | ContainerFormat::TupleStruct(_) => return Ok(()),
+        };
+
+        self.types.push(DeclaredType {
+            attrs: Vec::new(),
+            name,
+            inner,
+            // the tracer needs both
+            derives: vec!["Serialize", "Deserialize"],
+            span,
+            file: self.file,
+        });
+
+        Ok(())
+    }
+
+    // `span` is their container's
+    fn fields(&self, fields: &'a [Named<Format>], span: Span) -> Result<Struct<'a>, Diagnostic> {
+        let mut lowered = Vec::new();
+
+        for Named { name, value } in fields {
+            let span = self.key(name, span.end);
+
+            lowered.push(StructField {
+                attrs: Vec::new(),
+                name,
+                ty: self.format(value, span)?,
+                span,
+            });
+        }
+
+        Ok(Struct { fields: lowered })
+    }
+
+    fn format(&self, format: &'a Format, span: Span) -> Result<Type<'a>, Diagnostic> {
+        self.lower(format, span, &mut Vec::new())
+    }
+
+    // `inlined` are the structs being lowered in place, one of which holding itself would never end
+    fn lower(
+        &self,
+        format: &'a Format,
+        span: Span,
+        inlined: &mut Vec<&'a str>,
+    ) -> Result<Type<'a>, Diagnostic> {
+        let ty = match format {
+            Format::TypeName(name) => {
+                let (formats, newtype) = match self.registry.get(name) {
+                    Some(ContainerFormat::UnitStruct) => return Ok(Type::Unit),
+                    Some(ContainerFormat::NewTypeStruct(format)) => {
+                        (std::slice::from_ref(&**format), true)
+                    }
+                    Some(ContainerFormat::TupleStruct(formats)) => (formats.as_slice(), false),
+                    _ => {
+                        return Ok(Type::User(TypePath {
+                            segments: vec![name],
+                            args: Vec::new(),
+                        }))
+                    }
+                };
+
+                if inlined.contains(&name.as_str()) {
+                    return Err(Diagnostic::error(format!("`{name}` holds itself"))
+                        .with_span(span)
+                        .with_label("used here")
+                        .with_note(
+                            "newtype and tuple structs are lowered to what they hold, which never ends for this one",
+                        )
+                        .with_note("put it in a struct with named fields"));
+                }
+
+                inlined.push(name);
+
+                let mut types = formats
+                    .iter()
+                    .map(|format| self.lower(format, span, inlined))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                inlined.pop();
+
+                if newtype {
+                    types.remove(0)
+                } else if types.is_empty() {
+                    Type::Unit
+                } else {
+                    Type::Tuple(types)
+                }
+            }
+            Format::Unit => Type::Unit,
+            Format::Bool => Type::Bool,
+            Format::I8 => Type::Int(Int::I8),
+            Format::I16 => Type::Int(Int::I16),
+            Format::I32 => Type::Int(Int::I32),
+            Format::I64 => Type::Int(Int::I64),
+            Format::I128 => Type::Int(Int::I128),
+            Format::U8 => Type::Int(Int::U8),
+            Format::U16 => Type::Int(Int::U16),
+            Format::U32 => Type::Int(Int::U32),
+            Format::U64 => Type::Int(Int::U64),
+            Format::U128 => Type::Int(Int::U128),
+            Format::F32 => Type::Float(Float::F32),
+            Format::F64 => Type::Float(Float::F64),
+            Format::Char => Type::Char,
+            Format::Str => Type::String,
+            Format::Bytes => Type::Bytes(Bytes::Native),
+            Format::Option(format) => Type::Option(Box::new(self.lower(format, span, inlined)?)),
+            Format::Seq(format) => Type::Vec(Box::new(self.lower(format, span, inlined)?)),
+            Format::Tuple(formats) if formats.is_empty() => Type::Unit,
+            Format::Tuple(formats) => Type::Tuple(
+                formats
+                    .iter()
+                    .map(|format| self.lower(format, span, inlined))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Format::TupleArray { content, .. } => {
+                Type::Array(Box::new(self.lower(content, span, inlined)?))
+            }
+            Format::Map { key, value } => Type::Map(
+                Box::new(self.lower(key, span, inlined)?),
+                Box::new(self.lower(value, span, inlined)?),
+            ),
+            Format::Variable(_) => return Err(incomplete(span)),
+        };
+
+        Ok(ty)
+    }
+
+    // where `name` is first a key at or after `from`, or `from` if it isn't found
+    fn key(&self, name: &str, from: usize) -> Span {
+        let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+
+        let found = self.text[from..].match_indices(name).find_map(|(i, _)| {
+            let start = from + i;
+            let end = start + name.len();
+
+            let before = self.text[..start].chars().next_back();
+            let after = self.text[end..].trim_start_matches('"').trim_start();
+
+            let whole = !before.is_some_and(is_ident)
+                && !self.text[end..].starts_with(is_ident)
+                && after.starts_with(':');
+
+            whole.then_some((start, end))
+        });
+
+        let (start, end) = found.unwrap_or((from, from));
+        let line_start = self.text[..start].rfind('\n').map_or(0, |i| i + 1);
+
+        Span {
+            start,
+            end,
+            line: self.text[..start].matches('\n').count() + 1,
+            column: start - line_start + 1,
+        }
+    }
+}
+
+// the tracer didn't find out what something is, which `Tracer::registry` would have complained about
+fn incomplete(span: Span) -> Diagnostic {
+    Diagnostic::error("the registry is incomplete")
+        .with_span(span)
+        .with_label("its format is unknown")
+        .with_note("trace every type it uses, `Tracer::registry` says which are missing")
+}
@@ -0,0 +1,14 @@
+// the Python half of marshal, which all generated code depends on
+// it's embedded so the binary works wherever it's installed
+
+/// The source of `lib/marshal.py`
+pub const SOURCE: &str = include_str!("../lib/marshal.py");
+
+/// The protocol between generated code and the runtime
+///
+/// Bump this whenever either changes in a way the other has to know about,
+/// it must match `MARSHAL_RUNTIME_VERSION` in `lib/marshal.py`
+pub const VERSION: u32 = 9;
+
+/// The module name of a runtime written next to the generated code
+pub const MODULE: &str = "marshal_runtime";
@@ -0,0 +1,1222 @@
+// Types from rustdoc's JSON output for a whole crate
+//
+// `cargo +nightly rustdoc -- -Z unstable-options --output-format json --document-private-items`
+//
+// rustdoc has already expanded macros, evaluated `cfg`s and resolved every path, so walking its index
+// finds every type deriving `Serialize` or `Deserialize`, whichever module declares or re-exports it
+// the generic types fields use are resolved with their arguments, and the crate's own are declared
+// once for each instance of them fields use, `Wrapper<User>` as `WrapperUser`
+//
+// it leaves serde's attributes out though, so they're read from the item's source with `syn`
+// the source files are found relative to the JSON file's directories, as cargo runs rustdoc in the workspace
+// an item a macro generated has no source of its own, so it's taken as having no attributes
+//
+// names and paths are slices of the JSON, which never escapes them
+
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+
+use itertools::Itertools;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use serde_json::value::RawValue;
+
+use crate::diagnostic::Diagnostic;
+use crate::lexer::Span;
+use crate::parser::{
+    field_type, path_type, Attributes, DeclaredType, Enum, EnumVariant, EnumVariantInner,
+    InnerType, Struct, StructField, Type, TypePath,
+};
+use crate::syn_parser::SynParser;
+use crate::Source;
+
+/// The version of rustdoc's format this was written against, others may differ in what we read
+pub const FORMAT_VERSION: u32 = 57;
+
+#[derive(Deserialize)]
+struct Crate<'a> {
+    #[serde(borrow)]
+    index: HashMap<u32, &'a RawValue>,
+
+    /// Every item that's referred to, with its full path
+    #[serde(borrow)]
+    paths: HashMap<u32, Summary<'a>>,
+}
+
+#[derive(Deserialize)]
+struct Summary<'a> {
+    crate_id: u32,
+    #[serde(borrow)]
+    path: Vec<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct Item<'a> {
+    /// `0` is the crate being documented
+    crate_id: u32,
+    name: Option<&'a str>,
+    #[serde(borrow)]
+    span: Option<ItemSpan<'a>>,
+    #[serde(borrow)]
+    attrs: Vec<&'a RawValue>,
+
+    /// One entry, keyed by the kind of item
+    #[serde(borrow)]
+    inner: HashMap<&'a str, &'a RawValue>,
+}
+
+impl<'a> Item<'a> {
+    fn kind(&self) -> Option<(&'a str, &'a RawValue)> {
+        self.inner
+            .iter()
+            .next()
+            .map(|(kind, inner)| (*kind, *inner))
+    }
+}
+
+#[derive(Deserialize, Clone)]
+struct ItemSpan<'a> {
+    /// Relative to where rustdoc ran
+    #[serde(borrow)]
+    filename: Cow<'a, str>,
+
+    /// The line and column, both from 1
+    begin: (usize, usize),
+    end: (usize, usize),
+}
+
+#[derive(Deserialize)]
+struct Generics<'a> {
+    #[serde(borrow)]
+    params: Vec<GenericParam<'a>>,
+    where_predicates: Vec<IgnoredAny>,
+}
+
+impl<'a> Generics<'a> {
+    fn is_empty(&self) -> bool {
+        self.params.is_empty() && self.where_predicates.is_empty()
+    }
+
+    // the type parameters, lifetimes don't change what's serialized
+    fn types(&self) -> Vec<&'a str> {
+        self.params
+            .iter()
+            .filter(|param| matches!(param.kind, GenericParamKind::Type(_)))
+            .map(|param| param.name)
+            .collect()
+    }
+
+    fn has_consts(&self) -> bool {
+        self.params
+            .iter()
+            .any(|param| matches!(param.kind, GenericParamKind::Const(_)))
+    }
+}
+
+#[derive(Deserialize)]
+struct GenericParam<'a> {
+    name: &'a str,
+    kind: GenericParamKind,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum GenericParamKind {
+    Lifetime(IgnoredAny),
+    Type(IgnoredAny),
+    Const(IgnoredAny),
+}
+
+#[derive(Deserialize)]
+struct StructItem<'a> {
+    kind: StructKind,
+    #[serde(borrow)]
+    generics: Generics<'a>,
+    impls: Vec<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum StructKind {
+    Unit,
+    Tuple(IgnoredAny),
+    Plain {
+        fields: Vec<u32>,
+        has_stripped_fields: bool,
+    },
+}
+
+#[derive(Deserialize)]
+struct EnumItem<'a> {
+    #[serde(borrow)]
+    generics: Generics<'a>,
+    has_stripped_variants: bool,
+    variants: Vec<u32>,
+    impls: Vec<u32>,
+}
+
+#[derive(Deserialize)]
+struct VariantItem {
+    kind: VariantKind,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum VariantKind {
+    Plain,
+    /// `None` for a field rustdoc left out
+    Tuple(Vec<Option<u32>>),
+    Struct {
+        fields: Vec<u32>,
+        has_stripped_fields: bool,
+    },
+}
+
+#[derive(Deserialize)]
+struct ImplItem<'a> {
+    #[serde(borrow, rename = "trait")]
+    trait_: Option<ResolvedPath<'a>>,
+}
+
+#[derive(Deserialize)]
+struct TypeAliasItem<'a> {
+    #[serde(borrow, rename = "type")]
+    ty: RustdocType<'a>,
+    #[serde(borrow)]
+    generics: Generics<'a>,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+enum RustdocType<'a> {
+    #[serde(borrow)]
+    ResolvedPath(ResolvedPath<'a>),
+    Primitive(&'a str),
+    Tuple(Vec<RustdocType<'a>>),
+    Array {
+        #[serde(rename = "type")]
+        ty: Box<RustdocType<'a>>,
+    },
+    /// a type parameter, which an instance's argument replaces
+    Generic(&'a str),
+    // none of these are serializable, or they're references
+    DynTrait(IgnoredAny),
+    FunctionPointer(IgnoredAny),
+    Slice(IgnoredAny),
+    Pat(IgnoredAny),
+    ImplTrait(IgnoredAny),
+    Infer,
+    RawPointer(IgnoredAny),
+    BorrowedRef(IgnoredAny),
+    QualifiedPath(IgnoredAny),
+}
+
+#[derive(Deserialize, Clone)]
+struct ResolvedPath<'a> {
+    /// As it's exported, like `std::collections::HashMap`
+    path: &'a str,
+    id: u32,
+    #[serde(borrow)]
+    args: Option<Box<GenericArgs<'a>>>,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+enum GenericArgs<'a> {
+    AngleBracketed {
+        #[serde(borrow)]
+        args: Vec<GenericArg<'a>>,
+    },
+    Parenthesized(IgnoredAny),
+    ReturnTypeNotation,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+enum GenericArg<'a> {
+    Lifetime(IgnoredAny),
+    #[serde(borrow)]
+    Type(RustdocType<'a>),
+    Const(IgnoredAny),
+    Infer,
+}
+
+// what the source says about a type, which rustdoc leaves out
+struct Declaration<'a> {
+    attrs: Attributes<'a>,
+
+    /// where the type's name is
+    span: Span,
+
+    /// fields `a`, variants `A`, and their fields `A::a` or `A::0`
+    members: HashMap<String, Member<'a>>,
+}
+
+struct Member<'a> {
+    attrs: Attributes<'a>,
+
+    /// where its name is, or its type for a tuple field
+    span: Span,
+    ty_span: Span,
+}
+
+// a generic type with the arguments a field gives it, declared as a class of its own
+struct Instance<'a> {
+    id: u32,
+    args: Vec<RustdocType<'a>>,
+
+    /// the class's name, the type's and its arguments', `WrapperUser`
+    name: &'a str,
+
+    /// how many instances in it was found, see [`MAX_DEPTH`]
+    depth: usize,
+}
+
+/// How many instances deep a field can be, as a type can instantiate itself with ever larger arguments
+const MAX_DEPTH: usize = 8;
+
+// a source file, and its syntax tree once it's been parsed
+struct SourceFile<'a> {
+    source: &'a Source,
+    parsed: Option<Option<(SynParser<'a>, syn::File)>>,
+}
+
+/// The files the crate's structs and enums are declared in, as rustdoc names them
+///
+/// Used to find the sources to read their attributes from, an invalid file has none
+pub fn files(json: &str) -> Vec<String> {
+    let Ok(krate) = serde_json::from_str::<Crate>(json) else {
+        return Vec::new();
+    };
+
+    let mut files: Vec<_> = krate
+        .index
+        .values()
+        .filter_map(|raw| serde_json::from_str::<Item>(raw.get()).ok())
+        .filter(|item| item.crate_id == 0)
+        .filter(|item| matches!(item.kind(), Some(("struct" | "enum", _))))
+        .filter_map(|item| Some(item.span?.filename.into_owned()))
+        .collect();
+
+    files.sort();
+    files.dedup();
+
+    files
+}
+
+pub struct RustdocParser<'a> {
+    json: &'a str,
+
+    // the name of the JSON file
+    file: &'a str,
+
+    // enabled for `#[cfg_attr(feature = "...", ...)]`, rustdoc has evaluated every `#[cfg]`
+    features: &'a [String],
+
+    sources: Vec<SourceFile<'a>>,
+
+    // the names of the instances, which rustdoc has no strings for, see `instances`
+    names: &'a [String],
+
+    // the names of the instances that weren't in `names`
+    unnamed: Vec<String>,
+
+    // the instances still to be declared, and every one's type by its name
+    instances: VecDeque<Instance<'a>>,
+    instantiated: HashMap<String, String>,
+
+    // the type parameters of the instance being declared, and its arguments for them
+    params: Vec<(&'a str, RustdocType<'a>)>,
+    depth: usize,
+
+    types: Vec<DeclaredType<'a>>,
+
+    // problems we carried on after
+    errors: Vec<Diagnostic>,
+}
+
+impl<'a> RustdocParser<'a> {
+    /// `sources` are the crate's files, see [`files`]
+    pub fn new(json: &'a str, file: &'a str, sources: &'a [Source]) -> Self {
+        RustdocParser {
+            json,
+            file,
+            features: &[],
+            sources: sources
+                .iter()
+                .map(|source| SourceFile {
+                    source,
+                    parsed: None,
+                })
+                .collect(),
+            names: &[],
+            unnamed: Vec::new(),
+            instances: VecDeque::new(),
+            instantiated: HashMap::new(),
+            params: Vec::new(),
+            depth: 0,
+            types: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// The cargo features rustdoc ran with
+    pub fn with_features(mut self, features: &'a [String]) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// The names of the instances of generic types, from [`RustdocParser::instances`]
+    pub fn with_names(mut self, names: &'a [String]) -> Self {
+        self.names = names;
+        self
+    }
+
+    /// The names of the classes of the instances of generic types `parse` declares, which it borrows
+    ///
+    /// They're made up of the type's name and its arguments', so there are no strings for them in the JSON
+    pub fn instances(mut self, selected: impl Fn(&str) -> bool) -> Vec<String> {
+        // the errors are `parse`'s to report
+        let _ = self.walk(selected);
+
+        self.unnamed
+    }
+
+    /// Every type in the crate deriving `Serialize` or `Deserialize` whose name is `selected`,
+    /// in the order of their files and their positions in them,
+    /// then the instances of generic ones their fields use
+    pub fn parse(
+        mut self,
+        selected: impl Fn(&str) -> bool,
+    ) -> Result<Vec<DeclaredType<'a>>, Vec<Diagnostic>> {
+        if let Err(err) = self.walk(selected) {
+            return Err(vec![err]);
+        }
+
+        if self.errors.is_empty() {
+            Ok(self.types)
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    // finds the types and declares them, stopping at an error that leaves nothing else to do
+    fn walk(&mut self, selected: impl Fn(&str) -> bool) -> Result<(), Diagnostic> {
+        let krate = match serde_json::from_str::<Crate<'a>>(self.json) {
+            Ok(krate) => krate,
+            Err(err) => return Err(self.invalid(err)),
+        };
+
+        let mut found = Vec::new();
+
+        for (&id, raw) in &krate.index {
+            let item = match serde_json::from_str::<Item<'a>>(raw.get()) {
+                Ok(item) => item,
+                Err(err) => return Err(self.invalid(err)),
+            };
+
+            let (Some(name), Some(span)) = (item.name, &item.span) else {
+                continue;
+            };
+
+            if item.crate_id != 0 || !selected(name) {
+                continue;
+            }
+
+            let Some((impls, generics)) = self.impls_and_generics(&item)? else {
+                continue;
+            };
+
+            // declared for each of its instances instead
+            if !generics.types().is_empty() {
+                continue;
+            }
+
+            let derives = self.derives(&krate, &impls)?;
+
+            // a hand written impl could serialize it as anything
+            if derives
+                .iter()
+                .any(|derive| matches!(*derive, "Serialize" | "Deserialize"))
+            {
+                found.push((span.filename.clone(), span.begin, id, item, derives));
+            }
+        }
+
+        found.sort_by(|a, b| (&a.0, a.1, a.2).cmp(&(&b.0, b.1, b.2)));
+
+        let mut names: HashMap<&str, u32> = HashMap::new();
+
+        for (_, _, id, item, derives) in found {
+            let name = item.name.unwrap_or_default();
+
+            if let Some(first) = names.insert(name, id) {
+                let path = |id| {
+                    krate
+                        .paths
+                        .get(&id)
+                        .map_or(name.to_string(), |summary: &Summary| {
+                            summary.path.join("::")
+                        })
+                };
+
+                let mut err = Diagnostic::error(format!("there are two types named `{name}`"))
+                    .with_note(format!("`{}` and `{}`", path(first), path(id)))
+                    .with_note("the Python classes are named after the Rust types")
+                    .with_file(self.file);
+
+                let span = item.span.as_ref().expect("only items with spans are found");
+
+                if let Some(file) = self.source(&span.filename) {
+                    err = err
+                        .with_span(self.locate(file, span))
+                        .with_label("declared again here")
+                        .with_file(&self.sources[file].source.name);
+                }
+
+                self.errors.push(err);
+
+                continue;
+            }
+
+            if let Err(err) = self.item(&krate, item, name, derives) {
+                self.errors.push(err);
+            }
+        }
+
+        while let Some(instance) = self.instances.pop_front() {
+            let item = self.item_by_id(&krate, instance.id)?;
+
+            let Some((impls, generics)) = self.impls_and_generics(&item)? else {
+                continue;
+            };
+
+            let derives = self.derives(&krate, &impls)?;
+
+            self.params = generics.types().into_iter().zip(instance.args).collect();
+            self.depth = instance.depth;
+
+            if let Err(err) = self.item(&krate, item, instance.name, derives) {
+                self.errors.push(err);
+            }
+
+            // a type could be named like an instance
+            if names.insert(instance.name, instance.id).is_some() {
+                self.errors.push(
+                    Diagnostic::error(format!("there are two types named `{}`", instance.name))
+                        .with_note(
+                            "one's an instance of a generic type, named after it and its arguments",
+                        )
+                        .with_file(self.file),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    // the JSON isn't what we expected, or isn't JSON at all
+    fn invalid(&self, err: serde_json::Error) -> Diagnostic {
+        #[derive(Deserialize)]
+        struct Version {
+            format_version: u32,
+        }
+
+        let mut diagnostic = Diagnostic::error(format!("cannot read rustdoc's output: {err}"))
+            .with_span(Span::at(self.json, err.line(), err.column()))
+            .with_file(self.file);
+
+        if let Ok(Version { format_version }) = serde_json::from_str(self.json) {
+            if format_version != FORMAT_VERSION {
+                diagnostic = diagnostic.with_note(format!(
+                    "this is rustdoc's format version {format_version}, marshal reads version {FORMAT_VERSION}"
+                ));
+            }
+        }
+
+        diagnostic
+    }
+
+    fn read<T: Deserialize<'a>>(&self, raw: &'a RawValue) -> Result<T, Diagnostic> {
+        serde_json::from_str(raw.get()).map_err(|err| self.invalid(err))
+    }
+
+    fn item_by_id(&self, krate: &Crate<'a>, id: u32) -> Result<Item<'a>, Diagnostic> {
+        let raw = krate.index.get(&id).ok_or_else(|| {
+            Diagnostic::error(format!(
+                "rustdoc's output refers to item {id}, which isn't in it"
+            ))
+            .with_file(self.file)
+        })?;
+
+        self.read(raw)
+    }
+
+    // a struct's or enum's
+    fn impls_and_generics(
+        &self,
+        item: &Item<'a>,
+    ) -> Result<Option<(Vec<u32>, Generics<'a>)>, Diagnostic> {
+        Ok(match item.kind() {
+            Some(("struct", inner)) => {
+                let item = self.read::<StructItem>(inner)?;
+                Some((item.impls, item.generics))
+            }
+            Some(("enum", inner)) => {
+                let item = self.read::<EnumItem>(inner)?;
+                Some((item.impls, item.generics))
+            }
+            _ => None,
+        })
+    }
+
+    // the traits derived by the impls, `Serialize` only when it's serde's
+    fn derives(&self, krate: &Crate<'a>, impls: &[u32]) -> Result<Vec<&'a str>, Diagnostic> {
+        let mut derives = Vec::new();
+
+        for &id in impls {
+            let item = self.item_by_id(krate, id)?;
+
+            let derived = item
+                .attrs
+                .iter()
+                .any(|attr| attr.get().contains("automatically_derived"));
+
+            let Some(("impl", inner)) = item.kind().filter(|_| derived) else {
+                continue;
+            };
+
+            let Some(trait_) = self.read::<ImplItem>(inner)?.trait_ else {
+                continue;
+            };
+
+            let name = trait_.path.rsplit("::").next().unwrap_or_default();
+
+            let from_serde = krate.paths.get(&trait_.id).is_some_and(|summary| {
+                matches!(summary.path.first(), Some(&("serde" | "serde_core")))
+            });
+
+            if matches!(name, "Serialize" | "Deserialize") && !from_serde {
+                continue;
+            }
+
+            derives.push(name);
+        }
+
+        Ok(derives)
+    }
+
+    // `class` is the type's name, or its instance's
+    fn item(
+        &mut self,
+        krate: &Crate<'a>,
+        item: Item<'a>,
+        class: &'a str,
+        derives: Vec<&'a str>,
+    ) -> Result<(), Diagnostic> {
+        let name = item.name.unwrap_or_default();
+        let item_span = item.span.clone().expect("only items with spans are found");
+
+        let Some(file) = self.source(&item_span.filename) else {
+            return Err(Diagnostic::error(format!(
+                "cannot find `{}`, where `{name}` is declared",
+                item_span.filename
+            ))
+            .with_file(self.file)
+            .with_note("rustdoc leaves serde's attributes out, so they're read from the source")
+            .with_note("it's looked for next to the JSON file and in the directories above it"));
+        };
+
+        let declaration = self.declaration(file, name, &item_span);
+        let span = declaration
+            .as_ref()
+            .map_or_else(|| self.locate(file, &item_span), |decl| decl.span);
+
+        let source_name = self.sources[file].source.name.as_str();
+
+        let unsupported = |message: &str, label: &str| {
+            Diagnostic::error(message.to_string())
+                .with_span(span)
+                .with_label(label.to_string())
+                .with_file(source_name)
+        };
+
+        let stripped = || {
+            unsupported(
+                &format!("rustdoc left some of `{name}` out"),
+                "has private fields or variants",
+            )
+            .with_note("run rustdoc with `--document-private-items`")
+        };
+
+        let (kind, inner) = item.kind().expect("only structs and enums are found");
+
+        let inner = if kind == "struct" {
+            let inner = self.read::<StructItem>(inner)?;
+
+            match inner.kind {
+                StructKind::Plain {
+                    has_stripped_fields: true,
+                    ..
+                } => return Err(stripped()),
+                StructKind::Plain { fields, .. } => InnerType::Struct(Struct {
+                    fields: self.fields(krate, &fields, "", declaration.as_ref(), span, file)?,
+                }),
+                StructKind::Unit | StructKind::Tuple(_) => {
+                    return Err(unsupported(
+                        "only structs with named fields are supported",
+                        "has no named fields",
+                    ))
+                }
+            }
+        } else {
+            let inner = self.read::<EnumItem>(inner)?;
+
+            if inner.has_stripped_variants {
+                return Err(stripped());
+            }
+
+            let mut variants = Vec::new();
+
+            for id in inner.variants {
+                let variant = self.item_by_id(krate, id)?;
+                let variant_name = variant.name.unwrap_or_default();
+
+                let Some(("variant", kind)) = variant.kind() else {
+                    continue;
+                };
+
+                let member = declaration
+                    .as_ref()
+                    .and_then(|decl| decl.members.get(variant_name));
+
+                let inner = match self.read::<VariantItem>(kind)?.kind {
+                    VariantKind::Plain => EnumVariantInner::Unit,
+                    VariantKind::Tuple(fields) => {
+                        let mut types = Vec::new();
+
+                        for (i, field) in fields.into_iter().enumerate() {
+                            let Some(field) = field else {
+                                return Err(stripped());
+                            };
+
+                            let field = self.item_by_id(krate, field)?;
+                            let ty_span = declaration
+                                .as_ref()
+                                .and_then(|decl| decl.members.get(&format!("{variant_name}::{i}")))
+                                .map_or(span, |member| member.ty_span);
+
+                            let Some(("struct_field", ty)) = field.kind() else {
+                                continue;
+                            };
+
+                            let ty = self.ty(krate, self.read(ty)?, ty_span, source_name)?;
+
+                            types.push(ty);
+                        }
+
+                        EnumVariantInner::Tuple(types)
+                    }
+                    VariantKind::Struct {
+                        has_stripped_fields: true,
+                        ..
+                    } => return Err(stripped()),
+                    VariantKind::Struct { fields, .. } => EnumVariantInner::Struct(Struct {
+                        fields: self.fields(
+                            krate,
+                            &fields,
+                            &format!("{variant_name}::"),
+                            declaration.as_ref(),
+                            span,
+                            file,
+                        )?,
+                    }),
+                };
+
+                let (attrs, default) = match member {
+                    Some(member) => (member.attrs.serde.clone(), member.attrs.default),
+                    None => (Vec::new(), false),
+                };
+
+                variants.push(EnumVariant {
+                    attrs,
+                    name: variant_name,
+                    inner,
+                    default,
+                });
+            }
+
+            InnerType::Enum(Enum { variants })
+        };
+
+        self.types.push(DeclaredType {
+            attrs: declaration.map(|decl| decl.attrs.serde).unwrap_or_default(),
+            name: class,
+            inner,
+            derives,
+            span,
+            file: source_name,
+        });
+
+        Ok(())
+    }
+
+    // `prefix` is `Variant::` for a variant's fields
+    fn fields(
+        &mut self,
+        krate: &Crate<'a>,
+        ids: &[u32],
+        prefix: &str,
+        declaration: Option<&Declaration<'a>>,
+        span: Span,
+        file: usize,
+    ) -> Result<Vec<StructField<'a>>, Diagnostic> {
+        let source_name = self.sources[file].source.name.as_str();
+        let mut fields = Vec::new();
+
+        for &id in ids {
+            let field = self.item_by_id(krate, id)?;
+            let name = field.name.unwrap_or_default();
+
+            let Some(("struct_field", ty)) = field.kind() else {
+                continue;
+            };
+
+            let member = declaration.and_then(|decl| decl.members.get(&format!("{prefix}{name}")));
+
+            let (attrs, serde_as, span, ty_span) = match member {
+                Some(member) => (
+                    member.attrs.serde.clone(),
+                    member.attrs.serde_as,
+                    member.span,
+                    member.ty_span,
+                ),
+                None => (Vec::new(), None, span, span),
+            };
+
+            let ty = self.ty(krate, self.read(ty)?, ty_span, source_name)?;
+            let ty = field_type(ty, ty_span, &attrs, serde_as)
+                .map_err(|err| err.with_file(source_name))?;
+
+            fields.push(StructField {
+                attrs,
+                name,
+                ty,
+                span,
+            });
+        }
+
+        Ok(fields)
+    }
+
+    fn ty(
+        &mut self,
+        krate: &Crate<'a>,
+        ty: RustdocType<'a>,
+        span: Span,
+        file: &str,
+    ) -> Result<Type<'a>, Diagnostic> {
+        let unsupported = || {
+            Diagnostic::error("unsupported type")
+                .with_span(span)
+                .with_label("not supported by marshal")
+                .with_file(file)
+        };
+
+        let path = match self.substitute(ty) {
+            RustdocType::Primitive(name) => TypePath {
+                segments: vec![name],
+                args: Vec::new(),
+            },
+            RustdocType::Tuple(types) if types.is_empty() => return Ok(Type::Unit),
+            RustdocType::Tuple(types) => {
+                return Ok(Type::Tuple(
+                    types
+                        .into_iter()
+                        .map(|ty| self.ty(krate, ty, span, file))
+                        .collect::<Result<_, _>>()?,
+                ))
+            }
+            RustdocType::Array { ty } => {
+                return Ok(Type::Array(Box::new(self.ty(krate, *ty, span, file)?)))
+            }
+            RustdocType::ResolvedPath(path) => {
+                if let Some(instance) = self.instance(krate, &path, span, file)? {
+                    return Ok(instance);
+                }
+
+                let summary = krate.paths.get(&path.id);
+
+                // an alias is what it stands for
+                if let (Ok(item), None) = (self.item_by_id(krate, path.id), &path.args) {
+                    if let (0, Some(("type_alias", inner))) = (item.crate_id, item.kind()) {
+                        let alias = self.read::<TypeAliasItem>(inner)?;
+
+                        if alias.generics.is_empty() {
+                            return self.ty(krate, alias.ty, span, file);
+                        }
+                    }
+                }
+
+                let segments = match summary {
+                    // the crate's own types by their full paths
+                    Some(summary) if summary.crate_id == 0 => summary.path.clone(),
+                    _ => path.path.split("::").filter(|s| !s.is_empty()).collect(),
+                };
+
+                let args = match path.args.map(|args| *args) {
+                    None => Vec::new(),
+                    Some(GenericArgs::AngleBracketed { args }) => args
+                        .into_iter()
+                        .map(|arg| match arg {
+                            GenericArg::Type(ty) => self.ty(krate, ty, span, file),
+                            _ => Err(unsupported()),
+                        })
+                        .collect::<Result<_, _>>()?,
+                    Some(_) => return Err(unsupported()),
+                };
+
+                TypePath { segments, args }
+            }
+            _ => return Err(unsupported()),
+        };
+
+        path_type(path, span).map_err(|err| err.with_file(file))
+    }
+
+    // `ty` with the parameters of the instance being declared replaced by its arguments
+    fn substitute(&self, ty: RustdocType<'a>) -> RustdocType<'a> {
+        match ty {
+            RustdocType::Generic(name) => self
+                .params
+                .iter()
+                .find(|(param, _)| *param == name)
+                .map_or(RustdocType::Generic(name), |(_, arg)| arg.clone()),
+            RustdocType::Tuple(types) => {
+                RustdocType::Tuple(types.into_iter().map(|ty| self.substitute(ty)).collect())
+            }
+            RustdocType::Array { ty } => RustdocType::Array {
+                ty: Box::new(self.substitute(*ty)),
+            },
+            RustdocType::ResolvedPath(mut path) => {
+                if let Some(GenericArgs::AngleBracketed { args }) = path.args.as_deref_mut() {
+                    for arg in args {
+                        if let GenericArg::Type(ty) = arg {
+                            *ty = self.substitute(ty.clone());
+                        }
+                    }
+                }
+
+                RustdocType::ResolvedPath(path)
+            }
+            ty => ty,
+        }
+    }
+
+    // a class for `path` when it's one of the crate's generic types, which is declared later
+    fn instance(
+        &mut self,
+        krate: &Crate<'a>,
+        path: &ResolvedPath<'a>,
+        span: Span,
+        file: &str,
+    ) -> Result<Option<Type<'a>>, Diagnostic> {
+        let Ok(item) = self.item_by_id(krate, path.id) else {
+            return Ok(None);
+        };
+
+        let Some((impls, generics)) = self.impls_and_generics(&item)? else {
+            return Ok(None);
+        };
+
+        if item.crate_id != 0 {
+            return Ok(None);
+        }
+
+        let params = generics.types();
+
+        // a hand written impl could serialize it as anything, so it's left for `--type`
+        if params.is_empty()
+            || !self
+                .derives(krate, &impls)?
+                .iter()
+                .any(|derive| matches!(*derive, "Serialize" | "Deserialize"))
+        {
+            return Ok(None);
+        }
+
+        let error = |message: String| {
+            Diagnostic::error(message)
+                .with_span(span)
+                .with_label("not supported by marshal")
+                .with_file(file)
+        };
+
+        let name = item.name.unwrap_or_default();
+
+        if generics.has_consts() {
+            return Err(error(format!("`{name}` has const parameters")));
+        }
+
+        let args: Vec<_> = type_args(path).cloned().collect();
+
+        if args.len() != params.len() {
+            return Err(error(format!(
+                "`{name}` takes {} type arguments, but {} were given",
+                params.len(),
+                args.len()
+            )));
+        }
+
+        if self.depth == MAX_DEPTH {
+            return Err(error(format!(
+                "`{name}` is instantiated {MAX_DEPTH} instances deep"
+            ))
+            .with_note("it's likely a type instantiating itself with ever larger arguments"));
+        }
+
+        let class = format!(
+            "{name}{}",
+            args.iter()
+                .map(|arg| type_name(krate, arg))
+                .collect::<String>()
+        );
+        let id = format!(
+            "{}{}",
+            path.id,
+            args.iter().map(type_id).collect::<String>()
+        );
+        let name = self.name(&class);
+
+        match self.instantiated.get(&class) {
+            Some(known) if *known == id => {}
+            Some(_) => {
+                return Err(
+                    error(format!("two instances of `{name}` are named `{class}`"))
+                        .with_note("the classes are named after the types and their arguments"),
+                )
+            }
+            None => {
+                self.instances.push_back(Instance {
+                    id: path.id,
+                    args,
+                    name,
+                    depth: self.depth + 1,
+                });
+                self.instantiated.insert(class, id);
+            }
+        }
+
+        Ok(Some(Type::User(TypePath {
+            segments: vec![name],
+            args: Vec::new(),
+        })))
+    }
+
+    // an instance's name as one of `names`, which the first walk doesn't have
+    fn name(&mut self, class: &str) -> &'a str {
+        let names = self.names;
+
+        match names.iter().find(|name| *name == class) {
+            Some(name) => name,
+            None => {
+                if !self.unnamed.iter().any(|name| name == class) {
+                    self.unnamed.push(class.to_string());
+                }
+
+                ""
+            }
+        }
+    }
+
+    // the index of the source rustdoc's `filename` is
+    fn source(&self, filename: &str) -> Option<usize> {
+        self.sources.iter().position(|file| {
+            file.source
+                .path
+                .as_ref()
+                .is_some_and(|path| path.ends_with(filename))
+        })
+    }
+
+    // a span in the source from rustdoc's line and column
+    fn locate(&self, file: usize, span: &ItemSpan) -> Span {
+        let (line, column) = span.begin;
+
+        Span::at(&self.sources[file].source.text, line, column)
+    }
+
+    // the struct or enum named `name` that rustdoc's `span` covers, with its attributes lowered
+    fn declaration(&mut self, file: usize, name: &str, span: &ItemSpan) -> Option<Declaration<'a>> {
+        let features = self.features;
+        let source = &mut self.sources[file];
+
+        let (parser, tree) = source
+            .parsed
+            .get_or_insert_with(|| {
+                let mut parser = SynParser::new(&source.source.text, &source.source.name)
+                    .with_features(features);
+
+                let tree = parser.file()?;
+
+                Some((parser, tree))
+            })
+            .as_mut()?;
+
+        let lines = span.begin.0..=span.end.0;
+        let item = find(&tree.items, name, &lines)?;
+
+        let mut members = HashMap::new();
+
+        let (attrs, ident) = match item {
+            Found::Struct(item) => {
+                members_of(parser, "", &item.fields, &mut members);
+
+                (parser.attributes(&item.attrs), &item.ident)
+            }
+            Found::Enum(item) => {
+                for variant in &item.variants {
+                    let name = parser.ident(&variant.ident);
+
+                    members_of(parser, &format!("{name}::"), &variant.fields, &mut members);
+
+                    let span = parser.span(variant.ident.span());
+                    let attrs = parser.attributes(&variant.attrs);
+
+                    members.insert(
+                        name.to_string(),
+                        Member {
+                            attrs,
+                            span,
+                            ty_span: span,
+                        },
+                    );
+                }
+
+                (parser.attributes(&item.attrs), &item.ident)
+            }
+        };
+
+        let span = parser.span(ident.span());
+
+        self.errors.extend(
+            parser
+                .take_errors()
+                .into_iter()
+                .map(|err| err.with_file(&source.source.name)),
+        );
+
+        Some(Declaration {
+            attrs,
+            span,
+            members,
+        })
+    }
+}
+
+// the fields, by name or position, after `prefix`
+fn members_of<'a>(
+    parser: &mut SynParser<'a>,
+    prefix: &str,
+    fields: &syn::Fields,
+    members: &mut HashMap<String, Member<'a>>,
+) {
+    use syn::spanned::Spanned;
+
+    for (i, field) in fields.iter().enumerate() {
+        let ty_span = parser.span(field.ty.span());
+
+        let (name, span) = match &field.ident {
+            Some(ident) => (parser.ident(ident).to_string(), parser.span(ident.span())),
+            None => (i.to_string(), ty_span),
+        };
+
+        let attrs = parser.attributes(&field.attrs);
+
+        members.insert(
+            format!("{prefix}{name}"),
+            Member {
+                attrs,
+                span,
+                ty_span,
+            },
+        );
+    }
+}
+
+// a type's part of an instance's name, `Vec<u8>` is `VecU8`
+fn type_name(krate: &Crate, ty: &RustdocType) -> String {
+    match ty {
+        RustdocType::Primitive(name) => {
+            let mut chars = name.chars();
+            chars.next().map_or(String::new(), |first| {
+                first.to_uppercase().chain(chars).collect()
+            })
+        }
+        RustdocType::Tuple(types) if types.is_empty() => "Unit".to_string(),
+        RustdocType::Tuple(types) => {
+            let types: String = types.iter().map(|ty| type_name(krate, ty)).collect();
+            format!("Tuple{types}")
+        }
+        RustdocType::Array { ty } => format!("Array{}", type_name(krate, ty)),
+        RustdocType::ResolvedPath(path) => {
+            // by its own name, not what it was imported as
+            let name = match krate.paths.get(&path.id) {
+                Some(summary) => summary.path.last().copied(),
+                None => path.path.rsplit("::").next(),
+            };
+
+            let args: String = type_args(path).map(|ty| type_name(krate, ty)).collect();
+
+            name.unwrap_or_default().to_string() + &args
+        }
+        _ => String::new(),
+    }
+}
+
+// what a type is, which two instances with the same name could differ in
+fn type_id(ty: &RustdocType) -> String {
+    match ty {
+        RustdocType::Primitive(name) => name.to_string(),
+        RustdocType::Tuple(types) => format!("({})", types.iter().map(type_id).join(",")),
+        RustdocType::Array { ty } => format!("[{}]", type_id(ty)),
+        RustdocType::ResolvedPath(path) => {
+            format!("#{}<{}>", path.id, type_args(path).map(type_id).join(","))
+        }
+        _ => "_".to_string(),
+    }
+}
+
+// the type arguments of a path, without its lifetimes
+fn type_args<'p, 'a>(path: &'p ResolvedPath<'a>) -> impl Iterator<Item = &'p RustdocType<'a>> {
+    let args = match path.args.as_deref() {
+        Some(GenericArgs::AngleBracketed { args }) => &args[..],
+        _ => &[],
+    };
+
+    args.iter().filter_map(|arg| match arg {
+        GenericArg::Type(ty) => Some(ty),
+        _ => None,
+    })
+}
+
+enum Found<'f> {
+    Struct(&'f syn::ItemStruct),
+    Enum(&'f syn::ItemEnum),
+}
+
+// the struct or enum called `name` whose name is on one of `lines`, looking into inline `mod`s
+fn find<'f>(
+    items: &'f [syn::Item],
+    name: &str,
+    lines: &std::ops::RangeInclusive<usize>,
+) -> Option<Found<'f>> {
+    let here = |ident: &syn::Ident| ident == name && lines.contains(&ident.span().start().line);
+
+    items.iter().find_map(|item| match item {
+        syn::Item::Struct(item) if here(&item.ident) => Some(Found::Struct(item)),
+        syn::Item::Enum(item) if here(&item.ident) => Some(Found::Enum(item)),
+        syn::Item::Mod(module) => find(&module.content.as_ref()?.1, name, lines),
+        _ => None,
+    })
+}
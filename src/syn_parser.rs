@@ -0,0 +1,589 @@
+// The same declarations as `parser`, read with `syn` instead, which understands all of Rust
+//
+// structs and enums are lowered into `parser`'s model, inline `mod`s are looked into,
+// and everything else, `impl`s, functions, `use`s, macros, ... is skipped
+//
+// names and strings are slices of the input, as they are for the native parser,
+// so both frontends produce the same model, spans included
+
+use proc_macro2::Span as SynSpan;
+use syn::ext::IdentExt;
+use syn::meta::ParseNestedMeta;
+use syn::parse::ParseStream;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{Attribute, Fields, GenericArgument, Item, LitStr, Meta, MetaList, PathArguments, Token};
+
+use crate::diagnostic::Diagnostic;
+use crate::lexer::Span;
+use crate::parser::{
+    field_type, path_type, Attributes, DeclaredType, Enum, EnumVariant, EnumVariantInner,
+    InnerType, SerdeAttribute, Struct, StructField, Type, TypePath,
+};
+
+/// Rewrites a user type, like `models::User`, into what it names, see [`SynParser::set_resolver`]
+pub(crate) type Resolver<'a> = Box<dyn Fn(TypePath<'a>, Span) -> Result<Type<'a>, Diagnostic> + 'a>;
+
+pub struct SynParser<'a> {
+    text: &'a str,
+
+    // the name of the file being parsed
+    file: &'a str,
+
+    // enabled for `#[cfg(feature = "...")]`
+    features: &'a [String],
+
+    // user types are left as they're written without one
+    resolver: Option<Resolver<'a>>,
+
+    types: Vec<DeclaredType<'a>>,
+
+    // problems we carried on after
+    errors: Vec<Diagnostic>,
+}
+
+impl<'a> SynParser<'a> {
+    pub fn new(text: &'a str, file: &'a str) -> Self {
+        SynParser {
+            text,
+            file,
+            features: &[],
+            resolver: None,
+            types: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// The cargo features to consider enabled when evaluating `#[cfg]` and `#[cfg_attr]`
+    pub fn with_features(mut self, features: &'a [String]) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// Parses every struct and enum, carrying on after errors in them so they can all be reported at once
+    ///
+    /// The file has to be valid Rust, `syn` stops at the first syntax error
+    pub fn parse(mut self) -> Result<Vec<DeclaredType<'a>>, Vec<Diagnostic>> {
+        if let Some(file) = self.file() {
+            self.items(&file.items);
+        }
+
+        if self.errors.is_empty() {
+            Ok(self.types)
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    /// The whole file, or `None` with its syntax error in `errors`
+    pub(crate) fn file(&mut self) -> Option<syn::File> {
+        // `syn` only says it "cannot parse string into token stream"
+        let tokens = match self.text.parse::<proc_macro2::TokenStream>() {
+            Ok(tokens) => tokens,
+            Err(err) => {
+                self.errors.push(
+                    Diagnostic::error("unclosed delimiter or literal")
+                        .with_span(self.span(err.span()))
+                        .with_label("never closed"),
+                );
+
+                return None;
+            }
+        };
+
+        match syn::parse2::<syn::File>(tokens) {
+            Ok(file) => Some(file),
+            Err(err) => {
+                let err = self.error(err);
+                self.errors.push(err);
+
+                None
+            }
+        }
+    }
+
+    /// The errors so far, for callers lowering items themselves
+    pub(crate) fn take_errors(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// The types lowered so far by [`SynParser::item`]
+    pub(crate) fn take_types(&mut self) -> Vec<DeclaredType<'a>> {
+        std::mem::take(&mut self.types)
+    }
+
+    /// Resolves the paths of the user types in the items lowered from now on
+    pub(crate) fn set_resolver(&mut self, resolver: Resolver<'a>) {
+        self.resolver = Some(resolver);
+    }
+
+    pub(crate) fn span(&self, span: SynSpan) -> Span {
+        let range = span.byte_range();
+
+        // columns count bytes, as the lexer's do
+        let line_start = self.text[..range.start].rfind('\n').map_or(0, |i| i + 1);
+
+        Span {
+            start: range.start,
+            end: range.end,
+            line: span.start().line,
+            column: range.start - line_start + 1,
+        }
+    }
+
+    fn error(&self, err: syn::Error) -> Diagnostic {
+        Diagnostic::error(err.to_string()).with_span(self.span(err.span()))
+    }
+
+    pub(crate) fn ident(&self, ident: &syn::Ident) -> &'a str {
+        let name = &self.text[ident.span().byte_range()];
+
+        name.strip_prefix("r#").unwrap_or(name)
+    }
+
+    // what's between the quotes, as it's written
+    fn string(&self, lit: &LitStr) -> &'a str {
+        let text = &self.text[lit.span().byte_range()];
+
+        // `r#"..."#`
+        let text = text.trim_start_matches('r').trim_matches('#');
+
+        &text[1..text.len() - 1]
+    }
+
+    fn items(&mut self, items: &[Item]) {
+        for item in items {
+            match item {
+                // like `#[cfg(test)] mod tests { ... }`, which doesn't exist
+                Item::Mod(module) => {
+                    if let Some((_, items)) = &module.content {
+                        if !self.attributes(&module.attrs).disabled {
+                            self.items(items);
+                        }
+                    }
+                }
+                item => self.item(item),
+            }
+        }
+    }
+
+    /// Lowers a struct or an enum, anything else is skipped
+    pub(crate) fn item(&mut self, item: &Item) {
+        match item {
+            Item::Struct(item) => self.item_struct(item),
+            Item::Enum(item) => self.item_enum(item),
+            _ => {}
+        }
+    }
+
+    fn unsupported_generics(&mut self, generics: &syn::Generics) {
+        if !generics.params.is_empty() || generics.where_clause.is_some() {
+            self.errors.push(
+                Diagnostic::error("generic types aren't supported")
+                    .with_span(self.span(generics.span()))
+                    .with_label("not supported by marshal"),
+            );
+        }
+    }
+
+    fn item_struct(&mut self, item: &syn::ItemStruct) {
+        let attrs = self.attributes(&item.attrs);
+
+        self.unsupported_generics(&item.generics);
+
+        let fields = match &item.fields {
+            Fields::Named(fields) => self.fields(&fields.named),
+            _ => {
+                self.errors.push(
+                    Diagnostic::error("only structs with named fields are supported")
+                        .with_span(self.span(item.ident.span()))
+                        .with_label("has no named fields"),
+                );
+
+                return;
+            }
+        };
+
+        if attrs.disabled {
+            return;
+        }
+
+        self.types.push(DeclaredType {
+            attrs: attrs.serde,
+            name: self.ident(&item.ident),
+            inner: InnerType::Struct(Struct { fields }),
+            derives: attrs.derives,
+            span: self.span(item.ident.span()),
+            file: self.file,
+        });
+    }
+
+    fn item_enum(&mut self, item: &syn::ItemEnum) {
+        let attrs = self.attributes(&item.attrs);
+
+        self.unsupported_generics(&item.generics);
+
+        let mut variants = Vec::new();
+
+        for variant in &item.variants {
+            let Attributes {
+                serde,
+                default,
+                disabled,
+                ..
+            } = self.attributes(&variant.attrs);
+
+            let inner = match &variant.fields {
+                Fields::Unit => EnumVariantInner::Unit,
+                Fields::Unnamed(fields) => {
+                    let types = fields
+                        .unnamed
+                        .iter()
+                        .map(|field| self.ty(&field.ty))
+                        .collect::<Result<_, _>>();
+
+                    match types {
+                        Ok(types) => EnumVariantInner::Tuple(types),
+                        Err(err) => {
+                            self.errors.push(err);
+                            continue;
+                        }
+                    }
+                }
+                Fields::Named(fields) => EnumVariantInner::Struct(Struct {
+                    fields: self.fields(&fields.named),
+                }),
+            };
+
+            if disabled {
+                continue;
+            }
+
+            variants.push(EnumVariant {
+                attrs: serde,
+                name: self.ident(&variant.ident),
+                inner,
+                default,
+            });
+        }
+
+        if attrs.disabled {
+            return;
+        }
+
+        self.types.push(DeclaredType {
+            attrs: attrs.serde,
+            name: self.ident(&item.ident),
+            inner: InnerType::Enum(Enum { variants }),
+            derives: attrs.derives,
+            span: self.span(item.ident.span()),
+            file: self.file,
+        });
+    }
+
+    fn fields(&mut self, fields: &Punctuated<syn::Field, Token![,]>) -> Vec<StructField<'a>> {
+        let mut lowered = Vec::new();
+
+        for field in fields {
+            let Attributes {
+                serde: attrs,
+                disabled,
+                serde_as,
+                ..
+            } = self.attributes(&field.attrs);
+
+            let Some(ident) = &field.ident else {
+                continue;
+            };
+
+            let ty_span = self.span(field.ty.span());
+            let ty = self
+                .ty(&field.ty)
+                .and_then(|ty| field_type(ty, ty_span, &attrs, serde_as));
+
+            let ty = match ty {
+                Ok(ty) => ty,
+                Err(err) => {
+                    self.errors.push(err);
+                    continue;
+                }
+            };
+
+            if disabled {
+                continue;
+            }
+
+            lowered.push(StructField {
+                attrs,
+                name: self.ident(ident),
+                ty,
+                span: self.span(ident.span()),
+            });
+        }
+
+        lowered
+    }
+
+    pub(crate) fn ty(&self, ty: &syn::Type) -> Result<Type<'a>, Diagnostic> {
+        let unsupported = |span: SynSpan| {
+            Diagnostic::error("unsupported type")
+                .with_span(self.span(span))
+                .with_label("not supported by marshal")
+        };
+
+        match ty {
+            syn::Type::Tuple(tuple) if tuple.elems.is_empty() => Ok(Type::Unit),
+            syn::Type::Tuple(tuple) => Ok(Type::Tuple(
+                tuple
+                    .elems
+                    .iter()
+                    .map(|ty| self.ty(ty))
+                    .collect::<Result<_, _>>()?,
+            )),
+            syn::Type::Paren(paren) => self.ty(&paren.elem),
+            syn::Type::Group(group) => self.ty(&group.elem),
+            // `[T; N]`, a sequence like a `Vec`
+            syn::Type::Array(array) => Ok(Type::Array(Box::new(self.ty(&array.elem)?))),
+            syn::Type::Path(path) if path.qself.is_none() => {
+                let segments = path
+                    .path
+                    .segments
+                    .iter()
+                    .map(|segment| self.ident(&segment.ident))
+                    .collect();
+
+                let args = match path.path.segments.last().map(|segment| &segment.arguments) {
+                    None | Some(PathArguments::None) => Vec::new(),
+                    Some(PathArguments::AngleBracketed(args)) => args
+                        .args
+                        .iter()
+                        .map(|arg| match arg {
+                            GenericArgument::Type(ty) => self.ty(ty),
+                            arg => Err(unsupported(arg.span())),
+                        })
+                        .collect::<Result<_, _>>()?,
+                    Some(arguments @ PathArguments::Parenthesized(_)) => {
+                        return Err(unsupported(arguments.span()))
+                    }
+                };
+
+                let span = self.span(ty.span());
+
+                match (
+                    path_type(TypePath { segments, args }, span)?,
+                    &self.resolver,
+                ) {
+                    (Type::User(path), Some(resolve)) => resolve(path, span),
+                    (ty, _) => Ok(ty),
+                }
+            }
+            ty => Err(unsupported(ty.span())),
+        }
+    }
+
+    pub(crate) fn attributes(&mut self, attrs: &[Attribute]) -> Attributes<'a> {
+        let mut lowered = Attributes::default();
+
+        for attr in attrs {
+            if let Err(err) = self.attribute(&attr.meta, &mut lowered) {
+                let err = self.error(err);
+                self.errors.push(err);
+            }
+        }
+
+        lowered
+    }
+
+    /// The attributes without reporting their errors, for a look at an item before it's lowered
+    pub(crate) fn peek_attributes(&mut self, attrs: &[Attribute]) -> Attributes<'a> {
+        let errors = self.errors.len();
+        let lowered = self.attributes(attrs);
+
+        self.errors.truncate(errors);
+
+        lowered
+    }
+
+    // what's inside `#[...]`, or one of the attributes in a `cfg_attr`
+    fn attribute(&mut self, meta: &Meta, attrs: &mut Attributes<'a>) -> syn::Result<()> {
+        let Some(name) = meta.path().get_ident() else {
+            return Ok(());
+        };
+
+        match name.to_string().as_str() {
+            "serde" => self.serde_attributes(meta.require_list()?, &mut attrs.serde)?,
+            "derive" => {
+                let paths = meta
+                    .require_list()?
+                    .parse_args_with(Punctuated::<syn::Path, Token![,]>::parse_terminated)?;
+
+                // `serde::Serialize` -> `Serialize`
+                for path in paths {
+                    if let Some(last) = path.segments.last() {
+                        attrs.derives.push(self.ident(&last.ident));
+                    }
+                }
+            }
+            "default" => attrs.default = true,
+            // a bare `#[serde_as]` on the container only tells the macro to look at the fields
+            "serde_as" => {
+                if let Meta::List(list) = meta {
+                    list.parse_nested_meta(|option| {
+                        if option.path.is_ident("as") {
+                            let lit: LitStr = option.value()?.parse()?;
+                            attrs.serde_as = Some((self.string(&lit), self.span(lit.span())));
+                        } else {
+                            self.errors.push(
+                                Diagnostic::error(format!(
+                                    "unsupported `serde_as` option `{}`",
+                                    self.path(&option)
+                                ))
+                                .with_span(self.span(option.path.span()))
+                                .with_label("only `as` is supported"),
+                            );
+
+                            skip_value(&option)?;
+                        }
+
+                        Ok(())
+                    })?;
+                }
+            }
+            "cfg" => {
+                let enabled = meta
+                    .require_list()?
+                    .parse_args_with(|input: ParseStream| self.cfg(input))?;
+
+                if !enabled {
+                    attrs.disabled = true;
+                }
+            }
+            "cfg_attr" => {
+                meta.require_list()?.parse_args_with(|input: ParseStream| {
+                    if !self.cfg(input)? {
+                        return input.parse::<proc_macro2::TokenStream>().map(drop);
+                    }
+
+                    // a trailing comma is allowed
+                    while input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+                        let meta: Meta = input.parse()?;
+                        self.attribute(&meta, attrs)?;
+                    }
+
+                    Ok(())
+                })?;
+            }
+            // some attribute we don't care about
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn path(&self, meta: &ParseNestedMeta) -> &'a str {
+        meta.path
+            .get_ident()
+            .map(|ident| self.ident(ident))
+            .unwrap_or_default()
+    }
+
+    // a `cfg` predicate, like `all(feature = "a", not(feature = "b"))`
+    // anything other than features, like `test` or `unix`, is false as we aren't compiling for anything
+    fn cfg(&mut self, input: ParseStream) -> syn::Result<bool> {
+        let name = input.call(syn::Ident::parse_any)?;
+
+        if input.parse::<Option<Token![=]>>()?.is_some() {
+            let value: LitStr = input.parse()?;
+            let value = self.string(&value);
+
+            return Ok(name == "feature" && self.features.iter().any(|feature| feature == value));
+        }
+
+        if !matches!(name.to_string().as_str(), "all" | "any" | "not") {
+            return Ok(false);
+        }
+
+        let content;
+        syn::parenthesized!(content in input);
+
+        let mut values = Vec::new();
+
+        while !content.is_empty() {
+            values.push(self.cfg(&content)?);
+
+            if content.parse::<Option<Token![,]>>()?.is_none() {
+                break;
+            }
+        }
+
+        match (name.to_string().as_str(), &values[..]) {
+            ("all", _) => Ok(values.iter().all(|value| *value)),
+            ("any", _) => Ok(values.iter().any(|value| *value)),
+            ("not", [value]) => Ok(!value),
+            _ => {
+                self.errors.push(
+                    Diagnostic::error("`not` takes exactly one predicate")
+                        .with_span(self.span(name.span()))
+                        .with_label("here"),
+                );
+
+                Ok(false)
+            }
+        }
+    }
+
+    // `serde` was just seen
+    fn serde_attributes(
+        &mut self,
+        list: &MetaList,
+        attrs: &mut Vec<SerdeAttribute<'a>>,
+    ) -> syn::Result<()> {
+        use SerdeAttribute::*;
+
+        list.parse_nested_meta(|meta| {
+            let string = |meta: &ParseNestedMeta| -> syn::Result<&'a str> {
+                let lit: LitStr = meta.value()?.parse()?;
+                Ok(self.string(&lit))
+            };
+
+            let attr = match self.path(&meta) {
+                "skip" => Skip,
+                "skip_serializing" => SkipSerializing,
+                "skip_deserializing" => SkipDeserializing,
+                "skip_serializing_if" => SkipSerializingIf(string(&meta)?),
+                "skip_deserializing_if" => SkipDeserializingIf(string(&meta)?),
+                "serialize_with" => SerializeWith(string(&meta)?),
+                "deserialize_with" => DeserializeWith(string(&meta)?),
+                "with" => With(string(&meta)?),
+                "rename" => Rename(string(&meta)?),
+                "default" if meta.input.peek(Token![=]) => Default(Some(string(&meta)?)),
+                "default" => Default(None),
+                name => {
+                    self.errors.push(
+                        Diagnostic::error(format!("unsupported serde attribute `{name}`"))
+                            .with_span(self.span(meta.path.span()))
+                            .with_label("not supported by marshal"),
+                    );
+
+                    // carry on with the next one
+                    return skip_value(&meta);
+                }
+            };
+
+            attrs.push(attr);
+
+            Ok(())
+        })
+    }
+}
+
+// whatever follows an option we don't understand, `= ...` or `(...)`
+fn skip_value(meta: &ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(Token![=]) {
+        meta.value()?.parse::<syn::Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        meta.input.parse::<proc_macro2::Group>()?;
+    }
+
+    Ok(())
+}
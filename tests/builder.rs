@@ -0,0 +1,152 @@
+// the library API, `Builder` and what it generates
+
+mod common;
+
+use common::*;
+use marshal::codegen::Options;
+use marshal::{Builder, Destination, Source};
+
+#[test]
+fn sources_go_into_one_module() {
+    let builder = Builder::new()
+        .sources([
+            Source::new("a.rs", "struct A { b: B }"),
+            Source::new("b.rs", "struct B { a: u8 }"),
+        ])
+        .options(Options::default());
+
+    let module = generate_with(builder);
+
+    // `B` is declared first, `A` needs it
+    assert!(
+        module.find("class B").unwrap() < module.find("class A").unwrap(),
+        "{module}"
+    );
+}
+
+#[test]
+fn types_are_picked_by_pattern() {
+    let rust = "struct UserA { a: u8 }\nstruct UserB { a: u8 }\nstruct Other { a: u8 }";
+
+    let builder = builder(rust, Options::default())
+        .include(&["User*"])
+        .unwrap()
+        .exclude(&["*B"])
+        .unwrap();
+
+    let lexed = builder.lex();
+    let types = builder.parse(&lexed).unwrap();
+
+    let names: Vec<_> = types[0].iter().map(|decl| decl.name).collect();
+    assert_eq!(names, ["UserA"]);
+
+    let err = Builder::new().include(&["[a"]).unwrap_err();
+    assert!(
+        err.message.starts_with("invalid pattern `[a`"),
+        "{}",
+        err.message
+    );
+}
+
+#[test]
+fn features_enable_cfgs() {
+    let rust = r#"
+        struct A {
+            #[cfg(feature = "extra")]
+            extra: u8,
+            #[cfg_attr(feature = "extra", serde(rename = "renamed"))]
+            plain: u8,
+        }
+    "#;
+
+    let module = generate(rust, &[]);
+    assert!(!module.contains("extra"), "{module}");
+    assert!(!module.contains("renamed"), "{module}");
+
+    let module = generate_with(builder(rust, Options::default()).features(["extra"]));
+    assert!(module.contains("extra: Annotated[int, U8]"), "{module}");
+    assert!(module.contains("renamed"), "{module}");
+}
+
+#[test]
+fn every_sources_errors_are_reported_together() {
+    let builder = Builder::new()
+        .sources([
+            Source::new("a.rs", "struct A { a u8 }"),
+            Source::new("b.rs", "struct B { b: }"),
+        ])
+        .options(Options::default());
+
+    let generated = builder.generate();
+
+    assert!(generated.files.is_empty());
+    assert_eq!(generated.diagnostics.len(), 2);
+
+    let rendered = generated.render_diagnostics();
+    assert!(
+        rendered.find("--> a.rs").unwrap() < rendered.find("--> b.rs").unwrap(),
+        "{rendered}"
+    );
+
+    // the same from `lex` and `parse`
+    let lexed = builder.lex();
+    let errors = builder.parse(&lexed).unwrap_err();
+    assert_eq!(builder.render(&errors), rendered);
+}
+
+#[test]
+fn dir_destinations_need_paths() {
+    let builder = builder("struct A { a: u8 }", Options::default())
+        .destination(Destination::Dir("out".into()));
+
+    let generated = builder.generate();
+
+    assert!(generated.files.is_empty());
+    assert!(
+        generated
+            .render_diagnostics()
+            .contains("`models.rs` has no path to name its module after"),
+        "{}",
+        generated.render_diagnostics()
+    );
+}
+
+#[test]
+fn generated_files_are_written_with_their_directories() {
+    let dir = temp_dir("builder");
+
+    std::fs::write(dir.join("a.rs"), "struct A { a: u8 }").unwrap();
+    std::fs::write(dir.join("b.rs"), "struct B { a: A }").unwrap();
+
+    let out = dir.join("out/nested");
+    let builder = Builder::new()
+        .sources([
+            Source::from_path(dir.join("a.rs")).unwrap(),
+            Source::from_path(dir.join("b.rs")).unwrap(),
+        ])
+        .options(Mode::Direct.options())
+        .destination(Destination::Dir(out.clone()));
+
+    let generated = builder.generate();
+    assert!(generated.diagnostics.is_empty());
+
+    generated.write().unwrap();
+
+    assert!(std::fs::read_to_string(out.join("a.py"))
+        .unwrap()
+        .contains("class A"));
+    assert!(std::fs::read_to_string(out.join("b.py"))
+        .unwrap()
+        .contains("from .a import A"));
+}
+
+#[test]
+fn unnamed_files_are_not_written() {
+    let builder = builder("struct A { a: u8 }", Options::default());
+    let generated = builder.generate();
+
+    assert_eq!(generated.files[0].path, None);
+
+    // there's nowhere to write it, which isn't an error
+    generated.write().unwrap();
+}
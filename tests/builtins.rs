@@ -0,0 +1,154 @@
+// the built-in mappings for std, chrono, time, uuid and url, in the formats their `Serialize` impls produce
+
+mod common;
+
+use common::*;
+
+const MODELS: &str = r#"
+    #[derive(Serialize, Deserialize)]
+    struct Event {
+        at: chrono::DateTime<Utc>,
+        local: NaiveDateTime,
+        day: NaiveDate,
+        id: Uuid,
+        link: url::Url,
+        ip: IpAddr,
+        v4: Ipv4Addr,
+        v6: std::net::Ipv6Addr,
+        socket: SocketAddr,
+        path: PathBuf,
+        took: Duration,
+    }
+"#;
+
+// each of these is a `datetime.datetime` with hooks of its own, which only direct mode can tell apart
+const TIMES: &str = r#"
+    #[derive(Serialize, Deserialize)]
+    struct Times {
+        at: DateTime<Utc>,
+        offset: time::OffsetDateTime,
+        created: std::time::SystemTime,
+    }
+"#;
+
+const EVENT: &str = r#"{
+    "at": "2024-05-01T12:30:00.250Z",
+    "local": "2024-05-01T12:30:00",
+    "day": "2024-05-01",
+    "id": "67e55044-10b1-426f-9247-bb680e5fe0c8",
+    "link": "https://example.com/",
+    "ip": "::1",
+    "v4": "127.0.0.1",
+    "v6": "::1",
+    "socket": "127.0.0.1:80",
+    "path": "a/b",
+    "took": {"secs": 1, "nanos": 500000000}
+}"#;
+
+#[test]
+fn builtin_types_round_trip() {
+    let payload = EVENT.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    for mode in MODES {
+        let module = generate(MODELS, mode.args());
+
+        assert_round_trips(&module, "Event", &[&payload], mode);
+    }
+}
+
+#[test]
+fn builtin_types_decode_to_python_values() {
+    let module = generate(MODELS, &[]);
+
+    let output = python(
+        &module,
+        &format!(
+            "import models\n\
+             e = models.Event.deserialize(__import__('json').loads('''{EVENT}'''))\n\
+             print(repr(e.at))\n\
+             print(repr(e.day))\n\
+             print(repr(e.id))\n\
+             print(repr(e.ip), repr(e.v4))\n\
+             print(repr(e.took))\n"
+        ),
+    );
+
+    assert_eq!(
+        output.lines().collect::<Vec<_>>(),
+        [
+            "datetime.datetime(2024, 5, 1, 12, 30, 0, 250000, tzinfo=datetime.timezone.utc)",
+            "datetime.date(2024, 5, 1)",
+            "UUID('67e55044-10b1-426f-9247-bb680e5fe0c8')",
+            "IPv6Address('::1') IPv4Address('127.0.0.1')",
+            "datetime.timedelta(seconds=1, microseconds=500000)",
+        ]
+    );
+}
+
+#[test]
+fn direct_mode_tells_datetime_formats_apart() {
+    let module = generate(TIMES, Mode::Direct.args());
+
+    let payload = r#"{"at": "2024-05-01T12:30:00.250Z", "offset": "2024-05-01 12:30:00.25 +02:00:00", "created": {"secs_since_epoch": 1714566600, "nanos_since_epoch": 0}}"#;
+
+    assert_round_trips(&module, "Times", &[payload], Mode::Direct);
+
+    let output = python(
+        &module,
+        &format!(
+            "import json, models\n\
+             t = models.Times.from_dict(json.loads('{payload}'))\n\
+             print(t.offset.utcoffset(), t.created.timestamp())\n"
+        ),
+    );
+
+    assert_eq!(output.trim(), "2:00:00 1714566600.0");
+}
+
+#[test]
+fn reflection_needs_one_hook_per_class() {
+    let errors = errors(TIMES, &[]);
+
+    assert!(
+        errors.contains(
+            "`time::OffsetDateTime` and `DateTime` are both mapped to `datetime.datetime`, with different hooks"
+        ),
+        "{errors}"
+    );
+}
+
+#[test]
+fn declared_and_mapped_types_override_builtins() {
+    let rust = r#"
+        struct Duration { days: u32 }
+        struct A { took: Duration, id: Uuid }
+    "#;
+
+    let module = generate(rust, &["--type", "Uuid=str"]);
+
+    assert!(module.contains("took: Duration"), "{module}");
+    assert!(module.contains("id: str"), "{module}");
+    assert!(!module.contains("import uuid"), "{module}");
+}
+
+#[test]
+fn builtin_defaults() {
+    let module = generate(
+        r#"
+        struct A {
+            #[serde(default)]
+            id: Uuid,
+            #[serde(default)]
+            took: Duration,
+        }
+        "#,
+        &[],
+    );
+
+    let output = python(&module, "import models\nprint(models.A.deserialize({}))\n");
+
+    assert_eq!(
+        output.trim(),
+        "A(id=UUID('00000000-0000-0000-0000-000000000000'), took=datetime.timedelta(0))"
+    );
+}
@@ -0,0 +1,180 @@
+// byte buffers as `bytes`, as numbers for JSON, left alone for binary formats, or as base64
+
+mod common;
+
+use common::*;
+
+const MODELS: &str = r#"
+    #[serde_as]
+    #[derive(Serialize, Deserialize)]
+    struct Blob {
+        data: bytes::Bytes,
+        buf: serde_bytes::ByteBuf,
+        #[serde(with = "serde_bytes")]
+        raw: Vec<u8>,
+        numbers: Vec<u8>,
+        #[serde_as(as = "Base64")]
+        b64: Vec<u8>,
+        #[serde_as(as = "Option<Base64<UrlSafe, Unpadded>>")]
+        url: Option<Vec<u8>>,
+        #[serde_as(as = "Vec<Base64<Standard, Unpadded>>")]
+        chunks: Vec<Vec<u8>>,
+    }
+"#;
+
+const RAW: &[&str] = &["--raw-bytes"];
+
+#[test]
+fn byte_buffers_are_bytes() {
+    let module = generate(MODELS, &[]);
+
+    for field in [
+        "data: Annotated[bytes, BYTE_ARRAY]",
+        "buf: Annotated[bytes, BYTE_ARRAY]",
+        "raw: Annotated[bytes, BYTE_ARRAY]",
+        "numbers: list[Annotated[int, U8]]",
+        "b64: Annotated[bytes, BASE64]",
+        "url: Annotated[bytes, BASE64_URL_SAFE_UNPADDED] | None",
+        "chunks: list[Annotated[bytes, BASE64_UNPADDED]]",
+    ] {
+        assert!(module.contains(field), "{field} in\n{module}");
+    }
+
+    let module = generate(MODELS, RAW);
+
+    assert!(
+        module.contains("data: Annotated[bytes, RAW_BYTES]"),
+        "{module}"
+    );
+    assert!(module.contains("b64: Annotated[bytes, BASE64]"), "{module}");
+}
+
+#[test]
+fn bare_names_are_byte_buffers_unless_declared() {
+    let module = generate(
+        r#"
+        struct A {
+            data: Bytes,
+            buf: Option<ByteBuf>,
+            #[serde(with = "serde_bytes")]
+            raw: ByteBuf,
+        }
+        "#,
+        &[],
+    );
+
+    for field in [
+        "data: Annotated[bytes, BYTE_ARRAY]",
+        "buf: Annotated[bytes, BYTE_ARRAY] | None",
+        "raw: Annotated[bytes, BYTE_ARRAY]",
+    ] {
+        assert!(module.contains(field), "{field} in\n{module}");
+    }
+
+    // the user's own
+    let module = generate(
+        r#"
+        struct Bytes { len: u32 }
+        struct A { data: Bytes, buf: bytes::Bytes }
+        "#,
+        &[],
+    );
+
+    assert!(module.contains("class Bytes:"), "{module}");
+    assert!(module.contains("data: Bytes"), "{module}");
+    assert!(
+        module.contains("buf: Annotated[bytes, BYTE_ARRAY]"),
+        "{module}"
+    );
+}
+
+#[test]
+fn json_bytes_round_trip() {
+    // `?>>` is `Pz4-` URL safe, `Pz4+` standard
+    let payloads = [
+        r#"{"data": [0, 255], "buf": [], "raw": [104, 105], "numbers": [1], "b64": "aGk=", "url": "Pz4-", "chunks": ["Pz4", ""]}"#,
+        r#"{"data": [], "buf": [1], "raw": [], "numbers": [], "b64": "", "url": null, "chunks": []}"#,
+    ];
+
+    for mode in MODES {
+        let module = generate(MODELS, mode.args());
+
+        assert_round_trips(&module, "Blob", &payloads, mode);
+
+        let decode = mode.decode("Blob");
+
+        let output = python(
+            &module,
+            &format!(
+                "import json, models\n\
+                 blob = {decode}(json.loads(r'''{}'''))\n\
+                 print(blob.data, blob.raw, blob.b64, blob.url, blob.chunks)\n",
+                payloads[0]
+            ),
+        );
+
+        assert_eq!(
+            output.trim(),
+            r"b'\x00\xff' b'hi' b'hi' b'?>>' [b'?>', b'']"
+        );
+    }
+}
+
+#[test]
+fn raw_bytes_are_left_for_binary_formats() {
+    for mode in MODES {
+        let (decode, encode) = (mode.decode("Blob"), mode.encode("Blob"));
+
+        let output = python(
+            &generate(MODELS, &[mode.args(), RAW].concat()),
+            &format!(
+                "import models\n\
+                 data = {{'data': b'\\x00', 'buf': b'', 'raw': b'hi', 'numbers': [], 'b64': 'aGk=', 'url': None, 'chunks': []}}\n\
+                 print({encode}({decode}(data)) == data)\n"
+            ),
+        );
+
+        assert_eq!(output.trim(), "True");
+    }
+}
+
+#[test]
+fn bad_bytes_are_rejected() {
+    for mode in MODES {
+        let decode = mode.decode("Blob");
+
+        let output = python(
+            &generate(MODELS, mode.args()),
+            &format!(
+                "import models\n\
+                 valid = {{'data': [], 'buf': [], 'raw': [], 'numbers': [], 'b64': '', 'url': None, 'chunks': []}}\n\
+                 for change in [{{'data': [256]}}, {{'data': 'aGk='}}, {{'b64': 'a$=='}}, {{'url': 'Pz4+'}}]:\n\
+                 \x20   try:\n\
+                 \x20       {decode}({{**valid, **change}})\n\
+                 \x20       print('accepted')\n\
+                 \x20   except ValueError:\n\
+                 \x20       print('rejected')\n"
+            ),
+        );
+
+        assert_eq!(output.trim(), "rejected\nrejected\nrejected\nrejected");
+    }
+}
+
+#[test]
+fn other_serde_as_conversions_are_errors() {
+    let errors = errors(
+        r#"
+        struct A {
+            #[serde_as(as = "DisplayFromStr")]
+            n: u32,
+        }
+        "#,
+        &[],
+    );
+
+    assert!(
+        errors.contains("unsupported `serde_as` conversion `DisplayFromStr`"),
+        "{errors}"
+    );
+}
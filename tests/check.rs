@@ -0,0 +1,94 @@
+// `generate --check`, diffing against what's on disk instead of writing
+
+mod common;
+
+use common::*;
+
+const MODELS: &str = "struct Point { x: i32, y: i32 }\n";
+
+#[test]
+fn up_to_date_files_pass() {
+    let dir = temp_dir("check");
+    std::fs::write(dir.join("models.rs"), MODELS).unwrap();
+
+    let (code, _, stderr) = marshal(&dir, &["generate", "models.rs", "-o", "models.py"]);
+    assert_eq!(code, 0, "{stderr}");
+
+    let (code, stdout, stderr) = marshal(
+        &dir,
+        &["generate", "models.rs", "-o", "models.py", "--check"],
+    );
+
+    assert_eq!(code, 0, "{stderr}");
+    assert_eq!(stdout, "");
+}
+
+#[test]
+fn stale_files_are_diffed_and_left_alone() {
+    let dir = temp_dir("check");
+    std::fs::write(dir.join("models.rs"), MODELS).unwrap();
+
+    let (code, _, stderr) = marshal(&dir, &["generate", "models.rs", "-o", "models.py"]);
+    assert_eq!(code, 0, "{stderr}");
+
+    std::fs::write(
+        dir.join("models.rs"),
+        "struct Point { x: i32, y: i32, z: i32 }\n",
+    )
+    .unwrap();
+    let before = std::fs::read_to_string(dir.join("models.py")).unwrap();
+
+    let (code, stdout, stderr) = marshal(
+        &dir,
+        &["generate", "models.rs", "-o", "models.py", "--check"],
+    );
+
+    assert_eq!(code, 4);
+    assert!(
+        stdout.starts_with("--- a/models.py\n+++ b/models.py\n"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("\n+    z: Annotated[int, I32]\n"),
+        "{stdout}"
+    );
+    assert_eq!(
+        stderr,
+        "error: 1 file out of date\n  = note: run `marshal generate` without `--check` to update them\n"
+    );
+
+    assert_eq!(
+        std::fs::read_to_string(dir.join("models.py")).unwrap(),
+        before
+    );
+}
+
+#[test]
+fn missing_files_are_stale() {
+    let dir = temp_dir("check");
+    std::fs::write(dir.join("a.rs"), MODELS).unwrap();
+    std::fs::write(dir.join("b.rs"), "struct Line { a: i32 }\n").unwrap();
+
+    let (code, stdout, stderr) =
+        marshal(&dir, &["generate", "a.rs", "b.rs", "-d", "out", "--check"]);
+
+    assert_eq!(code, 4, "{stderr}");
+    assert!(stdout.contains("+++ b/out/a.py"), "{stdout}");
+    assert!(stdout.contains("+++ b/out/b.py"), "{stdout}");
+    assert!(stderr.starts_with("error: 2 files out of date"), "{stderr}");
+    assert!(!dir.join("out").exists());
+}
+
+#[test]
+fn check_needs_files_to_compare() {
+    let dir = temp_dir("check");
+    std::fs::write(dir.join("models.rs"), MODELS).unwrap();
+
+    let (code, _, stderr) = marshal(&dir, &["generate", "models.rs", "--check"]);
+
+    assert_eq!(code, 2);
+    assert!(
+        stderr.contains("`--check` needs files to compare against"),
+        "{stderr}"
+    );
+}
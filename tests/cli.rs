@@ -0,0 +1,296 @@
+// the `marshal` binary's subcommands, outputs and exit codes
+
+mod common;
+
+use std::io::Write;
+use std::path::Path;
+
+use common::*;
+
+const USER: &str = r#"
+    #[derive(Serialize, Deserialize)]
+    pub struct User {
+        pub name: String,
+        pub address: Address,
+        pub role: Role,
+    }
+"#;
+
+const ADDRESS: &str = r#"
+    #[derive(Serialize, Deserialize)]
+    pub struct Address {
+        pub city: String,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub enum Role {
+        Admin,
+        Guest { until: u64 },
+    }
+"#;
+
+// a directory holding `user.rs` and `address.rs`
+fn inputs() -> std::path::PathBuf {
+    let dir = temp_dir("cli");
+
+    std::fs::write(dir.join("user.rs"), USER).unwrap();
+    std::fs::write(dir.join("address.rs"), ADDRESS).unwrap();
+
+    dir
+}
+
+fn read(dir: &Path, name: &str) -> String {
+    std::fs::read_to_string(dir.join(name)).unwrap()
+}
+
+#[test]
+fn generate_writes_to_stdout_by_default() {
+    let dir = inputs();
+
+    let (code, stdout, stderr) = marshal(&dir, &["generate", "address.rs"]);
+
+    assert_eq!(code, 0, "{stderr}");
+    assert!(stdout.contains("class Address"), "{stdout}");
+    assert!(stdout.contains("from lib.marshal import *"), "{stdout}");
+}
+
+#[test]
+fn generate_writes_one_module_with_output() {
+    let dir = inputs();
+
+    let (code, stdout, stderr) = marshal(
+        &dir,
+        &["generate", "*.rs", "-o", "models.py", "--runtime", "write"],
+    );
+
+    assert_eq!(code, 0, "{stderr}");
+    assert_eq!(stdout, "");
+
+    let models = read(&dir, "models.py");
+
+    assert!(models.contains("class User"), "{models}");
+    assert!(models.contains("class Address"), "{models}");
+    assert!(dir.join("marshal_runtime.py").is_file());
+}
+
+#[test]
+fn generate_creates_the_output_directory() {
+    let dir = inputs();
+
+    let (code, _, stderr) = marshal(
+        &dir,
+        &[
+            "generate",
+            "*.rs",
+            "-o",
+            "out/models.py",
+            "--runtime",
+            "write",
+        ],
+    );
+
+    assert_eq!(code, 0, "{stderr}");
+    assert!(read(&dir, "out/models.py").contains("class User"));
+    assert!(dir.join("out/marshal_runtime.py").is_file());
+}
+
+#[test]
+fn out_dir_modules_import_each_others_types() {
+    let dir = inputs();
+
+    for direct in [false, true] {
+        let out = dir.join(if direct { "direct" } else { "reflection" });
+        let out = out.to_str().unwrap();
+
+        let mut args = vec!["generate", "user.rs", "address.rs", "-d", out];
+        args.extend(["--runtime", "write"]);
+
+        if direct {
+            args.push("--direct");
+        }
+
+        let (code, _, stderr) = marshal(&dir, &args);
+        assert_eq!(code, 0, "{stderr}");
+
+        let user = read(Path::new(out), "user.py");
+        let address = read(Path::new(out), "address.py");
+
+        if direct {
+            assert!(
+                user.contains(
+                    "from .address import Address, Role, decode_Address, decode_Role, encode_Address, encode_Role\n"
+                ),
+                "{user}"
+            );
+        } else {
+            assert!(
+                user.contains("    from .address import Address, Role\n"),
+                "{user}"
+            );
+            assert!(
+                user.contains("    from address import Address, Role\n"),
+                "{user}"
+            );
+        }
+
+        assert!(!user.contains("class Address"), "{user}");
+        assert!(!address.contains("from .user"), "{address}");
+
+        let (decode, encode) = match direct {
+            false => ("user.User.deserialize", "user.asdict"),
+            true => ("user.User.from_dict", "user.User.to_dict"),
+        };
+
+        let payload = r#"{"name": "a", "address": {"city": "b"}, "role": {"Guest": {"until": 3}}}"#;
+        let script = format!(
+            "import json\n\
+             from {{package}} import user\n\
+             value = {decode}(json.loads('{payload}'))\n\
+             print(json.dumps({encode}(value)))\n"
+        );
+
+        let files = [
+            ("pkg/__init__.py", String::new()),
+            ("pkg/user.py", user.clone()),
+            ("pkg/address.py", address.clone()),
+            ("pkg/marshal_runtime.py", RUNTIME.to_string()),
+            ("user.py", user),
+            ("address.py", address),
+            ("marshal_runtime.py", RUNTIME.to_string()),
+        ];
+        let files: Vec<_> = files
+            .iter()
+            .map(|(name, contents)| (*name, contents.as_str()))
+            .collect();
+
+        // as a package, and as top-level modules
+        for script in [
+            script.replace("{package}", "pkg"),
+            script.replace("from {package} import user", "import user"),
+        ] {
+            assert_eq!(python_files(&files, &script).trim(), payload);
+        }
+    }
+}
+
+#[test]
+fn modules_prefer_their_own_types() {
+    let dir = temp_dir("cli");
+
+    std::fs::write(dir.join("a.rs"), "struct Id { a: u8 }\nstruct A { id: Id }").unwrap();
+    std::fs::write(dir.join("b.rs"), "struct Id { b: u8 }\nstruct B { id: Id }").unwrap();
+
+    let (code, _, stderr) = marshal(&dir, &["generate", "a.rs", "b.rs", "-d", "out"]);
+
+    assert_eq!(code, 0, "{stderr}");
+    assert!(!read(&dir.join("out"), "a.py").contains("from .b"));
+    assert!(!read(&dir.join("out"), "b.py").contains("from .a"));
+}
+
+#[test]
+fn check_reports_without_writing() {
+    let dir = inputs();
+
+    let (code, stdout, stderr) = marshal(&dir, &["check", "user.rs", "address.rs"]);
+
+    assert_eq!(code, 0, "{stderr}");
+    assert_eq!(stdout, "");
+    assert_eq!(stderr, "ok: 3 types in 2 files\n");
+    assert!(!dir.join("user.py").exists());
+
+    // `user.rs` alone can't generate, `Address` isn't declared anywhere
+    let (code, _, stderr) = marshal(&dir, &["check", "user.rs"]);
+
+    assert_eq!(code, 1);
+    assert!(stderr.contains("cannot find type `Address`"), "{stderr}");
+}
+
+#[test]
+fn validate_only_parses() {
+    let dir = inputs();
+
+    let (code, _, stderr) = marshal(&dir, &["validate", "user.rs"]);
+
+    assert_eq!(code, 0, "{stderr}");
+    assert_eq!(stderr, "ok: 1 type in 1 file\n");
+
+    let (code, _, stderr) = marshal(&dir, &["-q", "validate", "user.rs"]);
+
+    assert_eq!(code, 0);
+    assert_eq!(stderr, "");
+}
+
+#[test]
+fn dump_prints_declarations_and_tokens() {
+    let dir = inputs();
+
+    let (code, stdout, stderr) = marshal(&dir, &["dump", "address.rs"]);
+
+    assert_eq!(code, 0, "{stderr}");
+    assert!(stdout.starts_with("// address.rs\n"), "{stdout}");
+    assert!(stdout.contains("name: \"Address\""), "{stdout}");
+
+    let (code, stdout, _) = marshal(&dir, &["dump", "--tokens", "address.rs"]);
+
+    assert_eq!(code, 0);
+    assert!(stdout.contains("3:5 "), "{stdout}");
+}
+
+#[test]
+fn stdin_is_dash() {
+    let dir = inputs();
+
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_marshal"))
+        .args(["validate", "-"])
+        .current_dir(&dir)
+        .stdin(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(ADDRESS.as_bytes())
+        .unwrap();
+
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "ok: 2 types in 1 file\n"
+    );
+}
+
+#[test]
+fn exit_codes_say_what_went_wrong() {
+    let dir = inputs();
+
+    std::fs::write(dir.join("broken.rs"), "struct A { a u32 }").unwrap();
+
+    // invalid inputs
+    let (code, _, stderr) = marshal(&dir, &["validate", "broken.rs"]);
+    assert_eq!(code, 1);
+    assert!(stderr.contains("--> broken.rs:1:"), "{stderr}");
+
+    // usage
+    let (code, _, stderr) = marshal(&dir, &["validate"]);
+    assert_eq!(code, 2);
+    assert!(stderr.starts_with("error: no inputs"), "{stderr}");
+
+    let (code, _, _) = marshal(&dir, &["generate", "user.rs", "--runtime", "write"]);
+    assert_eq!(code, 2);
+
+    let (code, _, _) = marshal(&dir, &["validate", "nothing*.rs"]);
+    assert_eq!(code, 2);
+
+    // io
+    let (code, _, stderr) = marshal(&dir, &["validate", "missing.rs"]);
+    assert_eq!(code, 3);
+    assert!(
+        stderr.starts_with("error: cannot read `missing.rs`"),
+        "{stderr}"
+    );
+}
@@ -0,0 +1,125 @@
+// `with`, `serialize_with` and `deserialize_with` bound to Python callables
+
+mod common;
+
+use common::*;
+
+const MODELS: &str = r#"
+    #[derive(Serialize, Deserialize)]
+    struct Key {
+        #[serde(with = "crate::hex")]
+        id: Vec<u8>,
+        #[serde(serialize_with = "upper::serialize", deserialize_with = "lower")]
+        name: String,
+        #[serde(default, with = "hex")]
+        backup: Vec<u8>,
+        #[serde(skip_serializing, deserialize_with = "lower")]
+        alias: String,
+    }
+"#;
+
+const HOOKS: &str = "\
+def to_hex(value):
+    return bytes(value).hex()
+
+def from_hex(data):
+    return list(bytes.fromhex(data))
+
+def upper(value):
+    return value.upper()
+
+def lower(data):
+    return data.lower()
+";
+
+const CODECS: &[&str] = &[
+    // matched by suffix, like type mappings, so this is `crate::hex` too
+    "--serialize-with",
+    "hex=hooks.to_hex",
+    "--deserialize-with",
+    "hex=hooks.from_hex",
+    "--serialize-with",
+    "upper=hooks.upper",
+    "--deserialize-with",
+    "lower=hooks.lower",
+];
+
+#[test]
+fn hooks_are_imported_and_used_both_ways() {
+    for mode in MODES {
+        let module = generate(MODELS, &[mode.args(), CODECS].concat());
+
+        assert!(module.contains("\nimport hooks\n"), "{module}");
+
+        let (decode, encode) = (mode.decode("Key"), mode.encode("Key"));
+
+        let output = python_files(
+            &[
+                ("models.py", &module),
+                ("hooks.py", HOOKS),
+                ("lib/marshal.py", RUNTIME),
+            ],
+            &format!(
+                "import json, models\n\
+                 key = {decode}({{'id': 'cafe', 'name': 'MiXed', 'alias': 'AB'}})\n\
+                 print(key.id, key.name, key.backup, key.alias)\n\
+                 print(json.dumps({encode}(key)))\n"
+            ),
+        );
+
+        assert_eq!(
+            output.lines().collect::<Vec<_>>(),
+            [
+                "[202, 254] mixed [] ab",
+                r#"{"id": "cafe", "name": "MIXED", "backup": ""}"#,
+            ],
+            "{mode:?}"
+        );
+    }
+}
+
+#[test]
+fn unregistered_paths_are_errors() {
+    let errors = errors(MODELS, &[]);
+
+    assert!(
+        errors.contains(r#"no Python equivalent for `with = "crate::hex"`"#),
+        "{errors}"
+    );
+    assert!(
+        errors.contains(
+            "register them with `--serialize-with crate::hex=<callable> --deserialize-with crate::hex=<callable>`"
+        ),
+        "{errors}"
+    );
+}
+
+#[test]
+fn each_side_needs_its_own_hook() {
+    let rust = r#"struct A { #[serde(serialize_with = "upper::serialize")] name: String }"#;
+
+    let errors = errors(rust, &["--deserialize-with", "upper=hooks.lower"]);
+
+    assert!(
+        errors.contains(r#"no Python equivalent for `serialize_with = "upper::serialize"`"#),
+        "{errors}"
+    );
+    assert!(
+        errors.contains("register one with `--serialize-with upper::serialize=<callable>`"),
+        "{errors}"
+    );
+}
+
+#[test]
+fn skipped_sides_need_no_hook() {
+    let rust = r#"
+        struct A {
+            #[serde(skip_deserializing, default, serialize_with = "upper")]
+            name: String,
+        }
+    "#;
+
+    let module = generate(rust, &["--serialize-with", "upper=hooks.upper"]);
+
+    assert!(module.contains("import hooks"), "{module}");
+}
@@ -0,0 +1,241 @@
+// helpers shared by the integration tests, generating Python from Rust with the `marshal` binary
+// or the library's `Builder`, and running it
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use marshal::codegen::{self, Options};
+use marshal::{Builder, Source};
+
+/// The runtime, which generated modules import as `lib.marshal` unless they're told otherwise
+pub const RUNTIME: &str = include_str!("../../lib/marshal.py");
+
+/// How a module was generated, which decides how scripts convert with it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    Reflection,
+    Direct,
+}
+
+/// Both modes, for tests which hold for either
+pub const MODES: [Mode; 2] = [Mode::Reflection, Mode::Direct];
+
+impl Mode {
+    /// The command line options generating in this mode
+    pub fn args(self) -> &'static [&'static str] {
+        match self {
+            Mode::Reflection => &[],
+            Mode::Direct => &["--direct"],
+        }
+    }
+
+    /// The library's options generating in this mode
+    pub fn options(self) -> Options {
+        let mode = match self {
+            Mode::Reflection => codegen::Mode::Reflection,
+            Mode::Direct => codegen::Mode::Direct,
+        };
+
+        Options {
+            mode,
+            ..Options::default()
+        }
+    }
+
+    /// The Python function deserializing data into `class`
+    pub fn decode(self, class: &str) -> String {
+        match self {
+            Mode::Reflection => format!("models.{class}.deserialize"),
+            Mode::Direct => format!("models.{class}.from_dict"),
+        }
+    }
+
+    /// The Python function serializing a value of `class` into data
+    pub fn encode(self, class: &str) -> String {
+        match self {
+            Mode::Reflection => "models.asdict".to_string(),
+            Mode::Direct => format!("models.{class}.to_dict"),
+        }
+    }
+}
+
+/// The module generated from `rust` with the command line options `args`, panicking with what `marshal` printed if it fails
+pub fn generate(rust: &str, args: &[&str]) -> String {
+    let dir = temp_dir("generate");
+    let (code, _, stderr) = run(&dir, rust, args);
+
+    assert_eq!(code, 0, "marshal failed\n{stderr}");
+
+    let module = std::fs::read_to_string(dir.join("models.py")).unwrap();
+
+    let _ = std::fs::remove_dir_all(&dir);
+
+    module
+}
+
+/// What `marshal` printed failing on `rust` with the command line options `args`, panicking if it succeeds
+pub fn errors(rust: &str, args: &[&str]) -> String {
+    let dir = temp_dir("errors");
+    let (code, _, stderr) = run(&dir, rust, args);
+
+    assert_ne!(
+        code,
+        0,
+        "expected errors, generated\n{}",
+        std::fs::read_to_string(dir.join("models.py")).unwrap()
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+
+    stderr
+}
+
+/// A builder reading `rust` as `models.rs`
+pub fn builder(rust: &str, options: Options) -> Builder {
+    Builder::new()
+        .source(Source::new("models.rs", rust))
+        .options(options)
+}
+
+/// The module generated from `builder`, panicking with the diagnostics if there are any
+pub fn generate_with(builder: Builder) -> String {
+    let generated = builder.generate();
+
+    assert!(
+        generated.diagnostics.is_empty(),
+        "{}",
+        generated.render_diagnostics()
+    );
+
+    generated.files[0].contents.clone()
+}
+
+/// The rendered diagnostics from `builder`, panicking if there are none
+pub fn errors_with(builder: Builder) -> String {
+    let generated = builder.generate();
+
+    assert!(
+        !generated.diagnostics.is_empty(),
+        "expected errors, generated\n{}",
+        generated.files[0].contents
+    );
+
+    generated.render_diagnostics()
+}
+
+// `marshal` generating `models.py` from `rust` in `dir`
+fn run(dir: &Path, rust: &str, args: &[&str]) -> (i32, String, String) {
+    std::fs::write(dir.join("models.rs"), rust).unwrap();
+
+    // the options last, where flags taking an optional value can't swallow the input
+    let args: Vec<_> = ["generate", "models.rs", "-o", "models.py"]
+        .iter()
+        .chain(args)
+        .copied()
+        .collect();
+
+    marshal(dir, &args)
+}
+
+/// A new empty directory for a test
+pub fn temp_dir(name: &str) -> PathBuf {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    let dir = std::env::temp_dir().join(format!(
+        "marshal-test-{}-{}-{name}",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    dir
+}
+
+/// What `script` prints, run next to `module` as `models.py` and the runtime, panicking if it fails
+pub fn python(module: &str, script: &str) -> String {
+    python_files(
+        &[("models.py", module), ("lib/marshal.py", RUNTIME)],
+        script,
+    )
+}
+
+/// What `script` prints, run next to `files`, panicking if it fails
+pub fn python_files(files: &[(&str, &str)], script: &str) -> String {
+    let dir = temp_dir("python");
+
+    for (name, contents) in files {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    std::fs::write(dir.join("main.py"), script).unwrap();
+
+    let output = Command::new("python3")
+        .arg("main.py")
+        .current_dir(&dir)
+        .output()
+        .expect("the tests run the generated code with `python3`");
+
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+
+    assert!(
+        output.status.success(),
+        "python failed\n--- stdout\n{stdout}\n--- stderr\n{stderr}"
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+
+    stdout
+}
+
+/// A script printing each payload deserialized into `class` and serialized back, as JSON
+pub fn round_trip_script(class: &str, payloads: &[&str], mode: Mode) -> String {
+    let (decode, encode) = (mode.decode(class), mode.encode(class));
+
+    let payloads = payloads
+        .iter()
+        .map(|payload| format!("    r'''{payload}''',\n"))
+        .collect::<String>();
+
+    format!(
+        "import json\nimport models\n\nfor payload in [\n{payloads}]:\n    data = json.loads(payload)\n    value = {decode}(data)\n    print(json.dumps({encode}(value), sort_keys=True))\n"
+    )
+}
+
+/// Asserts every payload survives a round trip through `class`, compared as JSON
+pub fn assert_round_trips(module: &str, class: &str, payloads: &[&str], mode: Mode) {
+    let output = python(module, &round_trip_script(class, payloads, mode));
+
+    let got: Vec<serde_json::Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    let expected: Vec<serde_json::Value> = payloads
+        .iter()
+        .map(|payload| serde_json::from_str(payload).unwrap())
+        .collect();
+
+    assert_eq!(got, expected, "\n{module}");
+}
+
+/// The exit code, stdout and stderr of the `marshal` binary run in `dir`
+pub fn marshal(dir: &Path, args: &[&str]) -> (i32, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_marshal"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+
+    (
+        output.status.code().unwrap(),
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
@@ -0,0 +1,145 @@
+// `marshal.toml`, and the command line overriding it
+
+mod common;
+
+use common::*;
+
+const MODELS: &str = r#"
+    #[derive(Serialize, Deserialize)]
+    struct Event {
+        id: Uuid,
+        #[serde(default = "default_owner")]
+        owner: String,
+        tags: HashSet<String>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Internal { a: u8 }
+"#;
+
+const CONFIG: &str = r#"
+inputs = ["src/*.rs"]
+output = "python/models.py"
+exclude = ["Internal"]
+
+[types]
+Uuid = "str"
+
+[python]
+runtime = "inline"
+kw-only = true
+frozen-sets = true
+
+[python.defaults]
+default_owner = "lambda: 'root'"
+"#;
+
+fn project(config: &str) -> std::path::PathBuf {
+    let dir = temp_dir("config");
+
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    std::fs::create_dir_all(dir.join("python")).unwrap();
+    std::fs::write(dir.join("src/models.rs"), MODELS).unwrap();
+    std::fs::write(dir.join("marshal.toml"), config).unwrap();
+
+    dir
+}
+
+#[test]
+fn marshal_on_its_own_does_what_the_config_says() {
+    let dir = project(CONFIG);
+
+    let (code, stdout, stderr) = marshal(&dir, &[]);
+
+    assert_eq!(code, 0, "{stderr}");
+    assert_eq!(stdout, "");
+
+    let module = std::fs::read_to_string(dir.join("python/models.py")).unwrap();
+
+    assert!(module.contains("@dataclass(kw_only=True)"), "{module}");
+    assert!(module.contains("tags: frozenset[str]"), "{module}");
+    assert!(!module.contains("class Internal"), "{module}");
+    assert!(module.contains("# marshal runtime, protocol version"));
+
+    let output = python(
+        &module,
+        "import models\n\
+         event = models.Event.deserialize({'id': 'x', 'tags': ['a']})\n\
+         print(event.owner, event.tags)\n",
+    );
+    assert_eq!(output.trim(), "root frozenset({'a'})");
+}
+
+#[test]
+fn the_command_line_overrides_the_config() {
+    let dir = project(CONFIG);
+
+    let (code, stdout, stderr) = marshal(
+        &dir,
+        &[
+            "generate",
+            "-o",
+            "-",
+            "--kw-only=false",
+            "--include",
+            "Internal",
+            "--exclude",
+            "Nothing",
+        ],
+    );
+
+    assert_eq!(code, 0, "{stderr}");
+    assert!(stdout.contains("class Internal"), "{stdout}");
+    assert!(!stdout.contains("class Event"), "{stdout}");
+    assert!(stdout.contains("@dataclass\nclass Internal"), "{stdout}");
+}
+
+#[test]
+fn paths_are_relative_to_the_config() {
+    let dir = project(CONFIG);
+    let elsewhere = temp_dir("config");
+
+    let config = dir.join("marshal.toml");
+    let (code, _, stderr) = marshal(&elsewhere, &["-c", config.to_str().unwrap()]);
+
+    assert_eq!(code, 0, "{stderr}");
+    assert!(dir.join("python/models.py").is_file());
+}
+
+#[test]
+fn a_dash_output_is_stdout_wherever_the_config_is() {
+    let dir = project(&CONFIG.replace("\"python/models.py\"", "\"-\""));
+    let elsewhere = temp_dir("config");
+
+    let config = dir.join("marshal.toml");
+    let (code, stdout, stderr) = marshal(&elsewhere, &["-c", config.to_str().unwrap()]);
+
+    assert_eq!(code, 0, "{stderr}");
+    assert!(stdout.contains("class Event"), "{stdout}");
+    assert!(!dir.join("-").exists());
+}
+
+#[test]
+fn config_errors_point_into_the_file() {
+    let dir = project("inputs = [\"src/*.rs\"]\nouptut = \"models.py\"\n");
+
+    let (code, _, stderr) = marshal(&dir, &["check"]);
+
+    assert_eq!(code, 2);
+    assert!(stderr.contains("unknown field `ouptut`"), "{stderr}");
+    assert!(stderr.contains("--> marshal.toml:2:1"), "{stderr}");
+    assert!(stderr.contains("2 | ouptut = \"models.py\""), "{stderr}");
+}
+
+#[test]
+fn output_and_out_dir_conflict() {
+    let dir = project("output = \"a.py\"\nout-dir = \"b\"\n");
+
+    let (code, _, stderr) = marshal(&dir, &["check"]);
+
+    assert_eq!(code, 2);
+    assert!(
+        stderr.contains("`output` and `out-dir` can't both be set"),
+        "{stderr}"
+    );
+}
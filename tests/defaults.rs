@@ -0,0 +1,142 @@
+// `#[serde(default)]` and `#[serde(default = "path")]` fields, with mutable values made per instance
+
+mod common;
+
+use common::*;
+
+const DEFAULTS: &str = r#"
+    #[derive(Serialize, Deserialize)]
+    struct Config {
+        name: String,
+        #[serde(default)]
+        tags: Vec<String>,
+        #[serde(default = "Vec::new")]
+        aliases: Vec<String>,
+        #[serde(default = "HashSet::new")]
+        seen: HashSet<u32>,
+        #[serde(default)]
+        pair: (Vec<u8>, u8),
+        #[serde(default = "default_port")]
+        port: u16,
+        #[serde(default)]
+        retries: u8,
+    }
+"#;
+
+const WITH_PORT: &[&str] = &["--default", "default_port=lambda: 8080"];
+
+#[test]
+fn mutable_defaults_are_made_per_instance() {
+    for mode in MODES {
+        let module = generate(DEFAULTS, &[mode.args(), WITH_PORT].concat());
+
+        assert!(module.contains("field(default_factory=list)"), "{module}");
+        assert!(
+            module.contains("field(default_factory=lambda: (list(), 0))"),
+            "{module}"
+        );
+
+        let output = python(
+            &module,
+            "import models\n\
+             a = models.Config(name='a')\n\
+             b = models.Config(name='b')\n\
+             a.tags.append('x'); a.aliases.append('y'); a.seen.add(1); a.pair[0].append(1)\n\
+             print(b.tags, b.aliases, b.seen, b.pair, b.port, b.retries)\n",
+        );
+
+        assert_eq!(output.trim(), "[] [] set() ([], 0) 8080 0");
+    }
+}
+
+#[test]
+fn missing_fields_get_their_defaults() {
+    for mode in MODES {
+        let module = generate(DEFAULTS, &[mode.args(), WITH_PORT].concat());
+
+        let output = python(
+            &module,
+            &format!(
+                "import models\nprint({}({{'name': 'a'}}))\n",
+                mode.decode("Config")
+            ),
+        );
+
+        assert!(output.contains("tags=[]"), "{output}");
+        assert!(output.contains("pair=([], 0)"), "{output}");
+        assert!(output.contains("port=8080"), "{output}");
+    }
+}
+
+#[test]
+fn set_defaults_follow_frozen_sets() {
+    let module = generate(DEFAULTS, &[WITH_PORT, &["--frozen-sets"]].concat());
+
+    assert!(
+        module.contains("seen: frozenset[Annotated[int, U32]] = field(default_factory=frozenset)"),
+        "{module}"
+    );
+}
+
+#[test]
+fn immutable_tuples_are_plain_defaults() {
+    let module = generate("struct A { #[serde(default)] pair: (u8, String) }", &[]);
+
+    assert!(module.contains(r#"= (0, "")"#), "{module}");
+}
+
+#[test]
+fn unknown_default_functions_are_errors() {
+    let errors = errors(
+        "struct A { #[serde(default = \"make_tags\")] tags: Vec<String> }",
+        &[],
+    );
+
+    assert!(
+        errors.contains("no Python equivalent for default function `make_tags`"),
+        "{errors}"
+    );
+    assert!(
+        errors.contains("--default make_tags=<callable>"),
+        "{errors}"
+    );
+}
+
+#[test]
+fn only_spellings_of_default_are_type_defaults() {
+    let module = generate(
+        r#"
+        struct A {
+            #[serde(default = "Default::default")]
+            a: u8,
+            #[serde(default = "std::default::Default::default")]
+            b: String,
+            #[serde(default = "u32::default")]
+            c: u32,
+            #[serde(default = "<Vec<u8>>::default")]
+            d: Vec<u8>,
+        }
+        "#,
+        &[],
+    );
+
+    for field in [
+        "a: Annotated[int, U8] = 0",
+        "b: str = \"\"",
+        "c: Annotated[int, U32] = 0",
+        "d: list[Annotated[int, U8]] = field(default_factory=list)",
+    ] {
+        assert!(module.contains(field), "{field} in\n{module}");
+    }
+
+    // a function of the user's that happens to be called `default`
+    let errors = errors(
+        "struct A { #[serde(default = \"defaults::default\")] a: u8 }",
+        &[],
+    );
+
+    assert!(
+        errors.contains("no Python equivalent for default function `defaults::default`"),
+        "{errors}"
+    );
+}
@@ -0,0 +1,305 @@
+// `#[derive(Marshal)]` in a crate cargo builds, with the Python it writes
+
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use common::*;
+
+// a crate using `marshal-derive`, with `lib.rs`, built into a target directory the tests share
+fn krate(name: &str, lib: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join("derive")
+        .join(name);
+
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+
+    let derive = Path::new(env!("CARGO_MANIFEST_DIR")).join("marshal-derive");
+
+    std::fs::write(
+        dir.join("Cargo.toml"),
+        format!(
+            "[package]\n\
+             name = {name:?}\n\
+             version = \"0.1.0\"\n\
+             edition = \"2021\"\n\
+             \n\
+             [dependencies]\n\
+             marshal-derive = {{ path = {:?} }}\n\
+             serde = {{ version = \"1\", features = [\"derive\"] }}\n\
+             \n\
+             [workspace]\n",
+            derive.display().to_string()
+        ),
+    )
+    .unwrap();
+
+    // the same versions as this workspace, which are already downloaded
+    std::fs::copy(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.lock"),
+        dir.join("Cargo.lock"),
+    )
+    .unwrap();
+
+    std::fs::write(dir.join("src/lib.rs"), lib).unwrap();
+
+    dir
+}
+
+// runs `cargo <args>` in the crate, with its stderr
+fn cargo(dir: &Path, args: &[&str]) -> (bool, String) {
+    let output = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+        .args(args)
+        .args(["--offline", "--quiet"])
+        .current_dir(dir)
+        .env("MARSHAL_OUT_DIR", "python")
+        .env(
+            "CARGO_TARGET_DIR",
+            Path::new(env!("CARGO_TARGET_TMPDIR")).join("derive/target"),
+        )
+        .output()
+        .unwrap();
+
+    (
+        output.status.success(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+const MODELS: &str = r#"
+use marshal_derive::Marshal;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Marshal)]
+#[marshal(module = "models")]
+pub struct User {
+    pub name: String,
+    #[serde(default)]
+    pub level: Level,
+    pub address: Option<Address>,
+}
+
+#[derive(Serialize, Deserialize, Default, Marshal)]
+#[marshal(module = "models", default)]
+pub enum Level {
+    #[default]
+    Low,
+    High,
+}
+
+#[derive(Serialize, Deserialize, Marshal)]
+#[marshal(module = "models")]
+pub struct Address {
+    pub city: String,
+}
+"#;
+
+#[test]
+fn builds_write_the_module() {
+    let dir = krate("derive_models", MODELS);
+
+    let (built, stderr) = cargo(&dir, &["build"]);
+    assert!(built, "{stderr}");
+
+    let python = dir.join("python");
+    let module = std::fs::read_to_string(python.join("models.py")).unwrap();
+    let runtime = std::fs::read_to_string(python.join("marshal_runtime.py")).unwrap();
+
+    let output = python_files(
+        &[("models.py", &module), ("marshal_runtime.py", &runtime)],
+        "import json, models\n\
+         user = models.User.deserialize({'name': 'a', 'address': {'city': 'b'}})\n\
+         print(json.dumps(models.asdict(user)))\n",
+    );
+
+    assert_eq!(
+        output.trim(),
+        r#"{"name": "a", "level": "Low", "address": {"city": "b"}}"#
+    );
+
+    // the tests are compiled by another rustc, which keeps its own fragments and writes its own module
+    let (built, stderr) = cargo(&dir, &["test", "--no-run"]);
+    assert!(built, "{stderr}");
+
+    assert!(python.join(".marshal/derive_models-lib/models").is_dir());
+    assert!(python.join(".marshal/derive_models-test/models").is_dir());
+    assert_eq!(
+        std::fs::read_to_string(python.join("models.py")).unwrap(),
+        module
+    );
+    assert_eq!(
+        std::fs::read_to_string(python.join("derive_models-test/models.py")).unwrap(),
+        module
+    );
+}
+
+#[test]
+fn test_types_are_only_in_the_tests_module() {
+    let lib = format!(
+        "{MODELS}\n\
+         #[cfg(test)]\n\
+         #[derive(Serialize, Deserialize, Marshal)]\n\
+         #[marshal(module = \"models\")]\n\
+         pub struct Fixture {{\n\
+         \x20   pub user: User,\n\
+         }}\n"
+    );
+    let dir = krate("derive_test_types", &lib);
+
+    // whichever builds last
+    for args in [&["build"][..], &["test", "--no-run"], &["build"]] {
+        let (built, stderr) = cargo(&dir, args);
+        assert!(built, "{stderr}");
+    }
+
+    let python = dir.join("python");
+    let lib = std::fs::read_to_string(python.join("models.py")).unwrap();
+    let test = std::fs::read_to_string(python.join("derive_test_types-test/models.py")).unwrap();
+
+    assert!(!lib.contains("class Fixture"), "{lib}");
+    assert!(test.contains("class Fixture"), "{test}");
+    assert!(python
+        .join("derive_test_types-test/marshal_runtime.py")
+        .exists());
+}
+
+#[test]
+fn the_config_maps_types_and_codecs() {
+    let lib = format!(
+        "{MODELS}\n\
+         pub mod ids {{\n\
+         \x20   #[derive(serde::Serialize, serde::Deserialize)]\n\
+         \x20   pub struct Ulid(pub String);\n\
+         }}\n\
+         \n\
+         pub mod hex {{\n\
+         \x20   pub fn serialize<S: serde::Serializer>(v: &[u8], s: S) -> Result<S::Ok, S::Error> {{ s.serialize_bytes(v) }}\n\
+         \x20   pub fn deserialize<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {{ serde::Deserialize::deserialize(d) }}\n\
+         }}\n\
+         \n\
+         #[derive(Serialize, Deserialize, Marshal)]\n\
+         #[marshal(module = \"models\")]\n\
+         pub struct Key {{\n\
+         \x20   pub id: ids::Ulid,\n\
+         \x20   #[serde(with = \"hex\")]\n\
+         \x20   pub secret: Vec<u8>,\n\
+         }}\n"
+    );
+    let dir = krate("derive_config", &lib);
+
+    // `ids::Ulid` doesn't derive `Marshal`, and there's nothing for `hex`
+    let (built, stderr) = cargo(&dir, &["build"]);
+    assert!(!built);
+    assert!(
+        stderr
+            .contains("no associated item named `__marshal_module_models` found for struct `Ulid`"),
+        "{stderr}"
+    );
+
+    std::fs::write(
+        dir.join("marshal.toml"),
+        "[types]\n\
+         \"ids::Ulid\" = \"str\"\n\
+         \n\
+         [python.codecs]\n\
+         hex = { encode = \"codecs.to_hex\", decode = \"codecs.from_hex\" }\n",
+    )
+    .unwrap();
+
+    let (built, stderr) = cargo(&dir, &["build"]);
+    assert!(built, "{stderr}");
+
+    let module = std::fs::read_to_string(dir.join("python/models.py")).unwrap();
+
+    for field in ["id: str", "import codecs", "codecs.to_hex"] {
+        assert!(module.contains(field), "{field} in\n{module}");
+    }
+
+    // editing it rebuilds the crate
+    std::fs::write(
+        dir.join("marshal.toml"),
+        "[types]\n\
+         \"ids::Ulid\" = \"uuid.UUID\"\n\
+         \n\
+         [python.codecs]\n\
+         hex = { encode = \"codecs.to_hex\", decode = \"codecs.from_hex\" }\n",
+    )
+    .unwrap();
+
+    let (built, stderr) = cargo(&dir, &["build"]);
+    assert!(built, "{stderr}");
+
+    let module = std::fs::read_to_string(dir.join("python/models.py")).unwrap();
+    assert!(module.contains("id: uuid.UUID"), "{module}");
+}
+
+#[test]
+fn codegen_errors_fail_the_build() {
+    // `Level` derives `Default`, but doesn't say so
+    let lib = MODELS.replace(
+        r#"#[marshal(module = "models", default)]"#,
+        r#"#[marshal(module = "models")]"#,
+    );
+    let dir = krate("derive_errors", &lib);
+
+    let (built, stderr) = cargo(&dir, &["build"]);
+
+    assert!(!built);
+    assert!(
+        stderr.contains("`Level` does not derive `Default`"),
+        "{stderr}"
+    );
+    assert!(stderr.contains("#[marshal(default)]"), "{stderr}");
+    assert!(!dir.join("python/models.py").exists());
+}
+
+#[test]
+fn used_types_have_to_derive_marshal_into_the_same_module() {
+    let lib = MODELS.replace(
+        "#[derive(Serialize, Deserialize, Marshal)]\n#[marshal(module = \"models\")]\npub struct Address",
+        "#[derive(Serialize, Deserialize)]\npub struct Address",
+    );
+    let dir = krate("derive_missing", &lib);
+
+    let (built, stderr) = cargo(&dir, &["build"]);
+
+    assert!(!built);
+    assert!(
+        stderr.contains(
+            "no associated item named `__marshal_module_models` found for struct `Address`"
+        ),
+        "{stderr}"
+    );
+
+    let lib = MODELS.replace(
+        "#[marshal(module = \"models\")]\npub struct Address",
+        "#[marshal(module = \"places\")]\npub struct Address",
+    );
+    let dir = krate("derive_elsewhere", &lib);
+
+    let (built, stderr) = cargo(&dir, &["build"]);
+
+    assert!(!built);
+    assert!(
+        stderr.contains(
+            "no associated item named `__marshal_module_models` found for struct `Address`"
+        ),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn attributes_are_checked() {
+    let lib = MODELS.replace(r#"module = "models", default"#, "modul = 1");
+    let dir = krate("derive_attributes", &lib);
+
+    let (built, stderr) = cargo(&dir, &["build"]);
+
+    assert!(!built);
+    assert!(
+        stderr.contains("expected `#[marshal(module = \"...\")]` or `#[marshal(default)]`"),
+        "{stderr}"
+    );
+}
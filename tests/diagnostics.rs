@@ -0,0 +1,58 @@
+// errors reported as diagnostics quoting the source they point at
+
+mod common;
+
+use common::*;
+
+#[test]
+fn diagnostics_quote_the_line_they_point_at() {
+    let errors = errors("struct A {\n    age: u32;\n}\n", &[]);
+
+    assert_eq!(
+        errors,
+        "error: unexpected character `;`\n \
+         --> models.rs:2:13\n  \
+         |\n\
+         2 |     age: u32;\n  \
+         |             ^ not valid here\n\
+         \n\
+         error: aborting due to 1 previous error\n"
+    );
+}
+
+#[test]
+fn carets_underline_the_whole_token() {
+    let errors = errors("struct A { age u32 }", &[]);
+
+    assert!(errors.contains("--> models.rs:1:16"), "{errors}");
+    assert!(
+        errors.contains("  |                ^^^ expected `:`\n"),
+        "{errors}"
+    );
+}
+
+#[test]
+fn carets_line_up_under_tabs() {
+    let errors = errors("\tstruct A { age: u32; }", &[]);
+
+    assert!(
+        errors.contains("1 | \tstruct A { age: u32; }\n  | \t                   ^"),
+        "{errors}"
+    );
+}
+
+#[test]
+fn diagnostics_without_a_span_are_just_the_message() {
+    let (code, _, stderr) = marshal(&temp_dir("no_input"), &["generate", "nothing*.rs"]);
+
+    assert_eq!(code, 2);
+    assert_eq!(stderr, "error: no files match `nothing*.rs`\n");
+}
+
+#[test]
+fn unsupported_types_are_diagnostics_not_panics() {
+    let errors = errors("struct A { f: fn(u8) -> u8 }", &[]);
+
+    assert!(errors.starts_with("error: "), "{errors}");
+    assert!(errors.contains("--> models.rs:1:"), "{errors}");
+}
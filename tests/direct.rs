@@ -0,0 +1,87 @@
+// direct mode's per-type functions produce the same data as the reflecting runtime
+
+mod common;
+
+use common::*;
+
+const MODELS: &str = r#"
+    #[derive(Serialize, Deserialize)]
+    enum Name {
+        Anonymous,
+        FirstLast(String, String),
+        Full {
+            first: String,
+            #[serde(default, skip_serializing_if = "Vec::is_empty")]
+            middle: Vec<String>,
+            last: String,
+        },
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct User {
+        #[serde(rename = "userName")]
+        name: Name,
+        #[serde(default)]
+        age: u32,
+        birthday: (u32, u32, Option<u32>),
+        friends: Vec<Option<Name>>,
+        score: f64,
+        #[serde(skip)]
+        cache: Vec<u8>,
+    }
+"#;
+
+const PAYLOADS: &[&str] = &[
+    r#"{"userName": "Anonymous", "age": 1, "birthday": [1, 2, null], "friends": [], "score": 0.5}"#,
+    r#"{"userName": {"FirstLast": ["a", "b"]}, "age": 0, "birthday": [1, 2, 3], "friends": [null, "Anonymous", {"Full": {"first": "a", "last": "b"}}], "score": 1.0}"#,
+    r#"{"userName": {"Full": {"first": "a", "middle": ["m"], "last": "b"}}, "age": 3, "birthday": [1, 2, 3], "friends": [], "score": -2.5}"#,
+];
+
+#[test]
+fn both_modes_round_trip() {
+    for mode in MODES {
+        assert_round_trips(&generate(MODELS, mode.args()), "User", PAYLOADS, mode);
+    }
+}
+
+#[test]
+fn both_modes_write_the_same_json() {
+    let [reflection, direct] = MODES.map(|mode| {
+        python(
+            &generate(MODELS, mode.args()),
+            &round_trip_script("User", PAYLOADS, mode),
+        )
+    });
+
+    assert_eq!(reflection, direct);
+}
+
+#[test]
+fn direct_mode_does_not_reflect() {
+    let module = generate(MODELS, Mode::Direct.args());
+
+    for reflection in ["ENUM_DATA", "deserialize(", "asdict(", "get_type_hints"] {
+        assert!(!module.contains(reflection), "{reflection} in\n{module}");
+    }
+
+    assert!(module.contains("def encode_User("), "{module}");
+    assert!(module.contains("def decode_User("), "{module}");
+}
+
+#[test]
+fn direct_mode_rejects_bad_data() {
+    let module = generate(MODELS, Mode::Direct.args());
+
+    let output = python(
+        &module,
+        "import models\n\
+         for data in [{'userName': 'Nobody'}, {'userName': 'Anonymous', 'age': 1}]:\n\
+         \x20   try:\n\
+         \x20       models.User.from_dict(data)\n\
+         \x20       print('accepted')\n\
+         \x20   except Exception:\n\
+         \x20       print('rejected')\n",
+    );
+
+    assert_eq!(output.trim(), "rejected\nrejected");
+}
@@ -0,0 +1,74 @@
+// the crate `rustdoc.json` documents, regenerate it with
+//
+// cargo +nightly rustdoc -F extra -- -Z unstable-options --output-format json --document-private-items
+// cp target/doc/fixture.json rustdoc.json
+
+use serde::{Deserialize, Serialize};
+
+mod models;
+
+pub use models::User as Account;
+pub use models::*;
+
+pub type Id = u64;
+pub type Tags = Vec<String>;
+
+#[derive(Serialize, Deserialize)]
+pub struct Order {
+    pub id: Id,
+    pub tags: Tags,
+    pub owner: Account,
+    pub role: Role,
+    #[serde(rename = "total")]
+    pub amount: u32,
+    #[cfg(feature = "never")]
+    pub gone: u8,
+    #[cfg_attr(feature = "extra", serde(rename = "note"))]
+    pub comment: String,
+}
+
+#[cfg(feature = "never")]
+#[derive(Serialize, Deserialize)]
+pub struct Hidden {
+    pub a: u8,
+}
+
+macro_rules! generated {
+    ($name:ident) => {
+        #[derive(Serialize, Deserialize)]
+        pub struct $name {
+            pub value: u8,
+        }
+    };
+}
+
+generated!(Generated);
+
+#[derive(Serialize, Deserialize)]
+pub struct Wrapper<T> {
+    pub value: T,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<Wrapper<T>>,
+    pub next: Option<Box<Page<T>>>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum Either<L, R> {
+    Left(L),
+    Right(R),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Catalog {
+    pub users: Page<Account>,
+    pub ids: Wrapper<Id>,
+    pub pair: Wrapper<(u8, String)>,
+    pub choice: Either<u8, Role>,
+}
+
+pub struct NotSerde {
+    pub a: u8,
+}
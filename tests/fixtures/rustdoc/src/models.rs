@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+pub struct User {
+    pub name: String,
+    #[serde(rename = "mail")]
+    pub email: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub enum Role {
+    #[serde(rename = "admin")]
+    Admin,
+    #[default]
+    Guest,
+}
@@ -0,0 +1,401 @@
+// the native and `syn` frontends read every sample the same way
+
+mod common;
+
+use common::*;
+use marshal::codegen::Options;
+use marshal::Frontend;
+
+// what each frontend has to agree on, a little of everything the others' tests cover
+const SAMPLES: &[(&str, &str)] = &[
+    (
+        "enums and renames",
+        r#"
+            #[derive(Serialize, Deserialize)]
+            enum Name {
+                Anonymous,
+                FirstLast(String, String),
+                Full {
+                    first: String,
+                    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+                    middle: Vec<String>,
+                    last: String,
+                },
+            }
+
+            #[derive(Serialize, Deserialize)]
+            struct User {
+                #[serde(rename = "userName")]
+                name: Name,
+                #[serde(default)]
+                age: u32,
+                birthday: (u32, u32, Option<u32>),
+                friends: Vec<Option<Name>>,
+                tags: BTreeSet<String>,
+                score: f64,
+                #[serde(skip)]
+                cache: Vec<u8>,
+            }
+        "#,
+    ),
+    (
+        "user defaults",
+        r#"
+            #[derive(Serialize, Deserialize, Default)]
+            struct Limits {
+                max: u32,
+                names: Vec<String>,
+            }
+
+            #[derive(Serialize, Deserialize, Default)]
+            enum Level {
+                Low,
+                #[default]
+                Medium,
+                High,
+            }
+
+            #[derive(Serialize, Deserialize)]
+            struct Account {
+                id: u64,
+                #[serde(default)]
+                limits: Limits,
+                #[serde(default)]
+                level: Level,
+            }
+
+            #[derive(Serialize, Deserialize, Default)]
+            #[serde(default)]
+            struct Settings {
+                verbose: bool,
+                level: Level,
+                limits: Limits,
+            }
+        "#,
+    ),
+    (
+        "defaults",
+        r#"
+            #[derive(Serialize, Deserialize)]
+            struct Config {
+                name: String,
+                #[serde(default)]
+                tags: Vec<String>,
+                #[serde(default = "Vec::new")]
+                aliases: Vec<String>,
+                #[serde(default = "HashSet::new")]
+                seen: HashSet<u32>,
+                #[serde(default)]
+                pair: (Vec<u8>, u8),
+                #[serde(default = "default_port")]
+                port: u16,
+                #[serde(default)]
+                retries: u8,
+            }
+        "#,
+    ),
+    (
+        "default spellings",
+        r#"
+            struct A {
+                #[serde(default = "Default::default")]
+                a: u8,
+                #[serde(default = "std::default::Default::default")]
+                b: String,
+                #[serde(default = "u32::default")]
+                c: u32,
+                #[serde(default = "<Vec<u8>>::default")]
+                d: Vec<u8>,
+            }
+        "#,
+    ),
+    (
+        "bytes",
+        r#"
+            #[serde_as]
+            #[derive(Serialize, Deserialize)]
+            struct Blob {
+                data: bytes::Bytes,
+                buf: serde_bytes::ByteBuf,
+                #[serde(with = "serde_bytes")]
+                raw: Vec<u8>,
+                numbers: Vec<u8>,
+                #[serde_as(as = "Base64")]
+                b64: Vec<u8>,
+                #[serde_as(as = "Option<Base64<UrlSafe, Unpadded>>")]
+                url: Option<Vec<u8>>,
+                #[serde_as(as = "Vec<Base64<Standard, Unpadded>>")]
+                chunks: Vec<Vec<u8>>,
+            }
+        "#,
+    ),
+    (
+        "declared bytes",
+        r#"
+            struct Bytes { len: u32 }
+            struct A { data: Bytes, buf: bytes::Bytes }
+        "#,
+    ),
+    (
+        "codecs",
+        r#"
+            #[derive(Serialize, Deserialize)]
+            struct Key {
+                #[serde(with = "crate::hex")]
+                id: Vec<u8>,
+                #[serde(serialize_with = "upper::serialize", deserialize_with = "lower")]
+                name: String,
+                #[serde(default, with = "hex")]
+                backup: Vec<u8>,
+                #[serde(skip_serializing, deserialize_with = "lower")]
+                alias: String,
+            }
+        "#,
+    ),
+    (
+        "skipped codecs",
+        r#"
+            struct A {
+                #[serde(skip_deserializing, default, serialize_with = "upper")]
+                name: String,
+            }
+        "#,
+    ),
+    (
+        "mappings",
+        r#"
+            #[derive(Serialize, Deserialize)]
+            struct Invoice {
+                total: Decimal,
+                lines: smallvec::SmallVec<Line>,
+                notes: SmallVec<Option<String>>,
+            }
+
+            #[derive(Serialize, Deserialize)]
+            struct Line {
+                price: rust_decimal::Decimal,
+                note: Note,
+            }
+
+            #[derive(Serialize, Deserialize)]
+            struct Note { text: String }
+        "#,
+    ),
+    (
+        "builtins",
+        r#"
+            #[derive(Serialize, Deserialize)]
+            struct Event {
+                at: chrono::DateTime<Utc>,
+                local: NaiveDateTime,
+                day: NaiveDate,
+                id: Uuid,
+                link: url::Url,
+                ip: IpAddr,
+                v4: Ipv4Addr,
+                v6: std::net::Ipv6Addr,
+                socket: SocketAddr,
+                path: PathBuf,
+                took: Duration,
+            }
+        "#,
+    ),
+    (
+        "times",
+        r#"
+            #[derive(Serialize, Deserialize)]
+            struct Times {
+                at: DateTime<Utc>,
+                offset: time::OffsetDateTime,
+                created: std::time::SystemTime,
+            }
+        "#,
+    ),
+    (
+        "declared builtins",
+        r#"
+            struct Duration { days: u32 }
+            struct A { took: Duration, id: Uuid }
+        "#,
+    ),
+    (
+        "sets",
+        r#"
+            #[derive(Serialize, Deserialize)]
+            struct Collections {
+                hashed: HashSet<String>,
+                ordered: std::collections::BTreeSet<u8>,
+                indexed: indexmap::IndexSet<i32>,
+                queue: VecDeque<u8>,
+                linked: LinkedList<String>,
+                heap: BinaryHeap<u32>,
+                nested: Vec<HashSet<(u8, String)>>,
+            }
+        "#,
+    ),
+    (
+        "user keys",
+        r#"
+            struct Tag { name: String }
+            struct A { counts: HashMap<Tag, u8> }
+        "#,
+    ),
+    (
+        "maps and tuples",
+        r#"
+            struct Order {
+                id: u64,
+                at: (i32, i32),
+                marker: (),
+                prices: BTreeMap<String, u32>,
+                counts: HashMap<u8, Vec<u64>>,
+                shape: Shape,
+                note: Option<String>,
+            }
+
+            enum Shape {
+                Circle(f64),
+                Square { side: u32 },
+                Empty,
+            }
+        "#,
+    ),
+    (
+        "scalars",
+        r#"
+            #[derive(Serialize, Deserialize)]
+            struct Scalars {
+                initial: char,
+                extra: serde_json::Value,
+                also: Value,
+                big: i128,
+                bigger: u128,
+                offset: isize,
+                len: usize,
+                #[serde(default)]
+                missing: Value,
+            }
+        "#,
+    ),
+    (
+        "double options",
+        r#"
+            #[derive(Serialize, Deserialize)]
+            struct Patch {
+                id: u32,
+                email: Option<String>,
+                #[serde(default, skip_serializing_if = "Option::is_none", with = "::serde_with::rust::double_option")]
+                nick: Option<Option<String>>,
+            }
+        "#,
+    ),
+    (
+        "ranges",
+        r#"
+            #[derive(Serialize, Deserialize)]
+            struct Reading {
+                small: u8,
+                signed: i16,
+                wide: u64,
+                ratio: f32,
+                precise: f64,
+                samples: Vec<Option<i8>>,
+            }
+        "#,
+    ),
+    (
+        "shared pointers",
+        r#"
+            #[derive(Serialize, Deserialize)]
+            struct Folder {
+                name: String,
+                entries: Vec<Entry>,
+            }
+
+            #[derive(Serialize, Deserialize)]
+            enum Entry {
+                File { name: String },
+                Sub { folder: Rc<Folder> },
+                Link { target: Arc<Entry> },
+            }
+        "#,
+    ),
+    (
+        "newtype variants",
+        r#"
+            #[derive(Serialize, Deserialize)]
+            struct Forest {
+                trees: Vec<Tree>,
+            }
+
+            #[derive(Serialize, Deserialize)]
+            enum Tree {
+                Leaf(u32),
+                Node(Box<Tree>, Box<Tree>),
+                Labelled(Box<Tree>),
+            }
+        "#,
+    ),
+    (
+        "cfg",
+        r#"
+            struct A {
+                #[cfg(feature = "extra")]
+                extra: u8,
+                #[cfg_attr(feature = "extra", serde(rename = "renamed"))]
+                plain: u8,
+            }
+        "#,
+    ),
+];
+
+fn samples() -> Vec<(&'static str, &'static str)> {
+    let mut samples = vec![("sample.rs", include_str!("../sample.rs"))];
+    samples.extend_from_slice(SAMPLES);
+    samples
+}
+
+#[test]
+fn both_frontends_declare_the_same_types() {
+    for (name, rust) in samples() {
+        let native = builder(rust, Options::default());
+        let syn = builder(rust, Options::default()).frontend(Frontend::Syn);
+
+        let (native_lexed, syn_lexed) = (native.lex(), syn.lex());
+
+        let native = format!("{:#?}", native.parse(&native_lexed).unwrap());
+        let syn = match syn.parse(&syn_lexed) {
+            Ok(types) => format!("{types:#?}"),
+            Err(errors) => panic!("{name}\n{}", syn.render(&errors)),
+        };
+
+        assert_eq!(native, syn, "{name}\n{rust}");
+    }
+}
+
+#[test]
+fn both_frontends_generate_the_same_code() {
+    for (name, rust) in samples() {
+        for mode in MODES {
+            let generated = |frontend| {
+                let builder = builder(rust, mode.options()).frontend(frontend);
+                let generated = builder.generate();
+
+                let files = generated
+                    .files
+                    .iter()
+                    .map(|file| file.contents.as_str())
+                    .collect::<Vec<_>>()
+                    .concat();
+
+                files + &generated.render_diagnostics()
+            };
+
+            assert_eq!(
+                generated(Frontend::Native),
+                generated(Frontend::Syn),
+                "{name} in {mode:?}\n{rust}"
+            );
+        }
+    }
+}
@@ -0,0 +1,339 @@
+// whole crates, read from their roots through their `mod`s, with the paths in them resolved
+
+mod common;
+
+use common::*;
+use marshal::parser::{DeclaredType, InnerType, StructField, Type};
+use marshal::{Builder, Frontend, Source};
+
+// a crate of `files`, by their paths from its directory, whose root is `src/lib.rs`
+fn krate(name: &str, files: &[(&str, &str)]) -> Builder {
+    let dir = temp_dir(name);
+
+    for (path, text) in files {
+        let path = dir.join(path);
+
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, text).unwrap();
+    }
+
+    Builder::new()
+        .source(Source::from_path(dir.join("src/lib.rs")).unwrap())
+        .frontend(Frontend::Crate)
+}
+
+fn field<'d, 'a>(decl: &'d DeclaredType<'a>, name: &str) -> &'d StructField<'a> {
+    let InnerType::Struct(struc) = &decl.inner else {
+        panic!("`{}` isn't a struct", decl.name);
+    };
+
+    struc
+        .fields
+        .iter()
+        .find(|field| field.name == name)
+        .unwrap()
+}
+
+// the segments of the user type `ty` is
+fn segments<'a>(ty: &Type<'a>) -> Vec<&'a str> {
+    match ty {
+        Type::User(path) => path.segments.clone(),
+        ty => panic!("{ty:?} isn't a user type"),
+    }
+}
+
+#[test]
+fn modules_are_found_where_rustc_looks() {
+    let builder = krate(
+        "modules",
+        &[
+            (
+                "src/lib.rs",
+                "mod a;\n\
+                 mod b;\n\
+                 #[path = \"elsewhere/c.rs\"]\n\
+                 mod c;\n\
+                 mod d {\n\
+                 \x20   mod e;\n\
+                 }\n",
+            ),
+            // next to a file that isn't a `mod.rs`, children are in a directory named after it
+            (
+                "src/a.rs",
+                "mod inner;\n#[derive(Serialize)]\nstruct A { x: u8 }\n",
+            ),
+            (
+                "src/a/inner.rs",
+                "#[derive(Serialize)]\nstruct AInner { x: u8 }\n",
+            ),
+            (
+                "src/b/mod.rs",
+                "mod inner;\n#[derive(Serialize)]\nstruct B { x: u8 }\n",
+            ),
+            (
+                "src/b/inner.rs",
+                "#[derive(Serialize)]\nstruct BInner { x: u8 }\n",
+            ),
+            // and next to one a `#[path]` named
+            (
+                "src/elsewhere/c.rs",
+                "mod inner;\n#[derive(Serialize)]\nstruct C { x: u8 }\n",
+            ),
+            (
+                "src/elsewhere/inner.rs",
+                "#[derive(Serialize)]\nstruct CInner { x: u8 }\n",
+            ),
+            ("src/d/e.rs", "#[derive(Serialize)]\nstruct E { x: u8 }\n"),
+            // not declared anywhere
+            ("src/f.rs", "#[derive(Serialize)]\nstruct F { x: u8 }\n"),
+        ],
+    );
+
+    let lexed = builder.lex();
+    let types = builder.parse(&lexed).unwrap();
+
+    let names: Vec<_> = types.iter().flatten().map(|decl| decl.name).collect();
+    assert_eq!(names, ["A", "AInner", "B", "BInner", "C", "CInner", "E"]);
+
+    let files: Vec<_> = types.iter().flatten().map(|decl| decl.file).collect();
+    assert!(files[3].ends_with("src/b/inner.rs"), "{files:?}");
+    assert!(files[5].ends_with("src/elsewhere/inner.rs"), "{files:?}");
+}
+
+#[test]
+fn paths_resolve_through_uses_globs_super_and_crate() {
+    let builder = krate(
+        "paths",
+        &[
+            ("src/lib.rs", "mod models;\npub mod api;\n"),
+            (
+                "src/models/mod.rs",
+                "mod user;\n\
+                 pub use user::*;\n\
+                 use chrono::Utc;\n\
+                 \n\
+                 #[derive(Serialize)]\n\
+                 pub struct Team {\n\
+                 \x20   members: Vec<User>,\n\
+                 \x20   lead: self::user::User,\n\
+                 \x20   role: crate::api::Role,\n\
+                 \x20   created: Utc,\n\
+                 }\n",
+            ),
+            (
+                "src/models/user.rs",
+                "#[derive(Serialize)]\n\
+                 pub struct User {\n\
+                 \x20   role: super::super::api::Role,\n\
+                 }\n",
+            ),
+            (
+                "src/api.rs",
+                "use crate::models::{User as Member, *};\n\
+                 \n\
+                 #[derive(Serialize)]\n\
+                 pub enum Role {\n\
+                 \x20   Admin,\n\
+                 }\n\
+                 \n\
+                 #[derive(Serialize)]\n\
+                 pub struct Invite {\n\
+                 \x20   from: Member,\n\
+                 \x20   team: Team,\n\
+                 }\n",
+            ),
+        ],
+    );
+
+    let lexed = builder.lex();
+    let types = builder.parse(&lexed).unwrap();
+    let types: Vec<_> = types.iter().flatten().collect();
+
+    let team = types.iter().find(|decl| decl.name == "Team").unwrap();
+
+    let Type::Vec(member) = &field(team, "members").ty else {
+        panic!("{:?}", field(team, "members"));
+    };
+    assert_eq!(segments(member), ["crate", "models", "user", "User"]);
+    assert_eq!(
+        segments(&field(team, "lead").ty),
+        ["crate", "models", "user", "User"]
+    );
+    assert_eq!(segments(&field(team, "role").ty), ["crate", "api", "Role"]);
+    assert_eq!(segments(&field(team, "created").ty), ["chrono", "Utc"]);
+
+    let user = types.iter().find(|decl| decl.name == "User").unwrap();
+    assert_eq!(segments(&field(user, "role").ty), ["crate", "api", "Role"]);
+
+    let invite = types.iter().find(|decl| decl.name == "Invite").unwrap();
+    assert_eq!(
+        segments(&field(invite, "from").ty),
+        ["crate", "models", "user", "User"]
+    );
+    assert_eq!(
+        segments(&field(invite, "team").ty),
+        ["crate", "models", "Team"]
+    );
+}
+
+#[test]
+fn aliases_are_what_they_stand_for() {
+    let builder = krate(
+        "aliases",
+        &[
+            (
+                "src/lib.rs",
+                "pub mod models;\n\
+                 \n\
+                 pub type Id = u64;\n\
+                 pub type Pair<T> = (T, Id);\n\
+                 pub type Members = Vec<models::Member>;\n",
+            ),
+            (
+                "src/models/mod.rs",
+                "use std::collections::HashMap;\n\
+                 \n\
+                 // resolved where they're declared, not where they're used\n\
+                 type Lookup<V> = HashMap<crate::Id, V>;\n\
+                 \n\
+                 #[derive(Serialize, Deserialize)]\n\
+                 pub struct Team {\n\
+                 \x20   id: crate::Id,\n\
+                 \x20   pair: super::Pair<String>,\n\
+                 \x20   members: crate::Members,\n\
+                 \x20   by_id: Lookup<Member>,\n\
+                 }\n\
+                 \n\
+                 #[derive(Serialize, Deserialize)]\n\
+                 pub struct Member {\n\
+                 \x20   name: String,\n\
+                 }\n",
+            ),
+        ],
+    );
+
+    let module = generate_with(builder);
+
+    for field in [
+        "id: Annotated[int, U64]",
+        "pair: tuple[str, Annotated[int, U64]]",
+        "members: list[Member]",
+        "by_id: dict[Annotated[int, U64], Member]",
+    ] {
+        assert!(module.contains(field), "{field} in\n{module}");
+    }
+
+    assert_round_trips(
+        &module,
+        "Team",
+        &[
+            r#"{"id": 1, "pair": ["a", 2], "members": [{"name": "b"}], "by_id": {"3": {"name": "c"}}}"#,
+        ],
+        Mode::Reflection,
+    );
+}
+
+#[test]
+fn aliases_that_cannot_be_lowered_are_errors() {
+    let errors = errors_with(krate(
+        "alias_errors",
+        &[
+            (
+                "src/lib.rs",
+                "mod models;\n\
+                 \n\
+                 pub type Id = Missing;\n\
+                 pub type Itself = Vec<Itself>;\n\
+                 pub type Pair<T> = (T, T);\n",
+            ),
+            (
+                "src/models.rs",
+                "#[derive(Serialize)]\n\
+                 struct Order {\n\
+                 \x20   id: crate::Id,\n\
+                 \x20   itself: crate::Itself,\n\
+                 \x20   pair: crate::Pair,\n\
+                 }\n",
+            ),
+        ],
+    ));
+
+    // each points into the file the alias is declared in
+    assert!(
+        errors.contains("error: cannot find type `Missing` in `crate`"),
+        "{errors}"
+    );
+    assert!(
+        errors.contains("in what `crate::Id` stands for"),
+        "{errors}"
+    );
+    assert!(
+        errors.contains("error: `crate::Itself` stands for itself"),
+        "{errors}"
+    );
+    assert!(
+        errors.contains("error: `crate::Pair` takes 1 type arguments, but 0 were given"),
+        "{errors}"
+    );
+    assert!(errors.contains("src/lib.rs:3:15"), "{errors}");
+    assert!(errors.contains("src/models.rs:5:11"), "{errors}");
+}
+
+#[test]
+fn types_two_globs_import_are_ambiguous() {
+    let errors = errors_with(krate(
+        "ambiguous",
+        &[
+            (
+                "src/lib.rs",
+                "mod a;\n\
+                 mod b;\n\
+                 use a::*;\n\
+                 use b::*;\n\
+                 \n\
+                 #[derive(Serialize)]\n\
+                 struct Order {\n\
+                 \x20   user: User,\n\
+                 }\n",
+            ),
+            ("src/a.rs", "pub struct User;\n"),
+            ("src/b.rs", "pub struct User;\n"),
+        ],
+    ));
+
+    assert!(errors.contains("error: `User` is ambiguous"), "{errors}");
+    assert!(
+        errors.contains("it could be `crate::a::User` or `crate::b::User`"),
+        "{errors}"
+    );
+}
+
+#[test]
+fn modules_without_files_are_errors() {
+    let errors = errors_with(krate(
+        "missing",
+        &[(
+            "src/lib.rs",
+            "mod gone;\n\
+             #[path = \"nowhere.rs\"]\n\
+             mod other;\n\
+             \n\
+             #[derive(Serialize)]\n\
+             struct Order {\n\
+             \x20   id: u8,\n\
+             }\n",
+        )],
+    ));
+
+    assert!(
+        errors.contains("error: cannot find the file of module `gone`"),
+        "{errors}"
+    );
+    assert!(errors.contains("src/gone.rs` or `"), "{errors}");
+    assert!(errors.contains("src/gone/mod.rs`"), "{errors}");
+    assert!(
+        errors.contains("error: cannot find the file of module `other`"),
+        "{errors}"
+    );
+    assert!(errors.contains("src/nowhere.rs`"), "{errors}");
+}
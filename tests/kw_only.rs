@@ -0,0 +1,85 @@
+// `--kw-only`, keeping fields in their declared order
+
+mod common;
+
+use common::*;
+
+const MODELS: &str = r#"
+    #[derive(Serialize, Deserialize)]
+    enum Name {
+        Anonymous,
+        FirstLast(String, String),
+        Full { first: String, #[serde(default)] middle: Vec<String>, last: String },
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct User {
+        #[serde(default)]
+        nick: String,
+        name: Name,
+        age: u8,
+    }
+"#;
+
+const KW_ONLY: &[&str] = &["--kw-only"];
+
+#[test]
+fn fields_keep_their_declared_order() {
+    let module = generate(MODELS, KW_ONLY);
+
+    let nick = module.find("    nick: str").unwrap();
+    let name = module.find("    name: Name").unwrap();
+    let age = module.find("    age: Annotated").unwrap();
+
+    assert!(nick < name && name < age, "{module}");
+    assert!(
+        module.contains("@dataclass(kw_only=True)\nclass User"),
+        "{module}"
+    );
+
+    let module = generate(MODELS, &[]);
+    assert!(
+        module.find("    nick: str").unwrap() > module.find("    age: Annotated").unwrap(),
+        "{module}"
+    );
+}
+
+#[test]
+fn tuple_variants_stay_positional() {
+    for mode in MODES {
+        let module = generate(MODELS, &[mode.args(), KW_ONLY].concat());
+
+        assert!(
+            module.contains("dataclass\nclass FirstLast(TupleVariant)"),
+            "{module}"
+        );
+    }
+}
+
+#[test]
+fn every_variant_round_trips_in_declared_order() {
+    let payloads = [
+        r#"{"nick": "n", "name": "Anonymous", "age": 3}"#,
+        r#"{"nick": "n", "name": {"FirstLast": ["a", "b"]}, "age": 3}"#,
+        r#"{"nick": "n", "name": {"Full": {"first": "a", "middle": ["m"], "last": "b"}}, "age": 3}"#,
+    ];
+
+    for mode in MODES {
+        let module = generate(MODELS, &[mode.args(), KW_ONLY].concat());
+
+        assert_round_trips(&module, "User", &payloads, mode);
+
+        // byte for byte, keys in the declared order
+        let output = python(
+            &module,
+            &format!(
+                "import json, models\nvalue = {}(json.loads(r'''{}'''))\nprint(json.dumps({}(value)))\n",
+                mode.decode("User"),
+                payloads[2],
+                mode.encode("User"),
+            ),
+        );
+
+        assert_eq!(output.trim(), payloads[2]);
+    }
+}
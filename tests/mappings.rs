@@ -0,0 +1,137 @@
+// the user's Python types for Rust types from outside the inputs, with encode and decode hooks
+
+mod common;
+
+use common::*;
+
+const MODELS: &str = r#"
+    #[derive(Serialize, Deserialize)]
+    struct Invoice {
+        total: Decimal,
+        lines: smallvec::SmallVec<Line>,
+        notes: SmallVec<Option<String>>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Line {
+        price: rust_decimal::Decimal,
+        note: Note,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Note { text: String }
+"#;
+
+const MAPPED: &[&str] = &[
+    "--type",
+    "rust_decimal::Decimal=decimal.Decimal",
+    "--encode",
+    "rust_decimal::Decimal=str",
+    "--decode",
+    "rust_decimal::Decimal=decimal.Decimal",
+    "--type",
+    "smallvec::SmallVec<T>=list[T]",
+    // declared, but mapped, so it isn't generated
+    "--type",
+    "Note=dict[str, str]",
+];
+
+#[test]
+fn mapped_types_are_used_and_imported() {
+    let module = generate(MODELS, MAPPED);
+
+    assert!(module.contains("\nimport decimal\n"), "{module}");
+    assert!(module.contains("total: decimal.Decimal"), "{module}");
+    assert!(module.contains("price: decimal.Decimal"), "{module}");
+    assert!(module.contains("notes: list[str | None]"), "{module}");
+    assert!(module.contains("note: dict[str, str]"), "{module}");
+    assert!(!module.contains("class Note"), "{module}");
+}
+
+#[test]
+fn hooks_convert_both_ways() {
+    let payloads = [
+        r#"{"total": "1.50", "lines": [{"price": "0.75", "note": {"text": "a"}}], "notes": [null, "b"]}"#,
+    ];
+
+    for mode in MODES {
+        let module = generate(MODELS, &[mode.args(), MAPPED].concat());
+
+        assert_round_trips(&module, "Invoice", &payloads, mode);
+
+        let output = python(
+            &module,
+            &format!(
+                "import models\n\
+                 value = {}({{'total': '1.50', 'lines': [], 'notes': []}})\n\
+                 print(repr(value.total))\n",
+                mode.decode("Invoice")
+            ),
+        );
+        assert_eq!(output.trim(), "Decimal('1.50')");
+    }
+}
+
+#[test]
+fn reflection_registers_a_codec_per_hooked_class() {
+    let module = generate(MODELS, MAPPED);
+
+    assert_eq!(
+        module.matches("register_codec(decimal.Decimal").count(),
+        1,
+        "{module}"
+    );
+}
+
+#[test]
+fn hooks_on_builtin_types_need_direct_mode() {
+    let rust = "struct A { id: Uuid }";
+    let mapped = [
+        "--type", "Uuid=str", "--encode", "Uuid=str", "--decode", "Uuid=str",
+    ];
+
+    let errors = errors(rust, &mapped);
+    assert!(
+        errors.contains("the mapping for `Uuid` has hooks for the builtin type `str`"),
+        "{errors}"
+    );
+
+    generate(rust, &[Mode::Direct.args(), &mapped].concat());
+}
+
+#[test]
+fn unmapped_external_types_say_how_to_map_them() {
+    let errors = errors("struct A { total: rust_decimal::Decimal }", &[]);
+
+    assert!(
+        errors.contains("cannot find type `rust_decimal::Decimal`"),
+        "{errors}"
+    );
+    assert!(
+        errors.contains("--type rust_decimal::Decimal=<type>"),
+        "{errors}"
+    );
+}
+
+#[test]
+fn paths_naming_another_crate_are_other_types() {
+    let module = generate("struct A { a: std::time::Duration, b: Duration }", MAPPED);
+
+    assert!(module.contains("a: datetime.timedelta"), "{module}");
+    assert!(module.contains("b: datetime.timedelta"), "{module}");
+
+    // the `time` crate's, which serde writes as a tuple, not `{secs, nanos}`
+    let duration = errors("struct A { c: time::Duration }", MAPPED);
+
+    assert!(
+        duration.contains("cannot find type `time::Duration`"),
+        "{duration}"
+    );
+
+    let smallvec = errors("struct A { lines: tinyvec::SmallVec<u8> }", MAPPED);
+
+    assert!(
+        smallvec.contains("cannot find type `tinyvec::SmallVec`"),
+        "{smallvec}"
+    );
+}
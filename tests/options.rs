@@ -0,0 +1,63 @@
+// missing `Option` fields, and `double_option` telling `null` apart from missing
+
+mod common;
+
+use common::*;
+
+const MODELS: &str = r#"
+    #[derive(Serialize, Deserialize)]
+    struct Patch {
+        id: u32,
+        email: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none", with = "::serde_with::rust::double_option")]
+        nick: Option<Option<String>>,
+    }
+"#;
+
+#[test]
+fn missing_options_are_none() {
+    for mode in MODES {
+        let output = python(
+            &generate(MODELS, mode.args()),
+            &format!(
+                "import models\nprint({}({{'id': 1}}).email)\n",
+                mode.decode("Patch")
+            ),
+        );
+
+        assert_eq!(output.trim(), "None");
+    }
+}
+
+#[test]
+fn double_options_tell_null_from_missing() {
+    for mode in MODES {
+        let module = generate(MODELS, mode.args());
+
+        assert!(
+            module.contains("nick: str | None | Absent = ABSENT"),
+            "{module}"
+        );
+
+        let output = python(
+            &module,
+            &format!(
+                "import json, models\n\
+                 for data in [{{'id': 1}}, {{'id': 1, 'nick': None}}, {{'id': 1, 'nick': 'n'}}]:\n\
+                 \x20   value = {}(data)\n\
+                 \x20   print(value.nick is models.ABSENT, json.dumps({}(value), sort_keys=True))\n",
+                mode.decode("Patch"),
+                mode.encode("Patch"),
+            ),
+        );
+
+        assert_eq!(
+            output.lines().collect::<Vec<_>>(),
+            [
+                r#"True {"email": null, "id": 1}"#,
+                r#"False {"email": null, "id": 1, "nick": null}"#,
+                r#"False {"email": null, "id": 1, "nick": "n"}"#,
+            ]
+        );
+    }
+}
@@ -0,0 +1,134 @@
+// integers and floats checked against their Rust type's range, both ways
+
+mod common;
+
+use common::*;
+
+const MODELS: &str = r#"
+    #[derive(Serialize, Deserialize)]
+    struct Reading {
+        small: u8,
+        signed: i16,
+        wide: u64,
+        ratio: f32,
+        precise: f64,
+        samples: Vec<Option<i8>>,
+    }
+"#;
+
+const VALID: &str =
+    "{'small': 255, 'signed': -32768, 'wide': 0, 'ratio': 1.5, 'precise': 2, 'samples': [None, 127]}";
+
+// what happens to each change to `VALID`, deserialized and then serialized
+fn results(mode: Mode, changes: &[&str]) -> Vec<String> {
+    let module = generate(MODELS, mode.args());
+
+    let (decode, encode) = (mode.decode("Reading"), mode.encode("Reading"));
+
+    let changes = changes
+        .iter()
+        .map(|change| format!("    {{{change}}},\n"))
+        .collect::<String>();
+
+    let output = python(
+        &module,
+        &format!(
+            "import models\n\
+             for change in [\n{changes}]:\n\
+             \x20   try:\n\
+             \x20       {decode}({{**{VALID}, **change}})\n\
+             \x20       decoded = 'ok'\n\
+             \x20   except ValueError as err:\n\
+             \x20       decoded = str(err)\n\
+             \x20   value = {decode}({VALID})\n\
+             \x20   for name, field in change.items():\n\
+             \x20       setattr(value, name, field)\n\
+             \x20   try:\n\
+             \x20       {encode}(value)\n\
+             \x20       encoded = 'ok'\n\
+             \x20   except ValueError as err:\n\
+             \x20       encoded = str(err)\n\
+             \x20   print(decoded, '|', encoded)\n"
+        ),
+    );
+
+    output.lines().map(str::to_string).collect()
+}
+
+#[test]
+fn values_in_range_pass() {
+    for mode in MODES {
+        let results = results(
+            mode,
+            &[
+                "",
+                "'small': 0",
+                "'wide': 18446744073709551615",
+                "'ratio': 3.4028234663852886e38",
+                "'precise': -1e308",
+                "'samples': [-128]",
+            ],
+        );
+
+        assert!(
+            results.iter().all(|result| result == "ok | ok"),
+            "{results:?}"
+        );
+    }
+}
+
+#[test]
+fn values_out_of_range_fail_both_ways() {
+    for mode in MODES {
+        let results = results(
+            mode,
+            &[
+                "'small': 256",
+                "'small': -1",
+                "'signed': 32768",
+                "'wide': 18446744073709551616",
+                "'samples': [None, 128]",
+            ],
+        );
+
+        assert_eq!(
+            results,
+            [
+                "256 isn't a valid `u8` | 256 isn't a valid `u8`",
+                "-1 isn't a valid `u8` | -1 isn't a valid `u8`",
+                "32768 isn't a valid `i16` | 32768 isn't a valid `i16`",
+                "18446744073709551616 isn't a valid `u64` | 18446744073709551616 isn't a valid `u64`",
+                "128 isn't a valid `i8` | 128 isn't a valid `i8`",
+            ]
+        );
+    }
+}
+
+#[test]
+fn kinds_are_kept() {
+    for mode in MODES {
+        let results = results(
+            mode,
+            &[
+                "'small': True",
+                "'small': 1.0",
+                "'ratio': 1e39",
+                "'precise': float('inf')",
+                "'precise': float('nan')",
+                "'ratio': '1'",
+            ],
+        );
+
+        assert_eq!(
+            results,
+            [
+                "True isn't a valid `u8` | True isn't a valid `u8`",
+                "1.0 isn't a valid `u8` | 1.0 isn't a valid `u8`",
+                "1e+39 isn't a valid `f32` | 1e+39 isn't a valid `f32`",
+                "inf isn't a valid `f64` | inf isn't a valid `f64`",
+                "nan isn't a valid `f64` | nan isn't a valid `f64`",
+                "'1' isn't a valid `f32` | '1' isn't a valid `f32`",
+            ]
+        );
+    }
+}
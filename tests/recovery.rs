@@ -0,0 +1,80 @@
+// recovering from parse errors, so one run reports every problem
+
+mod common;
+
+use common::*;
+use marshal::diagnostic::Diagnostic;
+use marshal::lexer::Span;
+
+#[test]
+fn every_broken_member_is_reported() {
+    let errors = errors(
+        "struct A {\n    a: u32,\n    b u32,\n    c: ,\n}\n\nenum B {\n    X(u8 u8),\n    Y,\n}\n",
+        &[],
+    );
+
+    assert!(errors.contains("--> models.rs:3:"), "{errors}");
+    assert!(errors.contains("--> models.rs:4:"), "{errors}");
+    assert!(errors.contains("--> models.rs:8:"), "{errors}");
+    assert!(
+        errors.ends_with("error: aborting due to 3 previous errors\n"),
+        "{errors}"
+    );
+}
+
+#[test]
+fn errors_come_in_source_order() {
+    let errors = errors(
+        "struct A { a u32 }\nstruct B { b: }\nstruct C { c u32 }\n",
+        &[],
+    );
+
+    let lines: Vec<_> = ["models.rs:1:", "models.rs:2:", "models.rs:3:"]
+        .iter()
+        .map(|at| {
+            errors
+                .find(at)
+                .unwrap_or_else(|| panic!("{at} in {errors}"))
+        })
+        .collect();
+
+    assert!(lines.is_sorted(), "{errors}");
+}
+
+#[test]
+fn declarations_after_a_broken_one_are_still_parsed() {
+    let errors = errors(
+        "struct A { a u32 }\n#[derive(Serialize)]\nstruct B { b: u32, c u32 }\n",
+        &[],
+    );
+
+    assert!(errors.contains("--> models.rs:3:"), "{errors}");
+    assert!(
+        errors.ends_with("error: aborting due to 2 previous errors\n"),
+        "{errors}"
+    );
+}
+
+#[test]
+fn truncated_input_is_an_error() {
+    for rust in ["struct A {", "struct A { a: u32,", "enum B { X(", "struct"] {
+        let errors = errors(rust, &[]);
+
+        assert!(errors.starts_with("error: "), "{errors}");
+        assert!(errors.contains("models.rs"), "{errors}");
+    }
+}
+
+#[test]
+fn default_spans_render_without_a_snippet() {
+    let rendered = Diagnostic::error("unexpected end of input")
+        .with_file("models.rs")
+        .with_span(Span::default())
+        .with_note("the file ends inside a declaration")
+        .render("struct A {");
+
+    assert_eq!(
+        rendered,
+        "error: unexpected end of input\n --> models.rs\n  = note: the file ends inside a declaration\n"
+    );
+}
@@ -0,0 +1,122 @@
+// types declared after they're used, and types referring to themselves or each other
+
+mod common;
+
+use common::*;
+
+#[test]
+fn types_used_before_they_are_declared_import() {
+    let module = generate(
+        r#"
+        #[derive(Serialize, Deserialize)]
+        struct User { name: Name }
+
+        #[derive(Serialize, Deserialize)]
+        enum Name { Anonymous, FirstLast(String, String) }
+        "#,
+        &[],
+    );
+
+    let name = module.find("Name = ").unwrap();
+    let user = module.find("class User").unwrap();
+    assert!(name < user, "{module}");
+
+    assert_round_trips(
+        &module,
+        "User",
+        &[
+            r#"{"name": "Anonymous"}"#,
+            r#"{"name": {"FirstLast": ["a", "b"]}}"#,
+        ],
+        Mode::Reflection,
+    );
+}
+
+const SELF_REFERENTIAL: &str = r#"
+    #[derive(Serialize, Deserialize)]
+    struct Node {
+        value: u32,
+        next: Option<Box<Node>>,
+        children: Vec<Node>,
+    }
+"#;
+
+#[test]
+fn a_type_refers_to_itself_through_a_box() {
+    let payloads = [
+        r#"{"value": 1, "next": null, "children": []}"#,
+        r#"{"value": 1, "next": {"value": 2, "next": null, "children": [{"value": 3, "next": null, "children": []}]}, "children": []}"#,
+    ];
+
+    for mode in MODES {
+        let module = generate(SELF_REFERENTIAL, mode.args());
+
+        assert!(module.contains("next: Node | None"), "{module}");
+        assert_round_trips(&module, "Node", &payloads, mode);
+    }
+}
+
+const MUTUALLY_RECURSIVE: &str = r#"
+    #[derive(Serialize, Deserialize)]
+    struct Folder {
+        name: String,
+        entries: Vec<Entry>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    enum Entry {
+        File { name: String },
+        Sub { folder: Rc<Folder> },
+        Link { target: Arc<Entry> },
+    }
+"#;
+
+#[test]
+fn types_refer_to_each_other() {
+    let payloads = [
+        r#"{"name": "root", "entries": []}"#,
+        r#"{"name": "root", "entries": [{"File": {"name": "a"}}, {"Sub": {"folder": {"name": "sub", "entries": [{"Link": {"target": {"File": {"name": "b"}}}}]}}}]}"#,
+    ];
+
+    for mode in MODES {
+        let module = generate(MUTUALLY_RECURSIVE, mode.args());
+
+        assert_round_trips(&module, "Folder", &payloads, mode);
+    }
+}
+
+const TREE: &str = r#"
+    #[derive(Serialize, Deserialize)]
+    struct Forest {
+        trees: Vec<Tree>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    enum Tree {
+        Leaf(u32),
+        Node(Box<Tree>, Box<Tree>),
+        Labelled(Box<Tree>),
+    }
+"#;
+
+#[test]
+fn newtype_variants_are_what_they_hold() {
+    // as serde writes them, one field isn't a sequence
+    let payloads = [
+        r#"{"trees": [{"Leaf": 1}, {"Labelled": {"Leaf": 2}}]}"#,
+        r#"{"trees": [{"Node": [{"Labelled": {"Node": [{"Leaf": 1}, {"Leaf": 2}]}}, {"Leaf": 3}]}]}"#,
+    ];
+
+    for mode in MODES {
+        let module = generate(TREE, mode.args());
+
+        assert_round_trips(&module, "Forest", &payloads, mode);
+    }
+}
+
+#[test]
+fn pointers_take_one_type_argument() {
+    let errors = errors("struct A { a: Box<u8, u8> }", &[]);
+
+    assert!(errors.contains("`Box` takes one type argument"), "{errors}");
+}
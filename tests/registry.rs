@@ -0,0 +1,205 @@
+// `tests/fixtures/registry.json`, traced from the types here, and what it lowers to
+
+mod common;
+
+use std::collections::{BTreeMap, HashMap};
+
+use common::*;
+use marshal::{Builder, Frontend, Source};
+use serde::{Deserialize, Serialize};
+use serde_reflection::{Tracer, TracerConfig};
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/registry.json");
+
+// the same, as serde_yaml 0.8 writes it, 0.9 can't
+const YAML: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/registry.yaml");
+
+#[derive(Serialize, Deserialize)]
+struct Id(u64);
+
+#[derive(Serialize, Deserialize)]
+struct Point(i32, i32);
+
+#[derive(Serialize, Deserialize)]
+struct Marker;
+
+#[derive(Serialize, Deserialize)]
+struct Order {
+    id: Id,
+    at: Point,
+    marker: Marker,
+    prices: BTreeMap<String, u32>,
+    counts: HashMap<u8, Vec<Id>>,
+    shape: Shape,
+    #[serde(rename = "note")]
+    comment: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Shape {
+    Circle(f64),
+    Square { side: u32 },
+    Empty,
+}
+
+// the same types, with the newtype and tuple structs written out, as the native frontend reads them
+const NATIVE: &str = r#"
+    struct Order {
+        id: u64,
+        at: (i32, i32),
+        marker: (),
+        prices: BTreeMap<String, u32>,
+        counts: HashMap<u8, Vec<u64>>,
+        shape: Shape,
+        note: Option<String>,
+    }
+
+    enum Shape {
+        Circle(f64),
+        Square { side: u32 },
+        Empty,
+    }
+"#;
+
+fn fixture() -> Builder {
+    Builder::new()
+        .source(Source::from_path(FIXTURE).unwrap())
+        .frontend(Frontend::Registry)
+}
+
+#[test]
+fn the_fixture_is_what_the_tracer_writes() {
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Order>().unwrap();
+    tracer.trace_simple_type::<Shape>().unwrap();
+
+    let traced = serde_json::to_string_pretty(&tracer.registry().unwrap()).unwrap() + "\n";
+
+    assert_eq!(
+        std::fs::read_to_string(FIXTURE).unwrap(),
+        traced,
+        "the types changed, write this to `tests/fixtures/registry.json`"
+    );
+}
+
+#[test]
+fn yaml_registries_are_read_the_same() {
+    for mode in MODES {
+        let yaml = Builder::new()
+            .source(Source::from_path(YAML).unwrap())
+            .frontend(Frontend::Registry)
+            .options(mode.options());
+
+        assert_eq!(
+            generate_with(yaml),
+            generate_with(fixture().options(mode.options()))
+        );
+    }
+}
+
+#[test]
+fn only_structs_with_fields_and_enums_are_declared() {
+    let builder = fixture();
+    let lexed = builder.lex();
+    let types = builder.parse(&lexed).unwrap();
+
+    let names: Vec<_> = types.iter().flatten().map(|decl| decl.name).collect();
+    assert_eq!(names, ["Order", "Shape"]);
+}
+
+#[test]
+fn newtypes_tuples_and_maps_lower_as_native_types_do() {
+    for mode in MODES {
+        let registry = generate_with(fixture().options(mode.options()));
+        let native = generate(NATIVE, mode.args());
+
+        assert_eq!(registry, native);
+    }
+
+    let module = generate_with(fixture());
+
+    for field in [
+        "id: Annotated[int, U64]",
+        "at: tuple[Annotated[int, I32], Annotated[int, I32]]",
+        "marker: None",
+        "prices: dict[str, Annotated[int, U32]]",
+        "counts: dict[Annotated[int, U8], list[Annotated[int, U64]]]",
+        "note: str | None",
+    ] {
+        assert!(module.contains(field), "{field} in\n{module}");
+    }
+}
+
+#[test]
+fn what_serde_writes_round_trips() {
+    let order = Order {
+        id: Id(1),
+        at: Point(-2, 3),
+        marker: Marker,
+        prices: [("a".to_string(), 4), ("b".to_string(), 5)].into(),
+        counts: [(6, vec![Id(7)])].into(),
+        shape: Shape::Square { side: 8 },
+        comment: None,
+    };
+
+    let json = serde_json::to_string(&order).unwrap();
+
+    for mode in MODES {
+        assert_round_trips(
+            &generate_with(fixture().options(mode.options())),
+            "Order",
+            &[&json],
+            mode,
+        );
+    }
+}
+
+#[test]
+fn integer_keys_are_checked() {
+    for mode in MODES {
+        let decode = mode.decode("Order");
+
+        let output = python(
+            &generate_with(fixture().options(mode.options())),
+            &format!(
+                "import models\n\
+                 valid = {{'id': 1, 'at': [0, 0], 'marker': None, 'prices': {{}}, 'counts': {{}}, 'shape': 'Empty', 'note': None}}\n\
+                 for counts in [{{'256': []}}, {{'a': []}}, {{'-1': []}}]:\n\
+                 \x20   try:\n\
+                 \x20       {decode}({{**valid, 'counts': counts}})\n\
+                 \x20       print('accepted')\n\
+                 \x20   except ValueError:\n\
+                 \x20       print('rejected')\n"
+            ),
+        );
+
+        assert_eq!(output.trim(), "rejected\nrejected\nrejected");
+    }
+}
+
+#[test]
+fn newtypes_holding_themselves_are_errors() {
+    let yaml = "\
+---
+Holder:
+  STRUCT:
+    - tree:
+        TYPENAME: Tree
+Tree:
+  NEWTYPESTRUCT:
+    SEQ:
+      TYPENAME: Tree
+";
+
+    let builder = Builder::new()
+        .source(Source::new("registry.yaml", yaml))
+        .frontend(Frontend::Registry);
+
+    let errors = errors_with(builder);
+
+    assert!(errors.contains("error: `Tree` holds itself"), "{errors}");
+    assert!(
+        errors.contains("put it in a struct with named fields"),
+        "{errors}"
+    );
+}
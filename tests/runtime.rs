@@ -0,0 +1,130 @@
+// the ways generated code gets the Python runtime, and the protocol version it checks
+
+mod common;
+
+use common::*;
+
+const MODELS: &str = r#"
+    #[derive(Serialize, Deserialize)]
+    struct Point { x: i32, y: i32 }
+"#;
+
+const SCRIPT: &str =
+    "import models\nprint(models.asdict(models.Point.deserialize({'x': 1, 'y': 2})))\n";
+
+// the protocol version the runtime declares
+fn version() -> u32 {
+    RUNTIME
+        .lines()
+        .find_map(|line| line.strip_prefix("MARSHAL_RUNTIME_VERSION = "))
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+#[test]
+fn inline_runtimes_need_nothing_else() {
+    let module = generate(MODELS, &["--runtime", "inline"]);
+
+    assert!(
+        module.contains(&format!(
+            "# marshal runtime, protocol version {}",
+            version()
+        )),
+        "{module}"
+    );
+    assert!(
+        !module.contains("MARSHAL_RUNTIME_VERSION\") !="),
+        "{module}"
+    );
+
+    let output = python_files(&[("models.py", &module)], SCRIPT);
+    assert_eq!(output.trim(), "{'x': 1, 'y': 2}");
+}
+
+#[test]
+fn written_runtimes_go_next_to_the_module() {
+    let dir = temp_dir("write");
+    std::fs::write(dir.join("models.rs"), MODELS).unwrap();
+
+    let (code, _, stderr) = marshal(
+        &dir,
+        &[
+            "generate",
+            "--runtime",
+            "write",
+            "models.rs",
+            "-o",
+            "models.py",
+        ],
+    );
+    assert_eq!(code, 0, "{stderr}");
+
+    let module = std::fs::read_to_string(dir.join("models.py")).unwrap();
+    let written = std::fs::read_to_string(dir.join("marshal_runtime.py")).unwrap();
+    assert_eq!(written, RUNTIME);
+
+    let output = python_files(
+        &[("models.py", &module), ("marshal_runtime.py", RUNTIME)],
+        SCRIPT,
+    );
+    assert_eq!(output.trim(), "{'x': 1, 'y': 2}");
+
+    // and inside a package, imported relatively
+    let output = python_files(
+        &[
+            ("pkg/__init__.py", ""),
+            ("pkg/models.py", &module),
+            ("pkg/marshal_runtime.py", RUNTIME),
+        ],
+        "from pkg import models\nprint(models.Point.deserialize({'x': 1, 'y': 2}).x)\n",
+    );
+    assert_eq!(output.trim(), "1");
+}
+
+#[test]
+fn imported_runtimes_come_from_the_named_module() {
+    let module = generate(MODELS, &["--runtime", "shared.rt"]);
+
+    assert!(module.contains("from shared.rt import *"), "{module}");
+
+    let output = python_files(
+        &[
+            ("models.py", &module),
+            ("shared/__init__.py", ""),
+            ("shared/rt.py", RUNTIME),
+        ],
+        SCRIPT,
+    );
+    assert_eq!(output.trim(), "{'x': 1, 'y': 2}");
+}
+
+#[test]
+fn stale_runtimes_are_refused() {
+    let module = generate(MODELS, &["--runtime", "write"]);
+
+    let stale = RUNTIME.replace(
+        &format!("MARSHAL_RUNTIME_VERSION = {}", version()),
+        "MARSHAL_RUNTIME_VERSION = 0",
+    );
+    assert_ne!(stale, RUNTIME);
+
+    let output = python_files(
+        &[("models.py", &module), ("marshal_runtime.py", &stale)],
+        "try:\n    import models\nexcept ImportError as err:\n    print(err)\n",
+    );
+
+    assert_eq!(
+        output.trim(),
+        format!(
+            "this module needs marshal runtime protocol version {}, regenerate it or update the runtime",
+            version()
+        )
+    );
+}
+
+#[test]
+fn the_embedded_runtime_matches_its_version() {
+    assert!(include_str!("../src/runtime.rs")
+        .contains(&format!("pub const VERSION: u32 = {};", version())));
+}
@@ -0,0 +1,196 @@
+// rustdoc's JSON for `tests/fixtures/rustdoc`, whose types are found however they're exported
+
+mod common;
+
+use common::*;
+use marshal::codegen::Options;
+use marshal::parser::{DeclaredType, InnerType, SerdeAttribute, StructField, Type};
+use marshal::{Builder, Frontend, Source};
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/rustdoc");
+
+// the fixture's JSON with the feature it was documented with,
+// without `Catalog` and the instances of generic types it uses
+fn fixture(options: Options) -> Builder {
+    Builder::new()
+        .source(Source::from_path(format!("{FIXTURE}/rustdoc.json")).unwrap())
+        .frontend(Frontend::Rustdoc)
+        .features(["extra"])
+        .exclude(&["Catalog"])
+        .unwrap()
+        .options(options)
+}
+
+fn field<'d, 'a>(decl: &'d DeclaredType<'a>, name: &str) -> Option<&'d StructField<'a>> {
+    let InnerType::Struct(struc) = &decl.inner else {
+        panic!("`{}` isn't a struct", decl.name);
+    };
+
+    struc.fields.iter().find(|field| field.name == name)
+}
+
+#[test]
+fn types_are_found_wherever_they_are_exported() {
+    let builder = fixture(Options::default());
+    let lexed = builder.lex();
+    let types = builder.parse(&lexed).unwrap();
+
+    // in file order, `models::User` through its glob and renamed re-exports,
+    // `Generated` from a macro, and neither `Hidden`, which is cfg'd out, nor `NotSerde`
+    let names: Vec<_> = types.iter().flatten().map(|decl| decl.name).collect();
+    assert_eq!(names, ["Order", "Generated", "User", "Role"]);
+
+    let files: Vec<_> = types.iter().flatten().map(|decl| decl.file).collect();
+    assert!(files[0].ends_with("src/lib.rs"), "{files:?}");
+    assert!(files[2].ends_with("src/models.rs"), "{files:?}");
+}
+
+#[test]
+fn paths_and_aliases_are_resolved() {
+    let builder = fixture(Options::default());
+    let lexed = builder.lex();
+    let types = builder.parse(&lexed).unwrap();
+    let order = &types[0][0];
+
+    // `Id` and `Tags` are what they stand for
+    assert!(matches!(field(order, "id").unwrap().ty, Type::Int(_)));
+    assert!(matches!(
+        &field(order, "tags").unwrap().ty,
+        Type::Vec(ty) if matches!(**ty, Type::String)
+    ));
+
+    // `Account` is `models::User`, by where it's declared
+    let Type::User(owner) = &field(order, "owner").unwrap().ty else {
+        panic!("{:?}", field(order, "owner"));
+    };
+    assert_eq!(owner.segments, ["fixture", "models", "User"]);
+
+    // and `gone` is cfg'd out
+    assert!(field(order, "gone").is_none());
+}
+
+#[test]
+fn serde_attributes_are_read_from_the_source() {
+    let renamed = |builder: Builder, name: &str| {
+        let lexed = builder.lex();
+        let types = builder.parse(&lexed).unwrap();
+
+        field(&types[0][0], name)
+            .unwrap()
+            .attrs
+            .iter()
+            .find_map(|attr| match attr {
+                SerdeAttribute::Rename(name) => Some(name.to_string()),
+                _ => None,
+            })
+    };
+
+    assert_eq!(
+        renamed(fixture(Options::default()), "amount").unwrap(),
+        "total"
+    );
+    assert_eq!(
+        renamed(fixture(Options::default()), "comment").unwrap(),
+        "note"
+    );
+
+    // `cfg_attr`s go by the features given, rustdoc doesn't say which it ran with
+    let builder = fixture(Options::default()).features(Vec::<String>::new());
+    assert_eq!(renamed(builder, "comment"), None);
+
+    let builder = fixture(Options::default());
+    let lexed = builder.lex();
+    let types = builder.parse(&lexed).unwrap();
+
+    let InnerType::Enum(role) = &types[0][3].inner else {
+        panic!("`Role` isn't an enum");
+    };
+    assert!(matches!(
+        role.variants[0].attrs[..],
+        [SerdeAttribute::Rename("admin")]
+    ));
+    assert!(role.variants[1].default);
+}
+
+#[test]
+fn generated_modules_round_trip() {
+    let payloads = [
+        r#"{"id": 1, "tags": ["a"], "owner": {"name": "b", "mail": null}, "role": "Guest", "total": 3, "note": ""}"#,
+    ];
+
+    for mode in MODES {
+        let module = generate_with(fixture(mode.options()));
+
+        assert_round_trips(&module, "Order", &payloads, mode);
+    }
+}
+
+#[test]
+fn generic_types_are_declared_for_each_instance() {
+    let builder = || {
+        Builder::new()
+            .source(Source::from_path(format!("{FIXTURE}/rustdoc.json")).unwrap())
+            .frontend(Frontend::Rustdoc)
+            .features(["extra"])
+    };
+
+    let plain = builder().options(Options::default());
+    let lexed = plain.lex();
+    let types = plain.parse(&lexed).unwrap();
+
+    // after the others, as fields use them, and the generic types themselves aren't declared
+    let names: Vec<_> = types.iter().flatten().map(|decl| decl.name).collect();
+    assert_eq!(
+        names,
+        [
+            "Order",
+            "Generated",
+            "Catalog",
+            "User",
+            "Role",
+            "PageUser",
+            "WrapperId",
+            "WrapperTupleU8String",
+            "EitherU8Role",
+            "WrapperUser",
+        ]
+    );
+
+    let payloads = [
+        r#"{"users": {"items": [{"value": {"name": "a", "mail": null}}], "next": {"items": [], "next": null}}, "ids": {"value": 1}, "pair": {"value": [2, "b"]}, "choice": {"Right": "Guest"}}"#,
+        r#"{"users": {"items": [], "next": null}, "ids": {"value": 3}, "pair": {"value": [4, "c"]}, "choice": {"Left": 5}}"#,
+    ];
+
+    for mode in MODES {
+        let module = generate_with(builder().options(mode.options()));
+
+        for class in [
+            "class PageUser:",
+            "next: PageUser | None",
+            "items: list[WrapperUser]",
+        ] {
+            assert!(module.contains(class), "{class} in\n{module}");
+        }
+
+        assert_round_trips(&module, "Catalog", &payloads, mode);
+    }
+}
+
+#[test]
+fn other_format_versions_are_named() {
+    let builder = Builder::new()
+        .source(Source::new(
+            "doc.json",
+            r#"{"format_version": 56, "index": []}"#,
+        ))
+        .frontend(Frontend::Rustdoc)
+        .options(Options::default());
+
+    let errors = errors_with(builder);
+
+    assert!(errors.contains("cannot read rustdoc's output"), "{errors}");
+    assert!(
+        errors.contains("this is rustdoc's format version 56, marshal reads version 57"),
+        "{errors}"
+    );
+}
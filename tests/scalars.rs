@@ -0,0 +1,93 @@
+// `char`, `serde_json::Value`, and 128-bit and pointer-sized integers
+
+mod common;
+
+use common::*;
+
+const MODELS: &str = r#"
+    #[derive(Serialize, Deserialize)]
+    struct Scalars {
+        initial: char,
+        extra: serde_json::Value,
+        also: Value,
+        big: i128,
+        bigger: u128,
+        offset: isize,
+        len: usize,
+        #[serde(default)]
+        missing: Value,
+    }
+"#;
+
+#[test]
+fn scalars_are_annotated() {
+    let module = generate(MODELS, &[]);
+
+    for field in [
+        "initial: Annotated[str, CHAR]",
+        "extra: JsonValue",
+        "also: JsonValue",
+        "big: Annotated[int, I128]",
+        "bigger: Annotated[int, U128]",
+        "offset: Annotated[int, ISIZE]",
+        "len: Annotated[int, USIZE]",
+        "missing: JsonValue = None",
+    ] {
+        assert!(module.contains(field), "{field} in\n{module}");
+    }
+}
+
+#[test]
+fn scalars_round_trip() {
+    let payloads = [
+        r#"{"initial": "é", "extra": {"a": [1, 2.5, null, true, "s", {"b": {}}]}, "also": 3, "big": -170141183460469231731687303715884105728, "bigger": 340282366920938463463374607431768211455, "offset": -1, "len": 18446744073709551615, "missing": null}"#,
+        r#"{"initial": "a", "extra": null, "also": [], "big": 0, "bigger": 0, "offset": 0, "len": 0, "missing": "x"}"#,
+    ];
+
+    for mode in MODES {
+        let module = generate(MODELS, mode.args());
+
+        assert_round_trips(&module, "Scalars", &payloads, mode);
+
+        // compared as text too, they're past what `serde_json::Value` keeps exactly
+        let output = python(&module, &round_trip_script("Scalars", &payloads, mode));
+
+        for number in [
+            "-170141183460469231731687303715884105728",
+            "340282366920938463463374607431768211455",
+            "18446744073709551615",
+        ] {
+            assert!(output.contains(number), "{number} in {output}");
+        }
+    }
+}
+
+#[test]
+fn a_char_is_one_character() {
+    for mode in MODES {
+        let decode = mode.decode("Scalars");
+
+        let output = python(
+            &generate(MODELS, mode.args()),
+            &format!(
+                "import models\n\
+                 data = {{'extra': None, 'also': None, 'big': 0, 'bigger': 0, 'offset': 0, 'len': 0}}\n\
+                 for initial in ['ab', '', 1]:\n\
+                 \x20   try:\n\
+                 \x20       {decode}({{**data, 'initial': initial}})\n\
+                 \x20       print('accepted')\n\
+                 \x20   except ValueError as err:\n\
+                 \x20       print(err)\n"
+            ),
+        );
+
+        assert_eq!(
+            output.lines().collect::<Vec<_>>(),
+            [
+                "'ab' isn't a valid `char`",
+                "'' isn't a valid `char`",
+                "1 isn't a valid `char`",
+            ]
+        );
+    }
+}
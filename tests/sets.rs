@@ -0,0 +1,161 @@
+// sets, maps, and the other collections serde writes as sequences
+
+mod common;
+
+use common::*;
+
+const MODELS: &str = r#"
+    #[derive(Serialize, Deserialize)]
+    struct Collections {
+        hashed: HashSet<String>,
+        ordered: std::collections::BTreeSet<u8>,
+        indexed: indexmap::IndexSet<i32>,
+        queue: VecDeque<u8>,
+        linked: LinkedList<String>,
+        heap: BinaryHeap<u32>,
+        nested: Vec<HashSet<(u8, String)>>,
+    }
+"#;
+
+const FROZEN: &[&str] = &["--frozen-sets"];
+
+#[test]
+fn sets_are_sets_and_sequences_are_lists() {
+    let module = generate(MODELS, &[]);
+
+    for field in [
+        "hashed: set[str]",
+        "ordered: set[Annotated[int, U8]]",
+        "indexed: set[Annotated[int, I32]]",
+        "queue: list[Annotated[int, U8]]",
+        "linked: list[str]",
+        "heap: list[Annotated[int, U32]]",
+        "nested: list[set[tuple[Annotated[int, U8], str]]]",
+    ] {
+        assert!(module.contains(field), "{field} in\n{module}");
+    }
+
+    let module = generate(MODELS, FROZEN);
+
+    assert!(module.contains("hashed: frozenset[str]"), "{module}");
+}
+
+#[test]
+fn collections_round_trip() {
+    // one item per set, Python doesn't keep their order
+    let payloads = [
+        r#"{"hashed": ["a"], "ordered": [1], "indexed": [-1], "queue": [1, 2], "linked": ["a", "b"], "heap": [3, 1], "nested": [[[1, "a"]], []]}"#,
+    ];
+
+    for mode in MODES {
+        for args in [mode.args(), &[mode.args(), FROZEN].concat()] {
+            assert_round_trips(&generate(MODELS, args), "Collections", &payloads, mode);
+        }
+    }
+}
+
+#[test]
+fn sets_of_sets_need_frozen_sets() {
+    let rust = "struct A { groups: HashSet<BTreeSet<u8>> }";
+
+    let errors = errors(rust, &[]);
+
+    assert!(
+        errors.contains("the items of a `set` have to be hashable"),
+        "{errors}"
+    );
+    assert!(errors.contains("--frozen-sets"), "{errors}");
+
+    let module = generate(rust, FROZEN);
+
+    assert!(
+        module.contains("groups: frozenset[frozenset[Annotated[int, U8]]]"),
+        "{module}"
+    );
+}
+
+#[test]
+fn sets_of_dataclasses_are_errors() {
+    let errors = errors(
+        "struct Tag { name: String }\nstruct A { tags: HashSet<Tag> }",
+        FROZEN,
+    );
+
+    assert!(
+        errors.contains("the items of a `frozenset` have to be hashable"),
+        "{errors}"
+    );
+}
+
+#[test]
+fn duplicates_are_dropped_with_a_warning() {
+    for mode in MODES {
+        let decode = mode.decode("A");
+
+        let output = python(
+            &generate("struct A { tags: HashSet<String> }", mode.args()),
+            &format!(
+                "import models, warnings\n\
+                 with warnings.catch_warnings(record=True) as caught:\n\
+                 \x20   warnings.simplefilter('always')\n\
+                 \x20   value = {decode}({{'tags': ['a', 'a', 'b', 'a']}})\n\
+                 print(sorted(value.tags))\n\
+                 print([str(warning.message) for warning in caught])\n"
+            ),
+        );
+
+        assert_eq!(
+            output.lines().collect::<Vec<_>>(),
+            ["['a', 'b']", "['2 duplicate items in a set were dropped']"]
+        );
+    }
+}
+
+#[test]
+fn map_keys_have_to_be_hashable() {
+    let rust = "struct Tag { name: String }\nstruct A { counts: HashMap<Tag, u8> }";
+
+    let errors = errors(rust, &[]);
+
+    assert!(
+        errors.contains("the keys of a `dict` have to be hashable"),
+        "{errors}"
+    );
+
+    let module = generate(
+        "struct A { counts: BTreeMap<BTreeSet<u8>, Vec<u8>> }",
+        FROZEN,
+    );
+
+    assert!(
+        module.contains("counts: dict[frozenset[Annotated[int, U8]], list[Annotated[int, U8]]]"),
+        "{module}"
+    );
+}
+
+#[test]
+fn integer_keys_are_read_from_their_digits() {
+    let rust = r#"
+        struct Note { text: String }
+        struct A { scores: HashMap<i32, Vec<u64>>, notes: BTreeMap<String, Note> }
+    "#;
+
+    let payloads = [
+        r#"{"scores": {"-1": [2], "3": []}, "notes": {"a": {"text": "b"}}}"#,
+        r#"{"scores": {}, "notes": {}}"#,
+    ];
+
+    for mode in MODES {
+        let module = generate(rust, mode.args());
+        let decode = mode.decode("A");
+
+        let output = python(
+            &module,
+            &format!("import models\nprint({decode}({{'scores': {{'-1': [2]}}, 'notes': {{}}}}).scores)\n"),
+        );
+
+        assert_eq!(output.trim(), "{-1: [2]}");
+
+        assert_round_trips(&module, "A", &payloads, mode);
+    }
+}
@@ -0,0 +1,112 @@
+// `#[serde(default)]` on fields of the user's types, and on whole containers
+
+mod common;
+
+use common::*;
+
+const MODELS: &str = r#"
+    #[derive(Serialize, Deserialize, Default)]
+    struct Limits {
+        max: u32,
+        names: Vec<String>,
+    }
+
+    #[derive(Serialize, Deserialize, Default)]
+    enum Level {
+        Low,
+        #[default]
+        Medium,
+        High,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Account {
+        id: u64,
+        #[serde(default)]
+        limits: Limits,
+        #[serde(default)]
+        level: Level,
+    }
+
+    #[derive(Serialize, Deserialize, Default)]
+    #[serde(default)]
+    struct Settings {
+        verbose: bool,
+        level: Level,
+        limits: Limits,
+    }
+"#;
+
+#[test]
+fn user_types_default_to_their_derived_default() {
+    for mode in MODES {
+        let output = python(
+            &generate(MODELS, mode.args()),
+            &format!(
+                "import models\n\
+                 a = {decode}({{'id': 1}})\n\
+                 b = {decode}({{'id': 2}})\n\
+                 a.limits.names.append('x')\n\
+                 print(a.limits.max, b.limits.names, type(b.level).__name__)\n",
+                decode = mode.decode("Account")
+            ),
+        );
+
+        assert_eq!(output.trim(), "0 [] Medium");
+    }
+}
+
+#[test]
+fn container_default_makes_every_field_optional() {
+    for mode in MODES {
+        let output = python(
+            &generate(MODELS, mode.args()),
+            &format!(
+                "import models\n\
+                 s = {}({{'verbose': True}})\n\
+                 print(s.verbose, type(s.level).__name__, s.limits.max)\n",
+                mode.decode("Settings")
+            ),
+        );
+
+        assert_eq!(output.trim(), "True Medium 0");
+    }
+}
+
+#[test]
+fn a_type_without_default_is_an_error() {
+    let errors = errors(
+        r#"
+        #[derive(Serialize, Deserialize)]
+        struct Inner { a: u8 }
+
+        #[derive(Serialize, Deserialize)]
+        struct Outer { #[serde(default)] inner: Inner }
+        "#,
+        &[],
+    );
+
+    assert!(
+        errors.contains("`Inner` does not derive `Default`"),
+        "{errors}"
+    );
+}
+
+#[test]
+fn an_enum_deriving_default_needs_a_default_variant() {
+    let errors = errors(
+        r#"
+        #[derive(Serialize, Deserialize, Default)]
+        enum Level { Low, High }
+
+        #[derive(Serialize, Deserialize)]
+        struct Outer { #[serde(default)] level: Level }
+        "#,
+        &[],
+    );
+
+    assert!(
+        errors.contains("`Level` derives `Default` without a `#[default]` variant"),
+        "{errors}"
+    );
+}